@@ -0,0 +1,190 @@
+//! Render dates in calendars other than the Gregorian one, through ICU4X (with the `icu`
+//! feature).
+//!
+//! Month names are spelled out in English. The Islamic calendar is Umm al-Qura, Saudi Arabia's
+//! official one; where months start on the sighting of the new moon, a date can be a day off.
+
+use chrono::{Datelike, NaiveDate};
+use icu_calendar::buddhist::Buddhist;
+use icu_calendar::hebrew::Hebrew;
+use icu_calendar::islamic::IslamicUmmAlQura;
+use icu_calendar::persian::Persian;
+use icu_calendar::Date;
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// A calendar to render dates in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Calendar {
+    Hebrew,
+    Islamic,
+    Persian,
+    Buddhist,
+}
+
+/// The names `Calendar`s are parsed from
+pub const CALENDARS: &[&str] = &["hebrew", "islamic", "persian", "buddhist"];
+
+/// A calendar name that isn't one of `CALENDARS`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UnknownCalendar {
+    pub name: String,
+}
+
+impl fmt::Display for UnknownCalendar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown calendar {:?} (expected one of {})",
+            self.name,
+            CALENDARS.join(", ")
+        )
+    }
+}
+
+impl Error for UnknownCalendar {}
+
+impl FromStr for Calendar {
+    type Err = UnknownCalendar;
+
+    fn from_str(name: &str) -> Result<Calendar, UnknownCalendar> {
+        match name.to_lowercase().as_str() {
+            "hebrew" => Ok(Calendar::Hebrew),
+            "islamic" => Ok(Calendar::Islamic),
+            "persian" => Ok(Calendar::Persian),
+            "buddhist" => Ok(Calendar::Buddhist),
+            _ => Err(UnknownCalendar {
+                name: name.to_owned(),
+            }),
+        }
+    }
+}
+
+const HEBREW_MONTHS: &[(&str, &str)] = &[
+    ("M01", "Tishrei"),
+    ("M02", "Cheshvan"),
+    ("M03", "Kislev"),
+    ("M04", "Tevet"),
+    ("M05", "Shevat"),
+    ("M05L", "Adar I"),
+    ("M06", "Adar"),
+    ("M06L", "Adar II"),
+    ("M07", "Nisan"),
+    ("M08", "Iyar"),
+    ("M09", "Sivan"),
+    ("M10", "Tammuz"),
+    ("M11", "Av"),
+    ("M12", "Elul"),
+];
+
+const ISLAMIC_MONTHS: &[&str] = &[
+    "Muharram",
+    "Safar",
+    "Rabi' al-Awwal",
+    "Rabi' al-Thani",
+    "Jumada al-Ula",
+    "Jumada al-Akhirah",
+    "Rajab",
+    "Sha'ban",
+    "Ramadan",
+    "Shawwal",
+    "Dhu al-Qa'dah",
+    "Dhu al-Hijjah",
+];
+
+const PERSIAN_MONTHS: &[&str] = &[
+    "Farvardin",
+    "Ordibehesht",
+    "Khordad",
+    "Tir",
+    "Mordad",
+    "Shahrivar",
+    "Mehr",
+    "Aban",
+    "Azar",
+    "Dey",
+    "Bahman",
+    "Esfand",
+];
+
+/// `date` in `calendar`, like `26 Adar 5781` or `10 March 2564 BE`
+pub fn format_date(date: NaiveDate, calendar: Calendar) -> String {
+    let iso = Date::try_new_iso_date(
+        date.year(),
+        u8::try_from(date.month()).expect("months fit in a u8"),
+        u8::try_from(date.day()).expect("days fit in a u8"),
+    )
+    .expect("chrono's dates are valid ISO dates");
+
+    match calendar {
+        Calendar::Hebrew => {
+            let date = iso.to_calendar(Hebrew::new());
+            let code = date.month().code.0;
+            let month = HEBREW_MONTHS
+                .iter()
+                .find(|(month, _)| *month == code.as_str())
+                .map_or(code.as_str(), |(_, name)| name);
+            format!("{} {} {}", date.day_of_month().0, month, date.year().number)
+        }
+        Calendar::Islamic => {
+            let date = iso.to_calendar(IslamicUmmAlQura::new());
+            format!(
+                "{} {} {} AH",
+                date.day_of_month().0,
+                ISLAMIC_MONTHS[date.month().ordinal as usize - 1],
+                date.year().number
+            )
+        }
+        Calendar::Persian => {
+            let date = iso.to_calendar(Persian);
+            format!(
+                "{} {} {} AP",
+                date.day_of_month().0,
+                PERSIAN_MONTHS[date.month().ordinal as usize - 1],
+                date.year().number
+            )
+        }
+        Calendar::Buddhist => {
+            let year = iso.to_calendar(Buddhist).year().number;
+            format!("{} {} BE", date.format("%-d %B"), year)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_date() {
+        let date = NaiveDate::from_ymd(2021, 3, 10);
+        assert_eq!(format_date(date, Calendar::Hebrew), "26 Adar 5781");
+        assert_eq!(format_date(date, Calendar::Islamic), "26 Rajab 1442 AH");
+        assert_eq!(format_date(date, Calendar::Persian), "20 Esfand 1399 AP");
+        assert_eq!(format_date(date, Calendar::Buddhist), "10 March 2564 BE");
+
+        // A leap year, with two Adars
+        assert_eq!(
+            format_date(NaiveDate::from_ymd(2022, 3, 10), Calendar::Hebrew),
+            "7 Adar II 5782"
+        );
+        assert_eq!(
+            format_date(NaiveDate::from_ymd(2022, 2, 10), Calendar::Hebrew),
+            "9 Adar I 5782"
+        );
+    }
+
+    #[test]
+    fn test_parse_calendar() {
+        assert_eq!("Hebrew".parse(), Ok(Calendar::Hebrew));
+        assert_eq!(
+            "mayan".parse::<Calendar>().unwrap_err().name,
+            "mayan".to_owned()
+        );
+    }
+}
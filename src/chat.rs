@@ -0,0 +1,53 @@
+// Markup that chat apps render as a time in each reader's own timezone, for posting a converted
+// time without having to say which zone it's in.
+
+use chrono::{DateTime, TimeZone};
+
+/// Slack's date token for `instant`: the date and time in the reader's zone, or `fallback` where
+/// Slack can't render the token (e.g. in notifications)
+pub fn slack<T: TimeZone>(instant: &DateTime<T>, fallback: &str) -> String {
+    let fallback = fallback
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    format!(
+        "<!date^{}^{{date_short_pretty}} at {{time}}|{}>",
+        instant.timestamp(),
+        fallback
+    )
+}
+
+/// The styles Discord renders timestamps in, from `t` (`16:20`) to `F` (`Wednesday, 10 March 2021
+/// 16:20`), and `R` for relative (`in 2 hours`)
+pub const DISCORD_STYLES: &[&str] = &["t", "T", "d", "D", "f", "F", "R"];
+
+/// Discord's timestamp markup for `instant`, shown in `style` (one of `DISCORD_STYLES`)
+pub fn discord<T: TimeZone>(instant: &DateTime<T>, style: &str) -> String {
+    format!("<t:{}:{}>", instant.timestamp(), style)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    #[test]
+    fn test_slack() {
+        let instant = Utc.ymd(2021, 3, 10).and_hms(9, 30, 0);
+        assert_eq!(
+            slack(&instant, "2021-03-10 09:30 <UTC>"),
+            "<!date^1615368600^{date_short_pretty} at {time}|2021-03-10 09:30 &lt;UTC&gt;>"
+        );
+    }
+
+    #[test]
+    fn test_discord() {
+        let instant = Utc.ymd(2021, 3, 10).and_hms(9, 30, 0);
+        assert_eq!(discord(&instant, "F"), "<t:1615368600:F>");
+        assert_eq!(
+            discord(&instant.with_timezone(&chrono_tz::Asia::Tokyo), "R"),
+            "<t:1615368600:R>"
+        );
+    }
+}
@@ -0,0 +1,328 @@
+// `tz config check`: find the mistakes in a config file that loading it would otherwise skip over
+// with a warning (or not notice at all), pointing at the line each one is on.
+
+use tz::format::check_format;
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::config::{Alias, Config, Group, Hours, Style};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// 1-based, if the problem could be traced to a line
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Every problem with the config in `contents`, in the order they appear in the file
+pub fn check(contents: &str) -> Vec<Problem> {
+    let config = match Config::from_toml(contents) {
+        Ok(config) => config,
+        Err(e) => {
+            return vec![Problem {
+                line: e.line_col().map(|(line, _)| line + 1),
+                message: e.to_string(),
+            }]
+        }
+    };
+
+    let mut checker = Checker {
+        contents,
+        config: &config,
+        problems: Vec::new(),
+    };
+
+    checker.zones("", &config.favorites, "favorite");
+    for (name, alias) in &config.aliases {
+        let zone = alias.zone().to_owned();
+        checker.zones("aliases", Some(&zone), &format!("alias {:?}", name));
+        if let Alias::Styled { style, .. } = alias {
+            checker.style("aliases", name, style);
+        }
+    }
+    checker.aliases();
+    checker.groups("groups", &config.groups);
+    checker.labels("label", &config.labels);
+    checker.hours("business_hours", config.business_hours.as_ref());
+
+    for (name, profile) in &config.profiles {
+        let table = format!("profiles.{}", name);
+        if let Some(favorites) = &profile.favorites {
+            checker.zones(&table, favorites, "favorite");
+        }
+        checker.groups(&format!("{}.groups", table), &profile.groups);
+        checker.labels(&format!("{}.label", table), &profile.labels);
+        checker.hours(
+            &format!("{}.business_hours", table),
+            profile.business_hours.as_ref(),
+        );
+    }
+
+    if let Some(format) = &config.format {
+        if let Err(e) = check_format(format) {
+            checker.problem("", Find::Key("format"), e.to_string());
+        }
+    }
+
+    if let Err(e) = config.locale() {
+        checker.problem("", Find::Key("locale"), e);
+    }
+
+    let mut problems = checker.problems;
+    problems.sort_by_key(|problem| problem.line);
+    problems
+}
+
+struct Checker<'a> {
+    contents: &'a str,
+    config: &'a Config,
+    problems: Vec<Problem>,
+}
+
+impl Checker<'_> {
+    fn problem(&mut self, table: &str, find: Find, message: String) {
+        let line = locate(self.contents, table, find);
+        self.problems.push(Problem { line, message });
+    }
+
+    fn zones<'z>(&mut self, table: &str, zones: impl IntoIterator<Item = &'z String>, kind: &str) {
+        for zone in zones {
+            if self.config.resolve_zone(zone).is_none() {
+                let message = format!("unknown timezone {:?} (in {})", zone, kind_of(table, kind));
+                self.problem(table, Find::Value(zone), message);
+            }
+        }
+    }
+
+    // Like `zones`, for zones used as keys
+    fn zone_keys<'z>(
+        &mut self,
+        table: &str,
+        zones: impl IntoIterator<Item = &'z String>,
+        kind: &str,
+    ) {
+        for zone in zones {
+            if self.config.resolve_zone(zone).is_none() {
+                let message = format!("unknown timezone {:?} (in {})", zone, kind_of(table, kind));
+                self.problem(table, Find::Key(zone), message);
+            }
+        }
+    }
+
+    // Keys that name the same zone, of which only one is used. One that's the zone's own name is
+    // used over the rest without a fuss, since it's clearly the one meant.
+    fn duplicate_zone_keys<'z>(
+        &mut self,
+        table: &str,
+        zones: impl IntoIterator<Item = &'z String>,
+        kind: &str,
+    ) {
+        for (tz, keys) in self.config.zone_keys(zones) {
+            if keys[0] == tz.name() {
+                continue;
+            }
+            for key in &keys[1..] {
+                let message = format!(
+                    "duplicate timezone {:?} (same as {:?}, in {})",
+                    key,
+                    keys[0],
+                    kind_of(table, kind)
+                );
+                self.problem(table, Find::Key(key), message);
+            }
+        }
+    }
+
+    fn groups(&mut self, table: &str, groups: &HashMap<String, Group>) {
+        for (name, group) in groups {
+            self.zones(table, group.zones(), &format!("group {:?}", name));
+            if let Group::Styled { style, .. } = group {
+                self.style(table, name, style);
+            }
+        }
+    }
+
+    fn style(&mut self, table: &str, name: &str, style: &Style) {
+        if let Some(format) = &style.format {
+            if let Err(e) = check_format(format) {
+                self.problem(table, Find::Key(name), format!("{} (for {:?})", e, name));
+            }
+        }
+    }
+
+    fn labels(&mut self, table: &str, labels: &HashMap<String, String>) {
+        self.zone_keys(table, labels.keys(), "label");
+        self.duplicate_zone_keys(table, labels.keys(), "label");
+    }
+
+    fn hours(&mut self, table: &str, hours: Option<&Hours>) {
+        if let Some(Hours::PerZone(zones)) = hours {
+            let zones = || zones.keys().filter(|zone| *zone != "default");
+            self.zone_keys(table, zones(), "business hours");
+            self.duplicate_zone_keys(table, zones(), "business hours");
+        }
+    }
+
+    // Aliases are looked up case-insensitively, so of two that only differ in case (or an alias
+    // with the same name as a group, which it hides) only one can be used
+    fn aliases(&mut self) {
+        let mut aliases = self
+            .config
+            .aliases
+            .keys()
+            .map(|alias| (locate(self.contents, "aliases", Find::Key(alias)), alias))
+            .collect::<Vec<_>>();
+        aliases.sort();
+
+        for (i, &(line, alias)) in aliases.iter().enumerate() {
+            let duplicate = aliases[..i]
+                .iter()
+                .find(|(_, other)| other.eq_ignore_ascii_case(alias));
+
+            if let Some((_, other)) = duplicate {
+                let message = format!("duplicate alias {:?} (same as {:?})", alias, other);
+                self.problems.push(Problem { line, message });
+            }
+
+            if let Some(group) = self
+                .config
+                .groups
+                .keys()
+                .find(|group| group.eq_ignore_ascii_case(alias))
+            {
+                let message = format!("alias {:?} has the same name as group {:?}", alias, group);
+                self.problems.push(Problem { line, message });
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Find<'a> {
+    /// A key, bare or quoted, at the start of a line
+    Key(&'a str),
+    /// A quoted string value anywhere in a line
+    Value(&'a str),
+}
+
+impl Find<'_> {
+    fn matches(self, line: &str) -> bool {
+        match self {
+            Find::Key(key) => {
+                let line = line.trim_start();
+                let rest = line
+                    .strip_prefix(&format!("{:?}", key))
+                    .or_else(|| line.strip_prefix(key));
+                rest.is_some_and(|rest| rest.trim_start().starts_with('='))
+            }
+            Find::Value(value) => line.contains(&format!("{:?}", value)),
+        }
+    }
+}
+
+fn kind_of(table: &str, kind: &str) -> String {
+    match table.strip_prefix("profiles.") {
+        Some(profile) => {
+            let profile = profile.split('.').next().unwrap_or(profile);
+            format!("{} for profile {:?}", kind, profile)
+        }
+        None => kind.to_owned(),
+    }
+}
+
+// The first line in `table` ("" for the top level) that `find` matches. Tables are tracked by
+// their headers, which is all the config format needs: none of its tables are inline.
+fn locate(contents: &str, table: &str, find: Find) -> Option<usize> {
+    let mut current = "";
+
+    for (i, line) in contents.lines().enumerate() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with('[') {
+            current = trimmed
+                .trim_start_matches('[')
+                .split(']')
+                .next()
+                .unwrap_or("")
+                .trim();
+        } else if current == table && !trimmed.starts_with('#') && find.matches(line) {
+            return Some(i + 1);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let problems = check(
+            r#"
+            favorites = ["Europe/London", "Nowhere/Special"]
+            format = "%H:%Q"
+
+            [aliases]
+            office = "America/Los_Angeles"
+            Office = "Europe/Berlin"
+            team = "Asia/Tokyo"
+            client = { zone = "Asia/Tokyo", format = "%Q" }
+
+            [label]
+            Nowhere = "Nobody"
+            kolkata = "Alice"
+            "asia/kolkata" = "Bob"
+            tokyo = "Carol"
+            "Asia/Tokyo" = "Dave"
+
+            [groups]
+            team = [
+                "office",
+                "Atlantis/Capital",
+            ]
+
+            [profiles.acme]
+            favorites = ["Nowhere/Else"]
+
+            [profiles.acme.business_hours]
+            london = "08:00-16:00"
+            "europe/london" = "09:00-17:00"
+            "#,
+        );
+
+        let problems = problems.iter().map(Problem::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            problems,
+            vec![
+                "line 2: unknown timezone \"Nowhere/Special\" (in favorite)",
+                "line 3: invalid format string \"%H:%Q\"",
+                "line 7: duplicate alias \"Office\" (same as \"office\")",
+                "line 8: alias \"team\" has the same name as group \"team\"",
+                "line 9: invalid format string \"%Q\" (for \"client\")",
+                "line 12: unknown timezone \"Nowhere\" (in label)",
+                "line 13: duplicate timezone \"kolkata\" (same as \"asia/kolkata\", in label)",
+                "line 21: unknown timezone \"Atlantis/Capital\" (in group \"team\")",
+                "line 25: unknown timezone \"Nowhere/Else\" (in favorite for profile \"acme\")",
+                "line 28: duplicate timezone \"london\" (same as \"europe/london\", in business hours for profile \"acme\")",
+            ]
+        );
+
+        assert_eq!(check("favorites = [\"Asia/Kolkata\"]\n"), vec![]);
+
+        let problems = check("clock = \"12h\"\nfavorites = [\n");
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].line, Some(3));
+    }
+}
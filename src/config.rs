@@ -0,0 +1,807 @@
+// User configuration, read from `config.toml` in the platform's config directory for tz
+// (`$XDG_CONFIG_HOME/tz` on Linux, `~/Library/Application Support/tz` on macOS, and
+// `%APPDATA%\tz\config` on Windows) or from the file passed to --config. Every key is optional,
+// e.g.:
+//
+//     favorites = ["America/New_York", "Europe/London", "Asia/Kolkata"]
+//     format = "%a %d %b %H:%M %Z"
+//     clock = "12h"
+//     show_zone = "both"          # or "abbreviation" (the default), "offset", or "none"
+//     color = "never"             # or "auto" (the default), or "always"
+//     day_delta = true            # note when a converted time falls on another day
+//     locale = "fr_FR"            # for times printed with the format, not JSON or other data
+//     business_hours = "09:00-17:00"
+//     holidays = ["2021-12-24", "2021-12-31"]   # skipped by `next business day` and the like
+//
+//     [aliases]
+//     office = "America/Los_Angeles"
+//     client = { zone = "Asia/Tokyo", format = "%a %H:%M" }
+//
+//     [groups]
+//     team = ["America/Los_Angeles", "Europe/Berlin", "Asia/Kolkata"]
+//     standup = { zones = ["office", "Europe/Berlin"], template = "markdown" }
+//
+//     [label]
+//     "America/New_York" = "Alice (NYC)"
+//
+//     # Business hours can also be set per zone, with `default` covering every other zone:
+//     # [business_hours]
+//     # default = "09:00-17:00"
+//     # "Asia/Kolkata" = "10:00-19:00"
+//
+//     # Selected with --profile, replacing the favorites and business hours and adding to the
+//     # groups and labels above
+//     [profiles.acme]
+//     favorites = ["Australia/Sydney", "Asia/Singapore"]
+//     business_hours = "07:00-15:00"
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, Weekday};
+use chrono_tz::Tz;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use tz::format::{parse_locale, Locale};
+use tz::parse::BusinessDays;
+use tz::resolve::parse_tz;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Zones to convert to when no --to is given
+    pub favorites: Vec<String>,
+    /// Short names that can be used anywhere a timezone is expected
+    pub aliases: HashMap<String, Alias>,
+    /// Named sets of zones that can be converted to all at once
+    pub groups: HashMap<String, Group>,
+    /// Names to show instead of a zone's own name in multi-zone output
+    #[serde(rename = "label")]
+    pub labels: HashMap<String, String>,
+    /// strftime-style format used to print converted times
+    pub format: Option<String>,
+    pub clock: Clock,
+    /// How the zone is shown at the end of converted times, unless a format is given
+    pub show_zone: ShowZone,
+    pub color: Color,
+    /// Whether to note that a converted time is on a different day than the one converted
+    pub day_delta: bool,
+    /// Locale for day and month names and `%c`/`%x`/`%X`, e.g. `fr_FR`. Defaults to the one in
+    /// the environment. Only times printed with the format are localized. Output for other programs
+    /// to read, like JSON, cron schedules, and `tz sort --utc`'s prefix, is the same in every locale.
+    pub locale: Option<String>,
+    /// Times outside these hours (or outside the work week) are marked in multi-zone output
+    pub business_hours: Option<Hours>,
+    /// The first of the five days in the work week
+    pub week_start: WeekStart,
+    /// Days off, which aren't counted as business days when parsing `+3 business days` and the
+    /// like
+    pub holidays: Vec<Holiday>,
+    pub profiles: HashMap<String, Profile>,
+    /// The labels by the zone they're for, resolved when the config is loaded
+    #[serde(skip)]
+    zone_labels: HashMap<Tz, String>,
+    /// Per-zone business hours by the zone they're for, resolved along with the labels
+    #[serde(skip)]
+    zone_hours: HashMap<Tz, BusinessHours>,
+}
+
+/// Settings that replace (or add to) the top-level ones when a profile is selected
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub favorites: Option<Vec<String>>,
+    pub groups: HashMap<String, Group>,
+    #[serde(rename = "label")]
+    pub labels: HashMap<String, String>,
+    pub business_hours: Option<Hours>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Clock {
+    #[serde(rename = "12h")]
+    TwelveHour,
+    #[default]
+    #[serde(rename = "24h")]
+    TwentyFourHour,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShowZone {
+    #[default]
+    Abbreviation,
+    Offset,
+    Both,
+    None,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Color {
+    /// Only when printing to a terminal, and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    pub fn enabled(self) -> bool {
+        match self {
+            Color::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+            Color::Always => true,
+            Color::Never => false,
+        }
+    }
+}
+
+/// A zone, optionally with how conversions to it are shown
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Alias {
+    Zone(String),
+    Styled {
+        zone: String,
+        #[serde(flatten)]
+        style: Style,
+    },
+}
+
+impl Alias {
+    pub fn zone(&self) -> &str {
+        match self {
+            Alias::Zone(zone) | Alias::Styled { zone, .. } => zone,
+        }
+    }
+}
+
+/// Zones, optionally with how conversions to them are shown
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Group {
+    Zones(Vec<String>),
+    Styled {
+        zones: Vec<String>,
+        #[serde(flatten)]
+        style: Style,
+    },
+}
+
+impl Group {
+    pub fn zones(&self) -> &[String] {
+        match self {
+            Group::Zones(zones) | Group::Styled { zones, .. } => zones,
+        }
+    }
+}
+
+/// How conversions to an alias or group are shown, in place of the top-level settings
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Style {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Template::is_plain")]
+    pub template: Template,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Template {
+    /// A line per zone
+    #[default]
+    Plain,
+    /// A Markdown table with a row per zone
+    Markdown,
+}
+
+impl Template {
+    fn is_plain(&self) -> bool {
+        *self == Template::Plain
+    }
+}
+
+/// Business hours for every zone, or for each zone separately
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Hours {
+    Everywhere(BusinessHours),
+    PerZone(HashMap<String, BusinessHours>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct WeekStart(pub Weekday);
+
+impl Default for WeekStart {
+    fn default() -> WeekStart {
+        WeekStart(Weekday::Mon)
+    }
+}
+
+impl WeekStart {
+    /// Whether `day` falls within the five-day work week starting on this day
+    pub fn is_workday(&self, day: Weekday) -> bool {
+        (7 + day.num_days_from_monday() - self.0.num_days_from_monday()) % 7 < 5
+    }
+}
+
+impl TryFrom<String> for WeekStart {
+    type Error = String;
+
+    fn try_from(day: String) -> Result<WeekStart, String> {
+        day.parse().map(WeekStart).map_err(|_| {
+            format!(
+                "invalid week_start {:?}, expected a day like \"monday\"",
+                day
+            )
+        })
+    }
+}
+
+/// A day off, like `2021-12-24`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Holiday(pub NaiveDate);
+
+impl TryFrom<String> for Holiday {
+    type Error = String;
+
+    fn try_from(day: String) -> Result<Holiday, String> {
+        NaiveDate::parse_from_str(&day, "%Y-%m-%d")
+            .map(Holiday)
+            .map_err(|_| {
+                format!(
+                    "invalid holiday {:?}, expected a date like \"2021-12-24\"",
+                    day
+                )
+            })
+    }
+}
+
+/// A daily span of working hours like `09:00-17:00`, which may wrap past midnight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct BusinessHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl BusinessHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+impl FromStr for BusinessHours {
+    type Err = String;
+
+    fn from_str(hours: &str) -> Result<BusinessHours, String> {
+        let invalid = || {
+            format!(
+                "invalid business hours {:?}, expected e.g. \"09:00-17:00\"",
+                hours
+            )
+        };
+        let (start, end) = hours.split_once('-').ok_or_else(invalid)?;
+        let time =
+            |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| invalid());
+
+        Ok(BusinessHours {
+            start: time(start)?,
+            end: time(end)?,
+        })
+    }
+}
+
+impl TryFrom<String> for BusinessHours {
+    type Error = String;
+
+    fn try_from(hours: String) -> Result<BusinessHours, String> {
+        hours.parse()
+    }
+}
+
+impl fmt::Display for BusinessHours {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Write(PathBuf, io::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "Couldn't read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "Invalid config {}: {}", path.display(), e),
+            ConfigError::Write(path, e) => write!(f, "Couldn't write {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl Config {
+    /// Where the config file lives by default, if a home directory can be found
+    pub fn path() -> Option<PathBuf> {
+        Some(project_dirs()?.config_dir().join("config.toml"))
+    }
+
+    /// Where tz keeps files it can regenerate, like its history
+    pub fn cache_dir() -> Option<PathBuf> {
+        Some(project_dirs()?.cache_dir().to_owned())
+    }
+
+    /// Load the config file from its default location, falling back to the defaults if there
+    /// isn't one
+    pub fn load() -> Result<Config, ConfigError> {
+        match Config::path() {
+            Some(path) if path.exists() => Config::read(&path),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    /// Load a config file that's expected to exist
+    pub fn read(path: &Path) -> Result<Config, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        Config::from_toml(&contents).map_err(|e| ConfigError::Parse(path.to_owned(), e))
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(contents).map(Config::resolved)
+    }
+
+    // Resolve the zones that settings are keyed by once, rather than on every lookup
+    fn resolved(mut self) -> Config {
+        self.zone_labels = self
+            .zone_keys(self.labels.keys())
+            .into_iter()
+            .map(|(tz, keys)| (tz, self.labels[keys[0]].clone()))
+            .collect();
+        self.zone_hours = match &self.business_hours {
+            Some(Hours::PerZone(zones)) => self
+                .zone_keys(zones.keys().filter(|zone| *zone != "default"))
+                .into_iter()
+                .map(|(tz, keys)| (tz, zones[keys[0]]))
+                .collect(),
+            _ => HashMap::new(),
+        };
+        self
+    }
+
+    /// Apply the named profile on top of the top-level settings
+    pub fn with_profile(mut self, name: &str) -> Result<Config, String> {
+        let profile = match self.profiles.remove(name) {
+            Some(profile) => profile,
+            None => {
+                let mut names = self.profiles.keys().map(String::as_str).collect::<Vec<_>>();
+                names.sort_unstable();
+                return Err(match names[..] {
+                    [] => format!("Unknown profile {:?} (no profiles are configured)", name),
+                    _ => format!(
+                        "Unknown profile {:?} (expected one of {})",
+                        name,
+                        names.join(", ")
+                    ),
+                });
+            }
+        };
+
+        if let Some(favorites) = profile.favorites {
+            self.favorites = favorites;
+        }
+        self.groups.extend(profile.groups);
+        self.labels.extend(profile.labels);
+        self.business_hours = profile.business_hours.or(self.business_hours);
+
+        Ok(self.resolved())
+    }
+
+    /// Resolve a timezone name given on the command line, checking aliases first
+    pub fn resolve_zone(&self, name: &str) -> Option<Tz> {
+        let alias = self
+            .aliases
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+            .map(|(_, alias)| alias.zone());
+
+        parse_tz(alias.unwrap_or(name))
+    }
+
+    /// Resolve a timezone argument that may also name a group of zones. Group members that
+    /// don't resolve are skipped with a warning.
+    pub fn resolve_zones(&self, name: &str) -> Option<Vec<Tz>> {
+        let group = self
+            .groups
+            .iter()
+            .find(|(group, _)| group.eq_ignore_ascii_case(name));
+
+        match group {
+            Some((name, group)) => {
+                Some(self.resolve_all(group.zones(), &format!("{} timezone", name)))
+            }
+            None => self.resolve_zone(name).map(|tz| vec![tz]),
+        }
+    }
+
+    /// How conversions to the alias or group called `name` are shown, if it has its own style
+    pub fn style(&self, name: &str) -> Option<&Style> {
+        let group = self
+            .groups
+            .iter()
+            .find(|(group, _)| group.eq_ignore_ascii_case(name))
+            .map(|(_, group)| group);
+
+        match group {
+            Some(Group::Styled { style, .. }) => Some(style),
+            Some(Group::Zones(_)) => None,
+            None => self
+                .aliases
+                .iter()
+                .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+                .and_then(|(_, alias)| match alias {
+                    Alias::Styled { style, .. } => Some(style),
+                    Alias::Zone(_) => None,
+                }),
+        }
+    }
+
+    /// How `tz` should be named in multi-zone output
+    pub fn label(&self, tz: Tz) -> &str {
+        self.zone_labels.get(&tz).map_or(tz.name(), String::as_str)
+    }
+
+    /// The zones that `keys` name, each with the keys that name it. When there are several, the
+    /// one that's the zone's own name comes first and the rest are sorted, and the first is the
+    /// one that's used.
+    pub fn zone_keys<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a String>,
+    ) -> HashMap<Tz, Vec<&'a str>> {
+        let mut zones: HashMap<Tz, Vec<&str>> = HashMap::new();
+        for key in keys {
+            if let Some(tz) = self.resolve_zone(key) {
+                zones.entry(tz).or_default().push(key);
+            }
+        }
+        for (tz, keys) in &mut zones {
+            keys.sort_by_key(|key| (*key != tz.name(), *key));
+        }
+        zones
+    }
+
+    /// The business hours that apply in `tz`, if any are configured for it
+    pub fn business_hours(&self, tz: Tz) -> Option<BusinessHours> {
+        match self.business_hours.as_ref()? {
+            Hours::Everywhere(hours) => Some(*hours),
+            Hours::PerZone(zones) => self
+                .zone_hours
+                .get(&tz)
+                .or_else(|| zones.get("default"))
+                .copied(),
+        }
+    }
+
+    /// Whether `datetime` is during business hours on a workday where it is, or `None` if no
+    /// business hours apply to its zone
+    pub fn is_business_time(&self, datetime: &DateTime<Tz>) -> Option<bool> {
+        let hours = self.business_hours(datetime.timezone())?;
+        Some(self.week_start.is_workday(datetime.weekday()) && hours.contains(datetime.time()))
+    }
+
+    /// Which days `next business day` and the like count
+    pub fn business_days(&self) -> BusinessDays {
+        BusinessDays {
+            week_start: self.week_start.0,
+            holidays: self.holidays.iter().map(|holiday| holiday.0).collect(),
+        }
+    }
+
+    /// The favorite zones that resolve, warning about any that don't
+    pub fn favorite_zones(&self) -> Vec<Tz> {
+        self.resolve_all(&self.favorites, "favorite timezone")
+    }
+
+    fn resolve_all(&self, names: &[String], kind: &str) -> Vec<Tz> {
+        names
+            .iter()
+            .filter_map(|name| {
+                let zone = self.resolve_zone(name);
+                if zone.is_none() {
+                    eprintln!("warning: Ignoring unknown {} {:?}", kind, name);
+                }
+                zone
+            })
+            .collect()
+    }
+
+    /// The locale converted times are printed in with the format: the configured one, or else the
+    /// first of `LC_ALL`, `LC_TIME`, and `LANG` that's set (ignored if tz doesn't know it)
+    pub fn locale(&self) -> Result<Locale, String> {
+        if let Some(locale) = &self.locale {
+            return parse_locale(locale).ok_or_else(|| format!("Unknown locale {:?}", locale));
+        }
+
+        let from_env = ["LC_ALL", "LC_TIME", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|locale| !locale.is_empty());
+
+        Ok(from_env
+            .and_then(|locale| parse_locale(&locale))
+            .unwrap_or(Locale::POSIX))
+    }
+
+    /// The format converted times are printed with
+    pub fn output_format(&self) -> String {
+        if let Some(format) = &self.format {
+            return format.clone();
+        }
+
+        let time = match self.clock {
+            Clock::TwelveHour => "%Y-%m-%d %I:%M:%S %p",
+            Clock::TwentyFourHour => "%Y-%m-%d %H:%M:%S",
+        };
+
+        let zone = match self.show_zone {
+            ShowZone::Abbreviation => " %Z",
+            ShowZone::Offset => " %:z",
+            ShowZone::Both => " %Z (%:z)",
+            ShowZone::None => "",
+        };
+
+        format!("{}{}", time, zone)
+    }
+}
+
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "tz")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use tz::format::DEFAULT_FORMAT;
+
+    use super::*;
+
+    #[test]
+    fn test_from_toml() {
+        let config = Config::from_toml(
+            r#"
+            clock = "12h"
+            favorites = ["Europe/London", "office", "Nowhere/Special"]
+            business_hours = "22:00-06:00"
+
+            [aliases]
+            office = "America/Los_Angeles"
+
+            [groups]
+            team = ["Asia/Tokyo", "office"]
+
+            [label]
+            "Europe/London" = "Bob (London)"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.clock, Clock::TwelveHour);
+        assert_eq!(config.output_format(), "%Y-%m-%d %I:%M:%S %p %Z");
+        assert_eq!(
+            config.resolve_zone("Office"),
+            Some(Tz::America__Los_Angeles)
+        );
+        assert_eq!(config.resolve_zone("kolkata"), Some(Tz::Asia__Kolkata));
+        assert_eq!(
+            config.favorite_zones(),
+            vec![Tz::Europe__London, Tz::America__Los_Angeles]
+        );
+
+        assert_eq!(
+            config.resolve_zones("Team"),
+            Some(vec![Tz::Asia__Tokyo, Tz::America__Los_Angeles])
+        );
+        assert_eq!(config.resolve_zones("tokyo"), Some(vec![Tz::Asia__Tokyo]));
+        assert_eq!(config.resolve_zones("Nowhere/Special"), None);
+
+        assert_eq!(config.label(Tz::Europe__London), "Bob (London)");
+        assert_eq!(config.label(Tz::Asia__Kolkata), "Asia/Kolkata");
+
+        // Of labels for the same zone, its own name wins, or else the first in order
+        let labeled = Config::from_toml(
+            "[label]\ntokyo = \"A\"\n\"Asia/Tokyo\" = \"B\"\nlondon = \"D\"\n\"europe/london\" = \"C\"",
+        )
+        .unwrap();
+        assert_eq!(labeled.label(Tz::Asia__Tokyo), "B");
+        assert_eq!(labeled.label(Tz::Europe__London), "C");
+
+        let hours = config.business_hours(Tz::Asia__Tokyo).unwrap();
+        assert_eq!(hours.to_string(), "22:00-06:00");
+        assert!(hours.contains(NaiveTime::from_hms(23, 0, 0)));
+        assert!(hours.contains(NaiveTime::from_hms(5, 59, 0)));
+        assert!(!hours.contains(NaiveTime::from_hms(12, 0, 0)));
+
+        assert_eq!(config.style("team"), None);
+        assert!(config.with_profile("work").is_err());
+
+        assert_eq!(
+            Config::from_toml("locale = \"fr_FR.UTF-8\"")
+                .unwrap()
+                .locale(),
+            Ok(Locale::fr_FR)
+        );
+        assert!(Config::from_toml("locale = \"xx\"")
+            .unwrap()
+            .locale()
+            .is_err());
+
+        let config = Config::from_toml("show_zone = \"both\"\ncolor = \"never\"").unwrap();
+        assert_eq!(config.output_format(), "%Y-%m-%d %H:%M:%S %Z (%:z)");
+        assert!(!config.color.enabled());
+        let config = Config::from_toml("show_zone = \"none\"\nformat = \"%H:%M\"").unwrap();
+        assert_eq!(config.output_format(), "%H:%M");
+
+        assert!(Config::from_toml("clock = \"13h\"").is_err());
+        assert!(Config::from_toml("color = \"sometimes\"").is_err());
+        assert!(Config::from_toml("business_hours = \"9 to 5\"").is_err());
+        assert_eq!(Config::default().output_format(), DEFAULT_FORMAT);
+    }
+
+    #[test]
+    fn test_style() {
+        let config = Config::from_toml(
+            r#"
+            [aliases]
+            office = "America/Los_Angeles"
+            client = { zone = "Asia/Tokyo", format = "%H:%M" }
+
+            [groups]
+            standup = { zones = ["office", "client"], template = "markdown" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.resolve_zone("client"), Some(Tz::Asia__Tokyo));
+        assert_eq!(
+            config.resolve_zones("standup"),
+            Some(vec![Tz::America__Los_Angeles, Tz::Asia__Tokyo])
+        );
+
+        let style = config.style("Client").unwrap();
+        assert_eq!(style.format.as_deref(), Some("%H:%M"));
+        assert_eq!(style.template, Template::Plain);
+        assert_eq!(
+            config.style("standup").unwrap().template,
+            Template::Markdown
+        );
+        assert_eq!(config.style("office"), None);
+        assert_eq!(config.style("tokyo"), None);
+
+        assert!(Config::from_toml("[groups]\nteam = { zones = [], template = \"html\" }").is_err());
+    }
+
+    #[test]
+    fn test_business_hours() {
+        let config = Config::from_toml(
+            r#"
+            week_start = "sunday"
+            holidays = ["2021-07-11"]
+
+            [business_hours]
+            default = "09:00-17:00"
+            kolkata = "10:00-19:00"
+            tokyo = "08:00-16:00"
+            "Asia/Tokyo" = "07:00-15:00"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.business_hours(Tz::UTC).unwrap().to_string(),
+            "09:00-17:00"
+        );
+        assert_eq!(
+            config
+                .business_hours(Tz::Asia__Kolkata)
+                .unwrap()
+                .to_string(),
+            "10:00-19:00"
+        );
+        assert_eq!(
+            config.business_hours(Tz::Asia__Tokyo).unwrap().to_string(),
+            "07:00-15:00"
+        );
+
+        // 2021-07-09 was a Friday, which is outside a Sunday–Thursday work week
+        let at = |tz: Tz, day, hour| tz.ymd(2021, 7, day).and_hms(hour, 0, 0);
+        assert_eq!(
+            config.is_business_time(&at(Tz::Asia__Kolkata, 8, 18)),
+            Some(true)
+        );
+        assert_eq!(config.is_business_time(&at(Tz::UTC, 8, 18)), Some(false));
+        assert_eq!(config.is_business_time(&at(Tz::UTC, 9, 12)), Some(false));
+        assert_eq!(config.is_business_time(&at(Tz::UTC, 11, 12)), Some(true));
+        assert_eq!(
+            Config::default().is_business_time(&at(Tz::UTC, 9, 12)),
+            None
+        );
+
+        assert!(WeekStart::default().is_workday(Weekday::Fri));
+        assert!(!WeekStart::default().is_workday(Weekday::Sat));
+        assert!(Config::from_toml("week_start = \"someday\"").is_err());
+
+        // Thursday the 8th, then past the weekend and the holiday on Sunday
+        let business_days = config.business_days();
+        assert_eq!(
+            business_days.add(NaiveDate::from_ymd(2021, 7, 8), 1),
+            NaiveDate::from_ymd(2021, 7, 12)
+        );
+        assert!(Config::from_toml("holidays = [\"24 December\"]").is_err());
+    }
+
+    #[test]
+    fn test_with_profile() {
+        let config = || {
+            Config::from_toml(
+                r#"
+                favorites = ["Europe/London"]
+                business_hours = "09:00-17:00"
+
+                [groups]
+                team = ["Asia/Tokyo"]
+
+                [profiles.acme]
+                favorites = ["Australia/Sydney"]
+
+                [profiles.acme.groups]
+                team = ["Asia/Singapore"]
+
+                [profiles.acme.label]
+                "Australia/Sydney" = "Acme HQ"
+
+                [profiles.personal]
+                business_hours = "10:00-14:00"
+                "#,
+            )
+            .unwrap()
+        };
+
+        let acme = config().with_profile("acme").unwrap();
+        assert_eq!(acme.favorite_zones(), vec![Tz::Australia__Sydney]);
+        assert_eq!(acme.resolve_zones("team"), Some(vec![Tz::Asia__Singapore]));
+        assert_eq!(acme.label(Tz::Australia__Sydney), "Acme HQ");
+        assert_eq!(acme.business_hours, config().business_hours);
+
+        let personal = config().with_profile("personal").unwrap();
+        assert_eq!(personal.favorite_zones(), vec![Tz::Europe__London]);
+        assert_eq!(
+            personal.business_hours(Tz::UTC).unwrap().to_string(),
+            "10:00-14:00"
+        );
+
+        assert_eq!(
+            config().with_profile("work").unwrap_err(),
+            "Unknown profile \"work\" (expected one of acme, personal)"
+        );
+    }
+}
@@ -0,0 +1,701 @@
+use chrono::offset::{Offset, TimeZone};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+
+use chrono_tz::{OffsetName, Tz};
+use lru::LruCache;
+use rayon::prelude::*;
+use regex::{Captures, Regex};
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+
+use crate::parse::parse_datetime_in_tz;
+
+/// Express `dt` in another timezone; the instant in time is unchanged
+pub fn convert<T: TimeZone>(dt: DateTime<Tz>, to_timezone: T) -> DateTime<T> {
+    dt.with_timezone(&to_timezone)
+}
+
+/// The last year the bundled tzdata has transitions for. Later instants keep whatever offset each
+/// zone had at the end of it, which for zones with daylight saving time is standard time.
+pub const LAST_TRANSITION_YEAR: i32 = 2099;
+
+// How far ahead a conversion can be before it's only a projection. Governments usually announce
+// daylight saving changes a few months ahead, and rarely more than a year.
+const PROJECTION_HORIZON_DAYS: i64 = 365;
+
+// The same for zones whose offsets have changed within `VOLATILE_YEARS`, which are more likely to
+// change again
+const VOLATILE_HORIZON_DAYS: i64 = 183;
+const VOLATILE_YEARS: i32 = 20;
+
+/// Why a conversion might not hold by the time its instant comes around
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Projection {
+    /// Far enough ahead that the zone's rules may well change before then
+    CurrentRules,
+    /// More than about six months ahead in a zone whose offsets have changed recently, and have
+    /// only been as they are now since the year `since`
+    VolatileRules { since: i32 },
+    /// After `LAST_TRANSITION_YEAR`, in a zone that observes daylight saving time, which isn't
+    /// applied
+    PastTransitions,
+}
+
+/// Whether `dt` is far enough from `now` that its offset is a projection of current rules
+pub fn projection(dt: &DateTime<Tz>, now: DateTime<Utc>) -> Option<Projection> {
+    if dt.year() > LAST_TRANSITION_YEAR {
+        let tz = dt.timezone();
+        let offset = |month| {
+            tz.ymd(LAST_TRANSITION_YEAR, month, 1)
+                .and_hms(12, 0, 0)
+                .offset()
+                .fix()
+        };
+        if offset(1) != offset(7) {
+            return Some(Projection::PastTransitions);
+        }
+    }
+
+    let ahead = dt.with_timezone(&Utc) - now;
+    if ahead > Duration::days(VOLATILE_HORIZON_DAYS) {
+        if let Some(since) = current_offsets_since(dt.timezone(), now.year()) {
+            return Some(Projection::VolatileRules { since });
+        }
+    }
+
+    if ahead > Duration::days(PROJECTION_HORIZON_DAYS) {
+        Some(Projection::CurrentRules)
+    } else {
+        None
+    }
+}
+
+/// The offset `dt` is at, if it's the local mean time tzdata gives a zone before its first
+/// transition: the sun's time where the zone is named for, to the second. Later offsets that are
+/// off the hour by minutes and seconds, like Madras time or Amsterdam's +00:20, were legal
+/// standard times and aren't included.
+pub fn mean_time_offset(dt: &DateTime<Tz>) -> Option<FixedOffset> {
+    if dt.offset().abbreviation() == "LMT" {
+        Some(dt.offset().fix())
+    } else {
+        None
+    }
+}
+
+/// The first full year `tz` has had the offsets through the year it has in `year`, if that's
+/// within `VOLATILE_YEARS`: the year after daylight saving time was started, stopped, or moved by
+/// weeks, or the standard offset changed (or the year itself, for changes on January 1st)
+pub fn current_offsets_since(tz: Tz, year: i32) -> Option<i32> {
+    // Mid-month, which the usual "last Sunday" or "second Sunday" rules never move across
+    let offsets = |year| {
+        (1..=12)
+            .map(|month| {
+                let utc = NaiveDate::from_ymd(year, month, 15).and_hms(12, 0, 0);
+                tz.offset_from_utc_datetime(&utc).fix()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    (year - VOLATILE_YEARS + 1..=year)
+        .rev()
+        .find(|&year| offsets(year) != offsets(year - 1))
+}
+
+/// Parse each date/time string in `from` and convert it to `to`, spreading the work across
+/// threads. Results are returned in input order, with `None` for strings that failed to parse.
+pub fn convert_batch<S: AsRef<str> + Sync>(
+    inputs: &[S],
+    from: Tz,
+    to: Tz,
+) -> Vec<Option<DateTime<Tz>>> {
+    inputs
+        .par_iter()
+        .map(|input| parse_datetime_in_tz(from, input.as_ref()).map(|dt| convert(dt, to)))
+        .collect()
+}
+
+// Large enough to cover the distinct timestamps in a typical window of log lines, small
+// enough that the memory it holds doesn't matter
+const CACHE_CAPACITY: usize = 4096;
+
+/// Remembers the most recent conversions so inputs that recur (repeated log timestamps, CSV
+/// headers) are only parsed and converted once.
+pub struct ConversionCache {
+    entries: LruCache<(String, Tz, Tz), Option<DateTime<Tz>>>,
+}
+
+impl ConversionCache {
+    /// Create a cache holding up to `capacity` conversions (at least one)
+    pub fn new(capacity: usize) -> ConversionCache {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+
+        ConversionCache {
+            entries: LruCache::new(capacity),
+        }
+    }
+
+    /// Like `convert_batch`, but inputs already in the cache skip parsing and conversion, and
+    /// each distinct input that isn't is only converted once.
+    pub fn convert_batch<S: AsRef<str>>(
+        &mut self,
+        inputs: &[S],
+        from: Tz,
+        to: Tz,
+    ) -> Vec<Option<DateTime<Tz>>> {
+        let mut known = HashMap::new();
+        let mut misses = Vec::new();
+
+        for input in inputs.iter().map(AsRef::as_ref) {
+            if known.contains_key(input) {
+                continue;
+            }
+
+            let cached = self.entries.get(&(input.to_owned(), from, to)).copied();
+            if cached.is_none() {
+                misses.push(input);
+            }
+            known.insert(input, cached.flatten());
+        }
+
+        for (&input, result) in misses.iter().zip(convert_batch(&misses, from, to)) {
+            known.insert(input, result);
+            self.entries.put((input.to_owned(), from, to), result);
+        }
+
+        inputs.iter().map(|input| known[input.as_ref()]).collect()
+    }
+}
+
+impl Default for ConversionCache {
+    fn default() -> ConversionCache {
+        ConversionCache::new(CACHE_CAPACITY)
+    }
+}
+
+/// Finds timestamps embedded in arbitrary text and rewrites them from one timezone to another,
+/// keeping their original shape. It knows the stamps common in logs:
+///
+/// - ISO 8601 / RFC 3339: `2021-01-01 08:08`, `2021-01-01T08:08:08.123Z`, `2021-01-01 08:08:08,123`
+///   (Python's `logging` and log4j), `2021-01-01T08:08:08+05:30`
+/// - syslog: `Jan  1 08:08:08`
+/// - Apache/nginx access logs: `[01/Jan/2021:08:08:08 +0000]`
+/// - java.util.logging: `Jan 1, 2021 8:08:08 AM`
+/// - Org mode: `<2021-01-01 Fri 08:08>`, `[2021-01-01 Fri 08:08-09:00]`, `<2021-01-01 Fri 08:08 +1w>`
+///
+/// Stamps with an offset (or `Z`) are converted from that offset rather than the source
+/// timezone. Syslog's stamps don't have a year, so they're taken to be from the last 12 months.
+pub struct TimestampRewriter {
+    detectors: Vec<(Regex, Shape)>,
+    from: Tz,
+    to: Tz,
+    // When the rewriter was made, in the source timezone
+    now: NaiveDateTime,
+    // Rewritten text for recently seen timestamps
+    cache: RefCell<LruCache<(Shape, String), String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Shape {
+    Iso,
+    Syslog,
+    CommonLog,
+    JavaUtilLogging,
+    Org,
+}
+
+const MONTHS: &str = "Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec";
+const COMMON_LOG_FORMAT: &str = "%d/%b/%Y:%H:%M:%S %z";
+
+impl TimestampRewriter {
+    pub fn new(from: Tz, to: Tz) -> TimestampRewriter {
+        TimestampRewriter::at(from, to, Utc::now().with_timezone(&from).naive_local())
+    }
+
+    fn at(from: Tz, to: Tz, now: NaiveDateTime) -> TimestampRewriter {
+        let month = format!("(?:{})", MONTHS);
+        let detectors = vec![
+            (
+                r"\b(\d{4}-\d{2}-\d{2})([ T])(\d{2}:\d{2})(:\d{2})?([.,]\d{1,9})?(Z|[+-]\d{2}:?\d{2})?\b"
+                    .to_owned(),
+                Shape::Iso,
+            ),
+            (
+                format!(r"\b({}) ([ \d]\d) (\d{{2}}:\d{{2}}:\d{{2}})\b", month),
+                Shape::Syslog,
+            ),
+            (
+                r"\[(\d{2}/[A-Z][a-z]{2}/\d{4}:\d{2}:\d{2}:\d{2} [+-]\d{4})\]".to_owned(),
+                Shape::CommonLog,
+            ),
+            (
+                format!(
+                    r"\b{} \d{{1,2}}, \d{{4}} \d{{1,2}}:\d{{2}}:\d{{2}} [AP]M\b",
+                    month
+                ),
+                Shape::JavaUtilLogging,
+            ),
+            (
+                r"([<\[])(\d{4}-\d{2}-\d{2}) [^\s\d>\]]+ (\d{1,2}:\d{2})(?:-(\d{1,2}:\d{2}))?([^>\]\n]*)([>\]])"
+                    .to_owned(),
+                Shape::Org,
+            ),
+        ];
+        let detectors = detectors
+            .into_iter()
+            .map(|(pattern, shape)| (Regex::new(&pattern).unwrap(), shape))
+            .collect();
+
+        let cache = RefCell::new(LruCache::new(NonZeroUsize::new(CACHE_CAPACITY).unwrap()));
+
+        TimestampRewriter {
+            detectors,
+            from,
+            to,
+            now,
+            cache,
+        }
+    }
+
+    /// Rewrite every timestamp in `text`. Anything that isn't a valid local time in the
+    /// source timezone is left untouched. Stamps with an offset (or `Z`) get the target's offset
+    /// in its place, and keep `Z` only if the target is at UTC then.
+    pub fn rewrite<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+
+        // No shape's rewritten stamps look like another's, so they can be rewritten in turn
+        for (pattern, shape) in &self.detectors {
+            let rewritten = match pattern.replace_all(&text, |caps: &Captures| {
+                let mut cache = self.cache.borrow_mut();
+                let key = (*shape, caps[0].to_owned());
+                if let Some(rewritten) = cache.get(&key) {
+                    return rewritten.clone();
+                }
+
+                let rewritten = self
+                    .rewrite_timestamp(*shape, caps)
+                    .unwrap_or_else(|| caps[0].to_owned());
+                cache.put(key, rewritten.clone());
+                rewritten
+            }) {
+                Cow::Owned(rewritten) => Some(rewritten),
+                Cow::Borrowed(_) => None,
+            };
+
+            if let Some(rewritten) = rewritten {
+                text = Cow::Owned(rewritten);
+            }
+        }
+
+        text
+    }
+
+    fn rewrite_timestamp(&self, shape: Shape, caps: &Captures) -> Option<String> {
+        match shape {
+            Shape::Iso => {
+                let seconds = caps.get(4).map_or(":00", |seconds| seconds.as_str());
+                let naive = format!("{} {}{}", &caps[1], &caps[3], seconds);
+                let naive = NaiveDateTime::parse_from_str(&naive, "%Y-%m-%d %H:%M:%S").ok()?;
+
+                let time_format = if caps.get(4).is_some() {
+                    "%H:%M:%S"
+                } else {
+                    "%H:%M"
+                };
+                let format = format!("%Y-%m-%d{}{}", &caps[2], time_format);
+                let fraction = caps.get(5).map_or("", |fraction| fraction.as_str());
+
+                match caps.get(6).map(|offset| offset.as_str()) {
+                    Some(offset) => {
+                        let converted = parse_offset(offset)?
+                            .from_local_datetime(&naive)
+                            .single()?
+                            .with_timezone(&self.to);
+                        let offset_format = match offset {
+                            "Z" if converted.offset().fix().local_minus_utc() == 0 => "Z",
+                            _ if offset.len() == 5 => "%z",
+                            _ => "%:z",
+                        };
+                        Some(format!(
+                            "{}{}{}",
+                            converted.format(&format),
+                            fraction,
+                            converted.format(offset_format)
+                        ))
+                    }
+                    None => Some(format!(
+                        "{}{}",
+                        self.convert(naive)?.format(&format),
+                        fraction
+                    )),
+                }
+            }
+            Shape::Syslog => {
+                let naive = self.syslog_time(caps)?;
+                let day_format = if caps[2].starts_with('0') { "%d" } else { "%e" };
+                let format = format!("%b {} %H:%M:%S", day_format);
+                Some(self.convert(naive)?.format(&format).to_string())
+            }
+            Shape::CommonLog => {
+                let converted = DateTime::parse_from_str(&caps[1], COMMON_LOG_FORMAT)
+                    .ok()?
+                    .with_timezone(&self.to);
+                Some(format!("[{}]", converted.format(COMMON_LOG_FORMAT)))
+            }
+            Shape::JavaUtilLogging => {
+                let naive =
+                    NaiveDateTime::parse_from_str(&caps[0], "%b %d, %Y %I:%M:%S %p").ok()?;
+                Some(
+                    self.convert(naive)?
+                        .format("%b %-d, %Y %-I:%M:%S %p")
+                        .to_string(),
+                )
+            }
+            Shape::Org => {
+                let time = |time: &str| {
+                    let naive = format!("{} {}", &caps[2], time);
+                    NaiveDateTime::parse_from_str(&naive, "%Y-%m-%d %H:%M").ok()
+                };
+                let start = self.convert(time(&caps[3])?)?;
+                let end = match caps.get(4) {
+                    Some(end) => Some(self.convert(time(end.as_str())?)?),
+                    None => None,
+                };
+                // The day name follows the date, in English as Org writes it
+                const FORMAT: &str = "%Y-%m-%d %a %H:%M";
+                let stamp = |time: &str| format!("{}{}{}{}", &caps[1], time, &caps[5], &caps[6]);
+
+                match end {
+                    // A range that ends on another day is written as two stamps
+                    Some(end) if end.date() != start.date() => Some(format!(
+                        "{}--{}",
+                        stamp(&start.format(FORMAT).to_string()),
+                        stamp(&end.format(FORMAT).to_string())
+                    )),
+                    Some(end) => Some(stamp(&format!(
+                        "{}-{}",
+                        start.format(FORMAT),
+                        end.format("%H:%M")
+                    ))),
+                    None => Some(stamp(&start.format(FORMAT).to_string())),
+                }
+            }
+        }
+    }
+
+    /// The instant of the first timestamp in `text` that's a valid time, for putting lines in the
+    /// order they happened. Stamps without an offset are taken to be in the source timezone.
+    pub fn first_instant(&self, text: &str) -> Option<DateTime<Utc>> {
+        self.detectors
+            .iter()
+            .filter_map(|(pattern, shape)| {
+                pattern.captures_iter(text).find_map(|caps| {
+                    let instant = self.instant(*shape, &caps)?;
+                    Some((caps.get(0).unwrap().start(), instant))
+                })
+            })
+            .min_by_key(|(start, _)| *start)
+            .map(|(_, instant)| instant)
+    }
+
+    fn instant(&self, shape: Shape, caps: &Captures) -> Option<DateTime<Utc>> {
+        let local = |naive: NaiveDateTime| {
+            let dt = self.from.from_local_datetime(&naive).single()?;
+            Some(dt.with_timezone(&Utc))
+        };
+
+        match shape {
+            Shape::Iso => {
+                let seconds = caps.get(4).map_or(":00", |seconds| seconds.as_str());
+                let fraction = caps.get(5).map_or("", |fraction| &fraction.as_str()[1..]);
+                let naive = format!("{} {}{}.{:0<9}", &caps[1], &caps[3], seconds, fraction);
+                let naive = NaiveDateTime::parse_from_str(&naive, "%Y-%m-%d %H:%M:%S%.f").ok()?;
+
+                match caps.get(6) {
+                    Some(offset) => {
+                        let dt = parse_offset(offset.as_str())?
+                            .from_local_datetime(&naive)
+                            .single()?;
+                        Some(dt.with_timezone(&Utc))
+                    }
+                    None => local(naive),
+                }
+            }
+            Shape::Syslog => local(self.syslog_time(caps)?),
+            Shape::CommonLog => {
+                let dt = DateTime::parse_from_str(&caps[1], COMMON_LOG_FORMAT).ok()?;
+                Some(dt.with_timezone(&Utc))
+            }
+            Shape::JavaUtilLogging => {
+                local(NaiveDateTime::parse_from_str(&caps[0], "%b %d, %Y %I:%M:%S %p").ok()?)
+            }
+            Shape::Org => {
+                let naive = format!("{} {}", &caps[2], &caps[3]);
+                local(NaiveDateTime::parse_from_str(&naive, "%Y-%m-%d %H:%M").ok()?)
+            }
+        }
+    }
+
+    // A syslog stamp's local date/time. A stamp from later in the year than now is from last year
+    // (allowing for clocks that are a little ahead).
+    fn syslog_time(&self, caps: &Captures) -> Option<NaiveDateTime> {
+        let parse = |year: i32| {
+            let naive = format!("{} {} {} {}", year, &caps[1], caps[2].trim(), &caps[3]);
+            NaiveDateTime::parse_from_str(&naive, "%Y %b %d %H:%M:%S").ok()
+        };
+        parse(self.now.year())
+            .filter(|naive| *naive <= self.now + Duration::days(1))
+            .or_else(|| parse(self.now.year() - 1))
+    }
+
+    // A local date/time in the source timezone in the target timezone, if it's a valid one
+    fn convert(&self, naive: NaiveDateTime) -> Option<DateTime<Tz>> {
+        let dt = self.from.from_local_datetime(&naive).single()?;
+        Some(convert(dt, self.to))
+    }
+}
+
+// `Z`, `+05:30`, or `+0530`
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    if offset == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+
+    let digits = offset[1..].replace(':', "");
+    let hours = digits.get(..2)?.parse::<i32>().ok()?;
+    let minutes = digits.get(2..)?.parse::<i32>().ok()?;
+    let seconds = hours * 3600 + minutes * 60;
+    FixedOffset::east_opt(if offset.starts_with('-') {
+        -seconds
+    } else {
+        seconds
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::{Asia::Kolkata, Europe::London};
+
+    use super::*;
+
+    #[test]
+    fn test_convert() {
+        let date = London.ymd(2021, 1, 1).and_hms(8, 8, 8);
+        let to_date = Kolkata.ymd(2021, 1, 1).and_hms(13, 38, 8);
+        assert_eq!(convert(date, Kolkata), to_date);
+    }
+
+    #[test]
+    fn test_projection() {
+        let now = Utc.ymd(2021, 3, 10).and_hms(9, 30, 0);
+        assert_eq!(
+            projection(&London.ymd(2021, 7, 9).and_hms(5, 0, 0), now),
+            None
+        );
+        assert_eq!(
+            projection(&London.ymd(2020, 7, 9).and_hms(5, 0, 0), now),
+            None
+        );
+        assert_eq!(
+            projection(&London.ymd(2030, 7, 9).and_hms(5, 0, 0), now),
+            Some(Projection::CurrentRules)
+        );
+
+        let summer = London.ymd(2100, 7, 9).and_hms(5, 0, 0);
+        assert_eq!(summer.offset().fix().local_minus_utc(), 0);
+        assert_eq!(projection(&summer, now), Some(Projection::PastTransitions));
+        // Without daylight saving time, there's nothing missing
+        assert_eq!(
+            projection(&Kolkata.ymd(2100, 7, 9).and_hms(5, 0, 0), now),
+            Some(Projection::CurrentRules)
+        );
+
+        // Brazil stopped observing daylight saving time in 2019, so under a year is enough
+        let sao_paulo = Tz::America__Sao_Paulo.ymd(2021, 12, 1).and_hms(9, 0, 0);
+        assert_eq!(
+            projection(&sao_paulo, now),
+            Some(Projection::VolatileRules { since: 2020 })
+        );
+        assert_eq!(
+            projection(&sao_paulo, Utc.ymd(2021, 10, 1).and_hms(0, 0, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_mean_time_offset() {
+        // Kolkata kept local mean time until 1854
+        let lmt = Utc
+            .ymd(1850, 3, 10)
+            .and_hms(9, 30, 0)
+            .with_timezone(&Kolkata);
+        assert_eq!(
+            mean_time_offset(&lmt),
+            Some(FixedOffset::east(5 * 3600 + 53 * 60 + 28))
+        );
+
+        // Then Howrah and Madras time, which were standard times however odd their offsets
+        let instant = |tz: Tz, year| Utc.ymd(year, 3, 10).and_hms(9, 30, 0).with_timezone(&tz);
+        let madras = instant(Kolkata, 1890);
+        assert_eq!(madras.format("%Z %:z").to_string(), "MMT +05:21");
+        assert_eq!(mean_time_offset(&madras), None);
+        assert_eq!(mean_time_offset(&instant(Kolkata, 1860)), None);
+        assert_eq!(
+            mean_time_offset(&instant(Tz::Europe__Amsterdam, 1938)),
+            None
+        );
+        assert_eq!(mean_time_offset(&instant(Tz::Africa__Monrovia, 1960)), None);
+
+        assert_eq!(mean_time_offset(&instant(Kolkata, 2021)), None);
+        assert_eq!(mean_time_offset(&instant(Tz::Asia__Kathmandu, 2021)), None);
+    }
+
+    #[test]
+    fn test_current_offsets_since() {
+        assert_eq!(current_offsets_since(London, 2021), None);
+        assert_eq!(current_offsets_since(Kolkata, 2021), None);
+        // Daylight saving time moved from April to March in 2007
+        assert_eq!(
+            current_offsets_since(Tz::America__New_York, 2021),
+            Some(2007)
+        );
+        // Permanent standard time from October 2014
+        assert_eq!(current_offsets_since(Tz::Europe__Moscow, 2021), Some(2015));
+    }
+
+    #[test]
+    fn test_convert_batch() {
+        let inputs = ["2021-01-01 08:08", "not a date", "2021-07-09 05:00"];
+        assert_eq!(
+            convert_batch(&inputs, London, Kolkata),
+            vec![
+                Some(Kolkata.ymd(2021, 1, 1).and_hms(13, 38, 0)),
+                None,
+                Some(Kolkata.ymd(2021, 7, 9).and_hms(9, 30, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conversion_cache() {
+        let mut cache = ConversionCache::new(2);
+        let inputs = [
+            "2021-01-01 08:08",
+            "2021-01-01 08:08",
+            "nope",
+            "2021-07-09 05:00",
+        ];
+        let expected = convert_batch(&inputs, London, Kolkata);
+
+        assert_eq!(cache.convert_batch(&inputs, London, Kolkata), expected);
+        // Inputs that have been evicted since are converted again
+        assert_eq!(cache.convert_batch(&inputs, London, Kolkata), expected);
+        assert_eq!(cache.entries.len(), 2);
+    }
+
+    #[test]
+    fn test_timestamp_rewriter() {
+        let rewriter = TimestampRewriter::new(London, Kolkata);
+
+        assert_eq!(
+            rewriter.rewrite("[2021-01-01 08:08] started, done at 2021-01-01T09:10:11Z"),
+            "[2021-01-01 13:38] started, done at 2021-01-01T14:40:11+05:30"
+        );
+        assert_eq!(rewriter.rewrite("no timestamps here"), "no timestamps here");
+        assert_eq!(rewriter.rewrite("2021-13-01 08:08"), "2021-13-01 08:08");
+
+        // Offsets are replaced with the target's, and Z is only kept while the target is at UTC
+        let from_kolkata = TimestampRewriter::new(Kolkata, London);
+        assert_eq!(
+            from_kolkata.rewrite("2021-01-01T14:40:11+05:30"),
+            "2021-01-01T09:10:11+00:00"
+        );
+        assert_eq!(
+            from_kolkata.rewrite("2021-07-01T09:10:11Z"),
+            "2021-07-01T10:10:11+01:00"
+        );
+    }
+
+    #[test]
+    fn test_timestamp_rewriter_log_formats() {
+        let now = NaiveDateTime::parse_from_str("2021-03-10 12:00:00", "%Y-%m-%d %H:%M:%S");
+        let rewriter = TimestampRewriter::at(London, Kolkata, now.unwrap());
+        let to_utc = TimestampRewriter::at(Kolkata, chrono_tz::UTC, now.unwrap());
+
+        // ISO 8601, with fractional seconds (Python, log4j), and with offsets
+        assert_eq!(
+            rewriter.rewrite("2021-01-01 08:08:08,123 INFO main"),
+            "2021-01-01 13:38:08,123 INFO main"
+        );
+        assert_eq!(
+            to_utc.rewrite("ts=2021-01-01T09:10:11.123456789Z level=info"),
+            "ts=2021-01-01T09:10:11.123456789Z level=info"
+        );
+        assert_eq!(
+            rewriter.rewrite("2021-07-01T09:10:11-0400"),
+            "2021-07-01T18:40:11+0530"
+        );
+
+        // syslog, which is space-padded and has no year
+        assert_eq!(
+            rewriter.rewrite("Mar  1 23:00:00 host sshd[1]: Accepted"),
+            "Mar  2 04:30:00 host sshd[1]: Accepted"
+        );
+        assert_eq!(
+            to_utc.rewrite("Jul 20 03:00:00 host cron[2]:"),
+            "Jul 19 21:30:00 host cron[2]:"
+        );
+
+        // Apache/nginx, which carry their own offset
+        assert_eq!(
+            rewriter.rewrite(r#"1.2.3.4 - - [10/Mar/2021:09:30:00 -0500] "GET / HTTP/1.1" 200"#),
+            r#"1.2.3.4 - - [10/Mar/2021:20:00:00 +0530] "GET / HTTP/1.1" 200"#
+        );
+
+        // java.util.logging
+        assert_eq!(
+            rewriter.rewrite("Mar 10, 2021 9:30:00 PM com.example.Main run"),
+            "Mar 11, 2021 3:00:00 AM com.example.Main run"
+        );
+
+        // Org mode, where the day name changes along with the date
+        assert_eq!(
+            rewriter
+                .rewrite("SCHEDULED: <2021-07-09 Fri 21:00 +1w> and [2021-07-09 Fri 09:00-10:30]"),
+            "SCHEDULED: <2021-07-10 Sat 01:30 +1w> and [2021-07-09 Fri 13:30-15:00]"
+        );
+        assert_eq!(rewriter.rewrite("<2021-07-09 Fri>"), "<2021-07-09 Fri>");
+
+        // A range that conversion moves across midnight
+        let from_tokyo = TimestampRewriter::at(Tz::Asia__Tokyo, chrono_tz::UTC, now.unwrap());
+        assert_eq!(
+            from_tokyo.rewrite("<2021-07-09 Fri 08:00-10:00>"),
+            "<2021-07-08 Thu 23:00>--<2021-07-09 Fri 01:00>"
+        );
+    }
+
+    #[test]
+    fn test_first_instant() {
+        let now = NaiveDateTime::parse_from_str("2021-03-10 12:00:00", "%Y-%m-%d %H:%M:%S");
+        let rewriter = TimestampRewriter::at(London, Kolkata, now.unwrap());
+        let instant = |text| rewriter.first_instant(text).map(|dt| dt.to_rfc3339());
+
+        assert_eq!(
+            instant("2021-03-10 09:30:00,250 ERROR at 2021-03-10T01:00:00Z"),
+            Some("2021-03-10T09:30:00.250+00:00".into())
+        );
+        assert_eq!(
+            instant(r#"1.2.3.4 - - [10/Mar/2021:09:30:00 -0500] "GET /""#),
+            Some("2021-03-10T14:30:00+00:00".into())
+        );
+        // The stamp that comes first wins, whatever its shape
+        assert_eq!(
+            instant("Mar  9 23:00:00 host app: retrying 2021-03-10T01:00:00Z"),
+            Some("2021-03-09T23:00:00+00:00".into())
+        );
+        assert_eq!(instant("    at com.example.Main.run"), None);
+    }
+}
@@ -0,0 +1,565 @@
+// `tz cron`: rewrite a cron schedule written for one zone so it runs at the same moments in
+// another. Where either zone observes daylight saving time the difference between them changes
+// through the year, and so does the schedule, so the conversion is worked out from the times
+// the schedule actually runs at over the coming year.
+
+use chrono::{Datelike, Duration, LocalResult, NaiveDate, Offset, TimeZone};
+use chrono_tz::Tz;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+const MONTHS: &[&str] = &[
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const WEEKDAYS: &[&str] = &["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// A standard five-field cron schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    pub minutes: BTreeSet<u32>,
+    pub hours: BTreeSet<u32>,
+    pub days: BTreeSet<u32>,
+    pub months: BTreeSet<u32>,
+    /// 0 is Sunday
+    pub weekdays: BTreeSet<u32>,
+    /// Whether the day fields start with `*`. If neither does, the schedule runs on days that
+    /// match either; otherwise, on days that match both.
+    pub any_day: bool,
+    pub any_weekday: bool,
+}
+
+/// A stretch of days over which the converted schedule stays the same
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Period {
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+    /// The converted schedule, which takes more than one expression when the times it runs at
+    /// can't be written as one
+    pub schedules: Vec<Schedule>,
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Schedule, String> {
+        let expression = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            expression if expression.starts_with('@') => {
+                return Err(format!(
+                    "{} isn't a schedule that can be converted",
+                    expression
+                ))
+            }
+            expression => expression,
+        };
+
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        let [minutes, hours, days, months, weekdays] = match fields[..] {
+            [a, b, c, d, e] => [a, b, c, d, e],
+            _ => return Err(format!("expected 5 fields, not {}", fields.len())),
+        };
+
+        let mut weekday_set = field(weekdays, 0, 7, WEEKDAYS)?;
+        if weekday_set.remove(&7) {
+            weekday_set.insert(0);
+        }
+
+        Ok(Schedule {
+            minutes: field(minutes, 0, 59, &[])?,
+            hours: field(hours, 0, 23, &[])?,
+            days: field(days, 1, 31, &[])?,
+            months: field(months, 1, 12, MONTHS)?,
+            weekdays: weekday_set,
+            any_day: days.starts_with('*'),
+            any_weekday: weekdays.starts_with('*'),
+        })
+    }
+
+    pub fn runs_on(&self, date: NaiveDate) -> bool {
+        let day = self.days.contains(&date.day());
+        let weekday = self
+            .weekdays
+            .contains(&date.weekday().num_days_from_sunday());
+
+        self.months.contains(&date.month())
+            && if self.any_day || self.any_weekday {
+                day && weekday
+            } else {
+                day || weekday
+            }
+    }
+
+    /// The schedule in `to` over the `days` days from `start`, for a schedule in `from`
+    pub fn convert(
+        &self,
+        from: Tz,
+        to: Tz,
+        start: NaiveDate,
+        days: i64,
+    ) -> Result<Vec<Period>, String> {
+        // How far ahead of `from` `to` is (in minutes) whenever the schedule runs, in runs of
+        // days where it's the same
+        let mut runs: Vec<(NaiveDate, NaiveDate, i32)> = Vec::new();
+
+        for date in (0..days).map(|day| start + Duration::days(day)) {
+            if !self.runs_on(date) {
+                continue;
+            }
+
+            for &hour in &self.hours {
+                for &minute in &self.minutes {
+                    // Times skipped as clocks go forward don't run
+                    let instant = match from.from_local_datetime(&date.and_hms(hour, minute, 0)) {
+                        LocalResult::None => continue,
+                        local => local.earliest().expect("the time exists"),
+                    };
+                    let ahead = instant.with_timezone(&to).offset().fix().local_minus_utc()
+                        - instant.offset().fix().local_minus_utc();
+                    let ahead = ahead / 60;
+
+                    match runs.last_mut() {
+                        Some((_, last, run)) if *run == ahead => *last = date,
+                        _ => runs.push((date, date, ahead)),
+                    }
+                }
+            }
+        }
+
+        // Schedules that run every hour or minute can come out the same either way
+        let mut periods: Vec<Period> = Vec::new();
+        for (first, last, ahead) in runs {
+            let schedules = self.shift(ahead)?;
+            match periods.last_mut() {
+                Some(period) if period.schedules == schedules => period.last = last,
+                _ => periods.push(Period {
+                    first,
+                    last,
+                    schedules,
+                }),
+            }
+        }
+
+        Ok(periods)
+    }
+
+    // This schedule `ahead` minutes later
+    fn shift(&self, ahead: i32) -> Result<Vec<Schedule>, String> {
+        // When the schedule runs every day, moving a time past midnight doesn't change which days
+        // it runs on
+        let every_day =
+            self.days.len() == 31 && self.weekdays.len() == 7 && self.months.len() == 12;
+
+        // Days later (or earlier) → hour → minutes
+        let mut shifted = BTreeMap::<i32, BTreeMap<u32, BTreeSet<u32>>>::new();
+        for &hour in &self.hours {
+            for &minute in &self.minutes {
+                let time = (hour * 60 + minute) as i32 + ahead;
+                let days = if every_day {
+                    0
+                } else {
+                    time.div_euclid(24 * 60)
+                };
+                let time = time.rem_euclid(24 * 60) as u32;
+
+                shifted
+                    .entry(days)
+                    .or_default()
+                    .entry(time / 60)
+                    .or_default()
+                    .insert(time % 60);
+            }
+        }
+
+        let mut schedules = Vec::new();
+        for (days, hours) in shifted {
+            let (days, weekdays) = self.shift_days(days)?;
+
+            // Hours with the same minutes can share an expression
+            let mut by_minutes = BTreeMap::<BTreeSet<u32>, BTreeSet<u32>>::new();
+            for (hour, minutes) in hours {
+                by_minutes.entry(minutes).or_default().insert(hour);
+            }
+
+            let mut by_minutes = by_minutes.into_iter().collect::<Vec<_>>();
+            by_minutes.sort_by_key(|(_, hours)| hours.iter().next().copied());
+
+            for (minutes, hours) in by_minutes {
+                schedules.push(Schedule {
+                    minutes,
+                    hours,
+                    days: days.clone(),
+                    weekdays: weekdays.clone(),
+                    ..self.clone()
+                });
+            }
+        }
+
+        Ok(schedules)
+    }
+
+    // The days of the month and of the week to run on to run `days` days later
+    fn shift_days(&self, days: i32) -> Result<(BTreeSet<u32>, BTreeSet<u32>), String> {
+        let every_day = self.days.len() == 31;
+        let every_weekday = self.weekdays.len() == 7;
+
+        if days == 0 {
+            return Ok((self.days.clone(), self.weekdays.clone()));
+        }
+
+        let crossing = "runs close enough to midnight to move to another day";
+        if self.months.len() < 12 {
+            return Err(format!(
+                "the schedule {} in some months only, and cron can't move those days to the \
+                 neighbouring month",
+                crossing
+            ));
+        }
+
+        match (every_day, every_weekday) {
+            (true, true) => Ok((self.days.clone(), self.weekdays.clone())),
+            (true, false) => {
+                let weekdays = self
+                    .weekdays
+                    .iter()
+                    .map(|&weekday| (weekday as i32 + days).rem_euclid(7) as u32)
+                    .collect();
+                Ok((self.days.clone(), weekdays))
+            }
+            (false, true) => {
+                // Days near the end of a month move into the next one in only some months
+                let moved = self
+                    .days
+                    .iter()
+                    .map(|&day| day as i32 + days)
+                    .collect::<Vec<_>>();
+                if self.days.iter().any(|&day| day > 28)
+                    || moved.iter().any(|day| !(1..=28).contains(day))
+                {
+                    return Err(format!(
+                        "the schedule {} on days of the month that don't move the same way in \
+                         every month",
+                        crossing
+                    ));
+                }
+                let days = moved.into_iter().map(|day| day as u32).collect();
+                Ok((days, self.weekdays.clone()))
+            }
+            (false, false) => Err(format!(
+                "the schedule {} on both days of the month and days of the week, which cron \
+                 can't move together",
+                crossing
+            )),
+        }
+    }
+}
+
+/// A crontab with each schedule in `from` rewritten for `to`, over the `days` days from `start`.
+/// Comments, variables, and `@reboot` lines are kept as they are. Where a schedule changes with
+/// daylight saving time, the lines for each stretch of dates are given, with all but the current
+/// stretch's commented out.
+pub fn convert_crontab(
+    crontab: &str,
+    from: Tz,
+    to: Tz,
+    start: NaiveDate,
+    days: i64,
+) -> Result<String, String> {
+    let mut converted = format!("# Converted by tz from {} to {}\n", from, to);
+
+    for (number, line) in crontab.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let is_variable = trimmed
+            .split_whitespace()
+            .next()
+            .is_some_and(|word| word.contains('=') && !word.starts_with('@'));
+        if trimmed.is_empty()
+            || trimmed.starts_with('#')
+            || trimmed.starts_with("@reboot")
+            || is_variable
+        {
+            converted.push_str(line);
+            converted.push('\n');
+            continue;
+        }
+
+        // The schedule is the first 5 fields, or just the first if it's a macro like @daily
+        let fields = if trimmed.starts_with('@') { 1 } else { 5 };
+        let mut command = trimmed;
+        for _ in 0..fields {
+            command = command.trim_start();
+            command = &command[command.find(char::is_whitespace).unwrap_or(command.len())..];
+        }
+        let expression = &trimmed[..trimmed.len() - command.len()];
+
+        let periods = Schedule::parse(expression)
+            .and_then(|schedule| schedule.convert(from, to, start, days))
+            .map_err(|e| format!("line {}: {}", number + 1, e))?;
+        let entries = |period: &Period, prefix: &str| {
+            period
+                .schedules
+                .iter()
+                .map(|schedule| format!("{}{}{}\n", prefix, schedule, command))
+                .collect::<String>()
+        };
+
+        match &periods[..] {
+            [] => {
+                converted.push_str("# tz: doesn't run in the coming year, so left as it is\n");
+                converted.push_str(line);
+                converted.push('\n');
+            }
+            [period] => converted.push_str(&entries(period, "")),
+            periods => {
+                converted.push_str(&format!(
+                    "# tz: {} in {} changes with daylight saving time; switch to each stretch's \
+                     lines on its first date\n",
+                    expression.trim(),
+                    from
+                ));
+                for (i, period) in periods.iter().enumerate() {
+                    converted.push_str(&format!("# {} to {}\n", period.first, period.last));
+                    converted.push_str(&entries(period, if i == 0 { "" } else { "#" }));
+                }
+            }
+        }
+    }
+
+    Ok(converted)
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            list(&self.minutes, 0, 59),
+            list(&self.hours, 0, 23),
+            list(&self.days, 1, 31),
+            list(&self.months, 1, 12),
+            list(&self.weekdays, 0, 6)
+        )
+    }
+}
+
+// A field: `*`, a number or name, a range (`1-5`), any of those with a step (`*/15`, `0-30/10`),
+// or a comma-separated list of them
+fn field(text: &str, min: u32, max: u32, names: &[&str]) -> Result<BTreeSet<u32>, String> {
+    let invalid = || format!("invalid field {:?}", text);
+    let value = |value: &str| {
+        names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(value))
+            .map(|i| i as u32 + min)
+            .or_else(|| value.parse().ok())
+            .filter(|value| (min..=max).contains(value))
+            .ok_or_else(invalid)
+    };
+
+    let mut values = BTreeSet::new();
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse::<u32>().map_err(|_| invalid())?)),
+            None => (part, None),
+        };
+
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (value(first)?, value(last)?),
+            None if step.is_some() => (value(range)?, max),
+            None => (value(range)?, value(range)?),
+        };
+
+        match step {
+            Some(0) => return Err(invalid()),
+            step if first <= last => {
+                values.extend((first..=last).step_by(step.unwrap_or(1) as usize))
+            }
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok(values)
+}
+
+// A set of values written as compactly as cron allows
+fn list(values: &BTreeSet<u32>, min: u32, max: u32) -> String {
+    let values = values.iter().copied().collect::<Vec<_>>();
+
+    if values.len() as u32 == max - min + 1 {
+        return "*".to_owned();
+    }
+
+    if let [first, second, ..] = values[..] {
+        let step = second - first;
+        let stepped = (first..=max).step_by(step as usize).collect::<Vec<_>>();
+        if first == min && step > 1 && stepped == values {
+            return format!("*/{}", step);
+        }
+    }
+
+    let mut parts = Vec::new();
+    let mut i = 0;
+    while i < values.len() {
+        let mut j = i;
+        while j + 1 < values.len() && values[j + 1] == values[j] + 1 {
+            j += 1;
+        }
+
+        match j - i {
+            0 => parts.push(values[i].to_string()),
+            1 => parts.push(format!("{},{}", values[i], values[j])),
+            _ => parts.push(format!("{}-{}", values[i], values[j])),
+        }
+        i = j + 1;
+    }
+
+    parts.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(expression: &str, from: Tz, to: Tz) -> Result<Vec<(String, Vec<String>)>, String> {
+        let start = NaiveDate::from_ymd(2021, 1, 1);
+        let periods = Schedule::parse(expression)?.convert(from, to, start, 365)?;
+        Ok(periods
+            .into_iter()
+            .map(|period| {
+                let schedules = period.schedules.iter().map(Schedule::to_string);
+                (period.first.to_string(), schedules.collect())
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_convert_crontab() {
+        let crontab = "\
+# Backups
+MAILTO=ops@example.com
+@reboot /usr/bin/startup
+0 9 * * 1-5   /usr/bin/report --daily
+@hourly /usr/bin/poll
+0 1 * * * /usr/bin/cleanup
+";
+        let start = NaiveDate::from_ymd(2021, 1, 1);
+        let converted =
+            convert_crontab(crontab, Tz::Asia__Kolkata, Tz::Europe__London, start, 365).unwrap();
+        assert_eq!(
+            converted,
+            "\
+# Converted by tz from Asia/Kolkata to Europe/London
+# Backups
+MAILTO=ops@example.com
+@reboot /usr/bin/startup
+# tz: 0 9 * * 1-5 in Asia/Kolkata changes with daylight saving time; switch to each stretch's lines on its first date
+# 2021-01-01 to 2021-03-26
+30 3 * * 1-5   /usr/bin/report --daily
+# 2021-03-29 to 2021-10-29
+#30 4 * * 1-5   /usr/bin/report --daily
+# 2021-11-01 to 2021-12-31
+#30 3 * * 1-5   /usr/bin/report --daily
+30 * * * * /usr/bin/poll
+# tz: 0 1 * * * in Asia/Kolkata changes with daylight saving time; switch to each stretch's lines on its first date
+# 2021-01-01 to 2021-03-28
+30 19 * * * /usr/bin/cleanup
+# 2021-03-29 to 2021-10-31
+#30 20 * * * /usr/bin/cleanup
+# 2021-11-01 to 2021-12-31
+#30 19 * * * /usr/bin/cleanup
+"
+        );
+
+        assert_eq!(
+            convert_crontab(
+                "0 9 * * * a\n0 20 1 * 1 b\n",
+                Tz::UTC,
+                Tz::Asia__Tokyo,
+                start,
+                365
+            ),
+            Err(
+                "line 2: the schedule runs close enough to midnight to move to another day on \
+                 both days of the month and days of the week, which cron can't move together"
+                    .to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let schedule = Schedule::parse("*/20 9-17/4 1,15 JAN-mar sun,7").unwrap();
+        assert_eq!(schedule.minutes, [0, 20, 40].iter().copied().collect());
+        assert_eq!(schedule.hours, [9, 13, 17].iter().copied().collect());
+        assert_eq!(schedule.months, [1, 2, 3].iter().copied().collect());
+        assert_eq!(schedule.weekdays, [0].iter().copied().collect());
+        assert_eq!(Schedule::parse("@daily"), Schedule::parse("0 0 * * *"));
+
+        for invalid in &[
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "@reboot",
+        ] {
+            assert!(Schedule::parse(invalid).is_err(), "{:?}", invalid);
+        }
+
+        let set = |values: &[u32]| values.iter().copied().collect();
+        assert_eq!(list(&set(&[0, 15, 30, 45]), 0, 59), "*/15");
+        assert_eq!(list(&set(&[1, 2, 3, 5, 6, 9]), 0, 23), "1-3,5,6,9");
+        assert_eq!(list(&(0..7).collect(), 0, 6), "*");
+    }
+
+    #[test]
+    fn test_convert() {
+        let plain = |expression: &str| vec![expression.to_owned()];
+
+        // Neither zone changes its clocks
+        assert_eq!(
+            convert("30 9 * * 1-5", Tz::Asia__Kolkata, Tz::UTC),
+            Ok(vec![("2021-01-01".to_owned(), plain("0 4 * * 1-5"))])
+        );
+
+        // Past midnight, the days move too
+        assert_eq!(
+            convert("0 22 * * 1-5", Tz::Etc__GMTPlus5, Tz::UTC),
+            Ok(vec![("2021-01-01".to_owned(), plain("0 3 * * 2-6"))])
+        );
+        assert_eq!(
+            convert("0 1 2,15 * *", Tz::Asia__Tokyo, Tz::UTC),
+            Ok(vec![("2021-01-02".to_owned(), plain("0 16 1,14 * *"))])
+        );
+
+        // New York is 5 hours behind UTC in winter, 4 in summer
+        assert_eq!(
+            convert("0 9 * * 1-5", Tz::America__New_York, Tz::UTC),
+            Ok(vec![
+                ("2021-01-01".to_owned(), plain("0 14 * * 1-5")),
+                ("2021-03-15".to_owned(), plain("0 13 * * 1-5")),
+                ("2021-11-08".to_owned(), plain("0 14 * * 1-5")),
+            ])
+        );
+
+        // Half an hour moves some times into the next hour but not others
+        assert_eq!(
+            convert("0,45 9 * * *", Tz::UTC, Tz::Asia__Kolkata),
+            Ok(vec![(
+                "2021-01-01".to_owned(),
+                vec!["30 14 * * *".to_owned(), "15 15 * * *".to_owned()]
+            )])
+        );
+
+        assert_eq!(
+            convert("*/30 * * * *", Tz::America__New_York, Tz::UTC),
+            Ok(vec![("2021-01-01".to_owned(), plain("*/30 * * * *"))])
+        );
+
+        assert!(convert("0 23 1 * 1", Tz::UTC, Tz::Asia__Tokyo).is_err());
+        assert!(convert("0 23 30 * *", Tz::UTC, Tz::Asia__Tokyo).is_err());
+        assert!(convert("0 23 * 6 *", Tz::UTC, Tz::Asia__Tokyo).is_err());
+    }
+}
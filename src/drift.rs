@@ -0,0 +1,156 @@
+// `tz drift`: how far the system clock is from an NTP server's, since every conversion starts from
+// the system's idea of now. This is a single SNTP (RFC 4330) request, which is plenty accurate for
+// telling whether a clock is off by more than a fraction of a second.
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use std::convert::TryInto;
+use std::net::UdpSocket;
+use std::time::Duration as StdDuration;
+
+pub const DEFAULT_SERVER: &str = "pool.ntp.org";
+
+const PORT: u16 = 123;
+const TIMEOUT_SECONDS: u64 = 5;
+
+// NTP counts seconds from 1900, Unix from 1970
+const NTP_TO_UNIX_SECONDS: i64 = 2_208_988_800;
+
+/// The result of asking a server for the time
+#[derive(Debug, PartialEq, Eq)]
+pub struct Drift {
+    /// How far the system clock is ahead of the server's (negative when it's behind)
+    pub ahead: Duration,
+    /// How long the request took there and back, which bounds how precise `ahead` is
+    pub round_trip: Duration,
+}
+
+/// Ask `server` for the time and compare the system clock to it
+pub fn measure(server: &str) -> Result<Drift, String> {
+    let socket = UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| {
+            socket.set_read_timeout(Some(StdDuration::from_secs(TIMEOUT_SECONDS)))?;
+            socket.connect((server, PORT))?;
+            Ok(socket)
+        })
+        .map_err(|e| format!("Couldn't reach {}: {}", server, e))?;
+
+    // Leap indicator 0, version 3, mode 3 (client)
+    let mut request = [0u8; 48];
+    request[0] = 0x1b;
+
+    let sent = Utc::now();
+    socket
+        .send(&request)
+        .map_err(|e| format!("Couldn't send a request to {}: {}", server, e))?;
+
+    let mut response = [0u8; 48];
+    let received = socket
+        .recv(&mut response)
+        .map_err(|e| format!("No answer from {}: {}", server, e))?;
+    let now = Utc::now();
+
+    if received < response.len() {
+        return Err(format!("{} sent a truncated answer", server));
+    }
+    drift(&response, sent, now).ok_or_else(|| format!("{} sent an unusable answer", server))
+}
+
+// The server's receive and transmit times, compared with when the request left and the answer
+// arrived here
+fn drift(response: &[u8; 48], sent: DateTime<Utc>, received: DateTime<Utc>) -> Option<Drift> {
+    // Mode 4 is a server's answer, and stratum 0 is a "kiss-o'-death" telling clients to go away
+    let mode = response[0] & 0x7;
+    let stratum = response[1];
+    if mode != 4 || stratum == 0 {
+        return None;
+    }
+
+    let server_received = timestamp(&response[32..40])?;
+    let server_sent = timestamp(&response[40..48])?;
+
+    let behind = ((server_received - sent) + (server_sent - received)) / 2;
+    let round_trip = (received - sent) - (server_sent - server_received);
+    Some(Drift {
+        ahead: -behind,
+        round_trip,
+    })
+}
+
+// A 64-bit NTP timestamp: seconds since 1900, then a fraction of a second in 1/2^32ths
+fn timestamp(bytes: &[u8]) -> Option<DateTime<Utc>> {
+    let seconds = u32::from_be_bytes(bytes[..4].try_into().ok()?);
+    let fraction = u32::from_be_bytes(bytes[4..8].try_into().ok()?);
+    if seconds == 0 && fraction == 0 {
+        return None;
+    }
+
+    // The seconds wrap around in February 2036. As RFC 4330 suggests, ones without the top bit
+    // set are taken to be after that rather than before 1968.
+    let seconds = if seconds & 0x8000_0000 == 0 {
+        i64::from(seconds) + (1 << 32)
+    } else {
+        i64::from(seconds)
+    };
+
+    let nanos = (u64::from(fraction) * 1_000_000_000) >> 32;
+    Utc.timestamp_opt(seconds - NTP_TO_UNIX_SECONDS, nanos as u32)
+        .single()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ntp(datetime: DateTime<Utc>) -> [u8; 8] {
+        let seconds = (datetime.timestamp() + NTP_TO_UNIX_SECONDS) as u32;
+        let fraction =
+            ((u64::from(datetime.timestamp_subsec_nanos()) << 32) / 1_000_000_000) as u32;
+        let mut bytes = [0u8; 8];
+        bytes[..4].copy_from_slice(&seconds.to_be_bytes());
+        bytes[4..].copy_from_slice(&fraction.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_drift() {
+        let sent = Utc.ymd(2021, 3, 10).and_hms_milli(9, 30, 0, 0);
+        let received = sent + Duration::milliseconds(100);
+
+        // The server's clock is 2s behind, and it took 20ms to answer
+        let mut response = [0u8; 48];
+        response[0] = 0x24;
+        response[1] = 2;
+        response[32..40].copy_from_slice(&ntp(sent - Duration::milliseconds(1960)));
+        response[40..48].copy_from_slice(&ntp(sent - Duration::milliseconds(1940)));
+
+        let drift = drift(&response, sent, received).unwrap();
+        assert!(
+            (drift.ahead - Duration::seconds(2))
+                .num_milliseconds()
+                .abs()
+                <= 1
+        );
+        assert!(
+            (drift.round_trip - Duration::milliseconds(80))
+                .num_milliseconds()
+                .abs()
+                <= 1
+        );
+
+        // Kiss-o'-death
+        response[1] = 0;
+        assert_eq!(super::drift(&response, sent, received), None);
+    }
+
+    #[test]
+    fn test_timestamp() {
+        for &datetime in &[
+            Utc.ymd(1999, 12, 31).and_hms(23, 59, 59),
+            Utc.ymd(2036, 2, 7).and_hms(6, 28, 17),
+            Utc.ymd(2040, 1, 1).and_hms(0, 0, 0),
+        ] {
+            assert_eq!(timestamp(&ntp(datetime)), Some(datetime));
+        }
+    }
+}
@@ -0,0 +1,273 @@
+// `tz exif`: fix the time photos were taken when the camera's clock was set to another zone (say,
+// still on home time while travelling). DateTimeOriginal holds the local time on the camera's
+// clock, so it's read as a time in --from, converted to --to, and written back along with
+// OffsetTimeOriginal, if the photo has one, to record the new offset.
+//
+// Only the values are changed, in place. Both have a fixed length, so nothing else in the file
+// moves, and adding fields (which would mean rewriting the Exif structure) is left to dedicated
+// tools like exiftool.
+
+use chrono::{DateTime, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+
+use std::convert::TryInto;
+use std::ops::Range;
+
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+const EXIF_IFD_POINTER: u16 = 0x8769;
+const ASCII: u16 = 2;
+
+const DATE_TIME_FORMAT: &str = "%Y:%m:%d %H:%M:%S";
+
+/// What was changed in a photo
+#[derive(Debug, PartialEq, Eq)]
+pub struct Shifted {
+    pub original: NaiveDateTime,
+    pub shifted: DateTime<Tz>,
+    /// Whether there was an OffsetTimeOriginal to update
+    pub offset_updated: bool,
+}
+
+/// Reinterpret the time a JPEG was taken as a time in `from`, and rewrite it for `to`
+pub fn shift(jpeg: &mut [u8], from: Tz, to: Tz) -> Result<Shifted, String> {
+    let tiff = tiff_range(jpeg).ok_or("no Exif data")?;
+    let exif = &mut jpeg[tiff];
+    let fields = Fields::read(exif).ok_or("the Exif data is malformed")?;
+
+    let date_time = fields
+        .date_time_original
+        .clone()
+        .ok_or("no DateTimeOriginal")?;
+    let text = std::str::from_utf8(&exif[date_time.clone()]).unwrap_or("");
+    let original = NaiveDateTime::parse_from_str(text.trim_end_matches('\0'), DATE_TIME_FORMAT)
+        .map_err(|_| format!("DateTimeOriginal {:?} isn't a date and time", text))?;
+
+    let shifted = from
+        .from_local_datetime(&original)
+        .earliest()
+        .ok_or_else(|| format!("{} doesn't exist in {}", original, from))?
+        .with_timezone(&to);
+
+    write_ascii(
+        exif,
+        date_time,
+        &shifted.format(DATE_TIME_FORMAT).to_string(),
+    )?;
+    let offset_updated = match fields.offset_time_original {
+        Some(offset) => {
+            write_ascii(exif, offset, &shifted.format("%:z").to_string())?;
+            true
+        }
+        None => false,
+    };
+
+    Ok(Shifted {
+        original,
+        shifted,
+        offset_updated,
+    })
+}
+
+// Where the TIFF structure holding the Exif data is, inside the JPEG's APP1 segment
+fn tiff_range(jpeg: &[u8]) -> Option<Range<usize>> {
+    if jpeg.get(..2)? != [0xff, 0xd8] {
+        return None;
+    }
+
+    let mut at = 2;
+    loop {
+        let (marker, length) = match jpeg.get(at..at + 4)? {
+            [0xff, marker, high, low] => (*marker, usize::from(u16::from_be_bytes([*high, *low]))),
+            _ => return None,
+        };
+        // Image data follows the start of scan, with no more metadata after it
+        if marker == 0xda {
+            return None;
+        }
+
+        let segment = at + 4..at + 2 + length;
+        if marker == 0xe1 && jpeg.get(segment.start..segment.start + 6)? == b"Exif\0\0" {
+            return Some(segment.start + 6..segment.end.min(jpeg.len()));
+        }
+        at = segment.end;
+    }
+}
+
+// The byte ranges of the values of the fields to rewrite, relative to the start of the TIFF
+// structure
+struct Fields {
+    date_time_original: Option<Range<usize>>,
+    offset_time_original: Option<Range<usize>>,
+}
+
+impl Fields {
+    fn read(tiff: &[u8]) -> Option<Fields> {
+        let big_endian = match tiff.get(..2)? {
+            b"MM" => true,
+            b"II" => false,
+            _ => return None,
+        };
+        let u16_at = |at: usize| {
+            let bytes = tiff.get(at..at + 2)?.try_into().ok()?;
+            Some(if big_endian {
+                u16::from_be_bytes(bytes)
+            } else {
+                u16::from_le_bytes(bytes)
+            })
+        };
+        let u32_at = |at: usize| {
+            let bytes = tiff.get(at..at + 4)?.try_into().ok()?;
+            Some(if big_endian {
+                u32::from_be_bytes(bytes)
+            } else {
+                u32::from_le_bytes(bytes)
+            } as usize)
+        };
+
+        // Each field in a directory is a tag, a type, a count, and the value (or where it is, if
+        // it's longer than 4 bytes)
+        let directory = |at: usize| -> Option<Vec<(u16, u16, usize, usize)>> {
+            let count = usize::from(u16_at(at)?);
+            (0..count)
+                .map(|i| {
+                    let entry = at + 2 + i * 12;
+                    let count = u32_at(entry + 4)?;
+                    let value = if count > 4 {
+                        u32_at(entry + 8)?
+                    } else {
+                        entry + 8
+                    };
+                    Some((u16_at(entry)?, u16_at(entry + 2)?, count, value))
+                })
+                .collect()
+        };
+
+        let first = directory(u32_at(4)?)?;
+        let exif = match first.iter().find(|(tag, ..)| *tag == EXIF_IFD_POINTER) {
+            Some(&(_, _, _, at)) => directory(u32_at(at)?)?,
+            None => Vec::new(),
+        };
+
+        let ascii = |wanted: u16| {
+            exif.iter()
+                .find(|(tag, kind, ..)| *tag == wanted && *kind == ASCII)
+                .map(|&(_, _, count, at)| at..at + count)
+                .filter(|range| range.end <= tiff.len())
+        };
+        Some(Fields {
+            date_time_original: ascii(DATE_TIME_ORIGINAL),
+            offset_time_original: ascii(OFFSET_TIME_ORIGINAL),
+        })
+    }
+}
+
+// Overwrite a NUL-terminated string that has exactly enough room for `value`
+fn write_ascii(tiff: &mut [u8], range: Range<usize>, value: &str) -> Result<(), String> {
+    if range.len() != value.len() + 1 {
+        return Err(format!("no room for {:?} in the Exif data", value));
+    }
+    tiff[range.clone()][..value.len()].copy_from_slice(value.as_bytes());
+    tiff[range.end - 1] = 0;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A JPEG with just an Exif segment: IFD0 pointing at an Exif IFD with the given ASCII fields
+    fn jpeg(big_endian: bool, fields: &[(u16, &str)]) -> Vec<u8> {
+        let u16_bytes = |value: u16| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+        let u32_bytes = |value: u32| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+
+        let mut tiff = Vec::new();
+        tiff.extend_from_slice(if big_endian { b"MM" } else { b"II" });
+        tiff.extend_from_slice(&u16_bytes(42));
+        tiff.extend_from_slice(&u32_bytes(8));
+        // IFD0, with only the pointer to the Exif IFD (at 26)
+        tiff.extend_from_slice(&u16_bytes(1));
+        tiff.extend_from_slice(&u16_bytes(EXIF_IFD_POINTER));
+        tiff.extend_from_slice(&u16_bytes(4));
+        tiff.extend_from_slice(&u32_bytes(1));
+        tiff.extend_from_slice(&u32_bytes(26));
+        tiff.extend_from_slice(&u32_bytes(0));
+
+        let values_at = 26 + 2 + fields.len() * 12 + 4;
+        let mut values = Vec::new();
+        tiff.extend_from_slice(&u16_bytes(fields.len() as u16));
+        for (tag, value) in fields {
+            tiff.extend_from_slice(&u16_bytes(*tag));
+            tiff.extend_from_slice(&u16_bytes(ASCII));
+            tiff.extend_from_slice(&u32_bytes(value.len() as u32 + 1));
+            tiff.extend_from_slice(&u32_bytes((values_at + values.len()) as u32));
+            values.extend_from_slice(value.as_bytes());
+            values.push(0);
+        }
+        tiff.extend_from_slice(&u32_bytes(0));
+        tiff.extend_from_slice(&values);
+
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe1];
+        jpeg.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(&tiff);
+        jpeg.extend_from_slice(&[0xff, 0xda, 0, 2, 0xff, 0xd9]);
+        jpeg
+    }
+
+    #[test]
+    fn test_shift() {
+        for &big_endian in &[false, true] {
+            let mut photo = jpeg(
+                big_endian,
+                &[
+                    (DATE_TIME_ORIGINAL, "2021:07:09 17:00:00"),
+                    (OFFSET_TIME_ORIGINAL, "+09:00"),
+                ],
+            );
+            let shifted = shift(&mut photo, Tz::Asia__Tokyo, Tz::Europe__Paris).unwrap();
+            assert_eq!(shifted.shifted.to_string(), "2021-07-09 10:00:00 CEST");
+            assert!(shifted.offset_updated);
+            assert_eq!(
+                photo,
+                jpeg(
+                    big_endian,
+                    &[
+                        (DATE_TIME_ORIGINAL, "2021:07:09 10:00:00"),
+                        (OFFSET_TIME_ORIGINAL, "+02:00"),
+                    ]
+                )
+            );
+        }
+
+        let mut photo = jpeg(false, &[(DATE_TIME_ORIGINAL, "2021:01:01 00:30:00")]);
+        let shifted = shift(&mut photo, Tz::Europe__London, Tz::America__New_York).unwrap();
+        assert!(!shifted.offset_updated);
+        assert_eq!(
+            photo,
+            jpeg(false, &[(DATE_TIME_ORIGINAL, "2020:12:31 19:30:00")])
+        );
+
+        let mut photo = jpeg(false, &[(OFFSET_TIME_ORIGINAL, "+09:00")]);
+        assert_eq!(
+            shift(&mut photo, Tz::UTC, Tz::UTC),
+            Err("no DateTimeOriginal".to_owned())
+        );
+        assert_eq!(
+            shift(&mut [0xff, 0xd8, 0xff, 0xd9], Tz::UTC, Tz::UTC),
+            Err("no Exif data".to_owned())
+        );
+    }
+}
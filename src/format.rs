@@ -0,0 +1,276 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone};
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{self, Display};
+
+pub use chrono::Locale;
+
+/// The format used when none is given, e.g. `2021-07-09 10:30:00 IST`
+pub const DEFAULT_FORMAT: &str = "%Y-%m-%d %H:%M:%S %Z";
+
+/// A format string containing a specifier that isn't understood
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FormatError {
+    pub format: String,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid format string {:?}", self.format)
+    }
+}
+
+impl Error for FormatError {}
+
+/// Check that every specifier in `format` is understood. `format_datetime` panics on formats
+/// that fail this check, so check any format that comes from user input first.
+pub fn check_format(format: &str) -> Result<(), FormatError> {
+    let expanded = expand_eras(format, |_| String::new());
+    if StrftimeItems::new(&expanded).any(|item| item == Item::Error) {
+        Err(FormatError {
+            format: format.to_owned(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Render `dt` using a strftime-style `format` (see `chrono::format::strftime`), plus glibc's
+/// `%EC`, `%Ey`, and `%EY` for Japanese eras: the era's name, the year in it, and both (`Reiwa
+/// 3`)
+pub fn format_datetime<T: TimeZone>(dt: &DateTime<T>, format: &str) -> String
+where
+    T::Offset: Display,
+{
+    let date = dt.naive_local().date();
+    let format = expand_eras(format, |specifier| era_field(date, specifier, false));
+    dt.format(&format).to_string()
+}
+
+/// Like `format_datetime`, but with day and month names, AM/PM, and the `%c`, `%x`, and `%X`
+/// representations (and so the order of the date's parts) taken from `locale`. Japanese locales
+/// write eras in kanji, as in `%EY%-m月%-d日` for `令和3年7月9日`.
+pub fn format_datetime_localized<T: TimeZone>(
+    dt: &DateTime<T>,
+    format: &str,
+    locale: Locale,
+) -> String
+where
+    T::Offset: Display,
+{
+    let date = dt.naive_local().date();
+    let kanji = locale == Locale::ja_JP;
+    let format = expand_eras(format, |specifier| era_field(date, specifier, kanji));
+    dt.format_localized(&format, locale).to_string()
+}
+
+struct Era {
+    // The day it began, as far as its dates are Gregorian
+    start: (i32, u32, u32),
+    // The year its years count from
+    first_year: i32,
+    kanji: &'static str,
+    name: &'static str,
+}
+
+// Japanese eras since the country switched to the Gregorian calendar partway through Meiji
+const ERAS: &[Era] = &[
+    Era {
+        start: (2019, 5, 1),
+        first_year: 2019,
+        kanji: "令和",
+        name: "Reiwa",
+    },
+    Era {
+        start: (1989, 1, 8),
+        first_year: 1989,
+        kanji: "平成",
+        name: "Heisei",
+    },
+    Era {
+        start: (1926, 12, 25),
+        first_year: 1926,
+        kanji: "昭和",
+        name: "Showa",
+    },
+    Era {
+        start: (1912, 7, 30),
+        first_year: 1912,
+        kanji: "大正",
+        name: "Taisho",
+    },
+    Era {
+        start: (1873, 1, 1),
+        first_year: 1868,
+        kanji: "明治",
+        name: "Meiji",
+    },
+];
+
+// The value of an era specifier (`C`, `y`, or `Y`) for `date`. Dates before Meiji's Gregorian
+// years fall back to the plain year, as glibc does.
+fn era_field(date: NaiveDate, specifier: char, kanji: bool) -> String {
+    let era = ERAS.iter().find(|era| {
+        let (year, month, day) = era.start;
+        date >= NaiveDate::from_ymd(year, month, day)
+    });
+    let (name, year) = match era {
+        Some(era) => (
+            if kanji { era.kanji } else { era.name },
+            date.year() - era.first_year + 1,
+        ),
+        None => return date.year().to_string(),
+    };
+
+    match (specifier, kanji) {
+        ('C', _) => name.to_owned(),
+        ('y', _) => year.to_string(),
+        // The first year of an era is 元年, not 1年
+        ('Y', true) if year == 1 => format!("{}元年", name),
+        ('Y', true) => format!("{}{}年", name, year),
+        _ => format!("{} {}", name, year),
+    }
+}
+
+// Replace each `%EC`, `%Ey`, and `%EY` in `format` (which chrono doesn't know) with what `field`
+// gives for `C`, `y`, or `Y`, escaping any `%` in it
+fn expand_eras(format: &str, field: impl Fn(char) -> String) -> Cow<'_, str> {
+    if !format.contains("%E") {
+        return Cow::Borrowed(format);
+    }
+
+    let mut expanded = String::with_capacity(format.len());
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some('E') => {
+                let mut rest = chars.clone();
+                rest.next();
+                match rest.next() {
+                    Some(specifier @ ('C' | 'y' | 'Y')) => {
+                        expanded.push_str(&field(specifier).replace('%', "%%"));
+                        chars = rest;
+                    }
+                    _ => expanded.push(c),
+                }
+            }
+            // `%%` is a literal `%`, which mustn't start a specifier
+            Some('%') => {
+                expanded.push_str("%%");
+                chars.next();
+            }
+            _ => expanded.push(c),
+        }
+    }
+    Cow::Owned(expanded)
+}
+
+/// Look up a POSIX locale name like `fr_FR`, `de_DE.UTF-8@euro`, or `C`, ignoring its encoding
+pub fn parse_locale(name: &str) -> Option<Locale> {
+    let (name, modifier) = match name.split_once('@') {
+        Some((name, modifier)) => (name, Some(modifier)),
+        None => (name, None),
+    };
+    let name = name.split('.').next().unwrap_or(name).replace('-', "_");
+
+    match name.as_str() {
+        "C" | "POSIX" => return Some(Locale::POSIX),
+        "" => return None,
+        _ => {}
+    }
+
+    modifier
+        .and_then(|modifier| Locale::try_from(format!("{}@{}", name, modifier).as_str()).ok())
+        .or_else(|| Locale::try_from(name.as_str()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Asia::Kolkata;
+
+    use super::*;
+
+    #[test]
+    fn test_format_datetime() {
+        let date = Kolkata.ymd(2021, 7, 9).and_hms_milli(10, 30, 0, 250);
+        assert_eq!(
+            format_datetime(&date, DEFAULT_FORMAT),
+            "2021-07-09 10:30:00 IST"
+        );
+        assert_eq!(format_datetime(&date, "%H:%M %z"), "10:30 +0530");
+    }
+
+    #[test]
+    fn test_format_datetime_localized() {
+        let date = Kolkata.ymd(2021, 7, 9).and_hms(22, 30, 0);
+        let format = "%A %d %B %Y, %x";
+        assert_eq!(
+            format_datetime_localized(&date, format, Locale::fr_FR),
+            "vendredi 09 juillet 2021, 09/07/2021"
+        );
+        assert_eq!(
+            format_datetime_localized(&date, format, Locale::POSIX),
+            format_datetime(&date, format)
+        );
+    }
+
+    #[test]
+    fn test_japanese_eras() {
+        let date = Kolkata.ymd(2021, 7, 9).and_hms(22, 30, 0);
+        assert_eq!(
+            format_datetime_localized(&date, "%EY%-m月%-d日", Locale::ja_JP),
+            "令和3年7月9日"
+        );
+        assert_eq!(format_datetime(&date, "%EY, %B %-d"), "Reiwa 3, July 9");
+        assert_eq!(format_datetime(&date, "%EC/%Ey 100%%EY"), "Reiwa/3 100%EY");
+
+        // Either side of the change from Heisei to Reiwa
+        let date = Kolkata.ymd(2019, 4, 30).and_hms(0, 0, 0);
+        assert_eq!(
+            format_datetime_localized(&date, "%EY", Locale::ja_JP),
+            "平成31年"
+        );
+        let date = Kolkata.ymd(2019, 5, 1).and_hms(0, 0, 0);
+        assert_eq!(
+            format_datetime_localized(&date, "%EY", Locale::ja_JP),
+            "令和元年"
+        );
+
+        assert_eq!(
+            format_datetime(&Kolkata.ymd(1900, 1, 1).and_hms(0, 0, 0), "%EY"),
+            "Meiji 33"
+        );
+        assert_eq!(
+            format_datetime(&Kolkata.ymd(1850, 1, 1).and_hms(0, 0, 0), "%EY"),
+            "1850"
+        );
+    }
+
+    #[test]
+    fn test_parse_locale() {
+        assert_eq!(parse_locale("fr_FR"), Some(Locale::fr_FR));
+        assert_eq!(parse_locale("en-GB"), Some(Locale::en_GB));
+        assert_eq!(parse_locale("de_DE.UTF-8@euro"), Some(Locale::de_DE_euro));
+        assert_eq!(parse_locale("de_DE.UTF-8"), Some(Locale::de_DE));
+        assert_eq!(parse_locale("en_US@nonsense"), Some(Locale::en_US));
+        assert_eq!(parse_locale("C.UTF-8"), Some(Locale::POSIX));
+        assert_eq!(parse_locale("xx_YY"), None);
+        assert_eq!(parse_locale(""), None);
+    }
+
+    #[test]
+    fn test_check_format() {
+        assert_eq!(check_format(DEFAULT_FORMAT), Ok(()));
+        assert_eq!(check_format("%H:%M %Q").unwrap_err().format, "%H:%M %Q");
+        assert_eq!(check_format("%EY%-m月%-d日"), Ok(()));
+        assert!(check_format("%EQ").is_err());
+    }
+}
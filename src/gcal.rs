@@ -0,0 +1,76 @@
+// `tz <tz_id> <datetime> --gcal`: a link that opens Google Calendar's "create event" page with
+// the converted time filled in, for when the conversion was the first step of scheduling something.
+
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+
+const TEMPLATE_URL: &str = "https://calendar.google.com/calendar/render?action=TEMPLATE";
+
+/// How long an event without a duration lasts, as Google Calendar needs an end
+pub const DEFAULT_LENGTH_MINUTES: i64 = 60;
+
+/// A link to create an event starting at `start`, lasting `duration` (or an hour), shown in
+/// `start`'s zone
+pub fn link(start: DateTime<Tz>, duration: Option<Duration>, summary: Option<&str>) -> String {
+    let end = start + duration.unwrap_or_else(|| Duration::minutes(DEFAULT_LENGTH_MINUTES));
+    let stamp = |datetime: DateTime<Tz>| {
+        datetime
+            .with_timezone(&Utc)
+            .format("%Y%m%dT%H%M%SZ")
+            .to_string()
+    };
+
+    let mut link = format!(
+        "{}&dates={}/{}&ctz={}",
+        TEMPLATE_URL,
+        stamp(start),
+        stamp(end),
+        encode(start.timezone().name())
+    );
+    if let Some(summary) = summary {
+        link.push_str(&format!("&text={}", encode(summary)));
+    }
+    link
+}
+
+/// Percent-encodes everything but RFC 3986's unreserved characters
+pub fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_link() {
+        let start = Tz::Asia__Kolkata.ymd(2021, 3, 10).and_hms(9, 30, 0);
+
+        assert_eq!(
+            link(
+                start,
+                Some(Duration::minutes(45)),
+                Some("Standup & planning")
+            ),
+            "https://calendar.google.com/calendar/render?action=TEMPLATE\
+             &dates=20210310T040000Z/20210310T044500Z&ctz=Asia%2FKolkata\
+             &text=Standup%20%26%20planning"
+        );
+        assert_eq!(
+            link(start, None, None),
+            "https://calendar.google.com/calendar/render?action=TEMPLATE\
+             &dates=20210310T040000Z/20210310T050000Z&ctz=Asia%2FKolkata"
+        );
+        assert_eq!(encode("Café 10:00"), "Caf%C3%A9%2010%3A00");
+    }
+}
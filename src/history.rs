@@ -0,0 +1,129 @@
+// Recent conversions, kept in a small file in the cache directory so `tz --last` can repeat the
+// latest one and `tz history-list` can show them. Each line is one conversion:
+//
+//     <RFC 3339 date/time>\t<from zone>\t<to zone>,<to zone>,...
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::config::Config;
+
+/// How many conversions are kept; older ones are dropped as new ones are recorded
+const HISTORY_SIZE: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// The converted date/time, in the zone it was converted from
+    pub datetime: DateTime<Tz>,
+    pub targets: Vec<Tz>,
+}
+
+impl Entry {
+    fn to_line(&self) -> String {
+        let targets = self.targets.iter().map(|tz| tz.name()).collect::<Vec<_>>();
+
+        format!(
+            "{}\t{}\t{}",
+            self.datetime.to_rfc3339(),
+            self.datetime.timezone().name(),
+            targets.join(",")
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Entry> {
+        let mut fields = line.split('\t');
+        let datetime = DateTime::parse_from_rfc3339(fields.next()?).ok()?;
+        let from = fields.next()?.parse::<Tz>().ok()?;
+        let targets = fields
+            .next()?
+            .split(',')
+            .map(|tz| tz.parse().ok())
+            .collect::<Option<Vec<Tz>>>()?;
+
+        Some(Entry {
+            datetime: datetime.with_timezone(&from),
+            targets,
+        })
+    }
+}
+
+/// Where the history is kept, if a home directory can be found
+pub fn path() -> Option<PathBuf> {
+    Some(Config::cache_dir()?.join("history"))
+}
+
+/// Every recorded conversion, oldest first. Lines that can't be read (e.g. because they name a
+/// zone this build of tz doesn't know) are skipped.
+pub fn load(path: &Path) -> io::Result<Vec<Entry>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents.lines().filter_map(Entry::from_line).collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Add a conversion to the history, dropping the oldest ones beyond `HISTORY_SIZE`. The file is
+/// replaced in one go, so a run that's killed partway through can't leave it cut short.
+pub fn record(path: &Path, entry: &Entry) -> io::Result<()> {
+    let mut entries = load(path)?;
+    entries.push(entry.clone());
+
+    let start = entries.len().saturating_sub(HISTORY_SIZE);
+    let contents = entries[start..]
+        .iter()
+        .map(|entry| entry.to_line() + "\n")
+        .collect::<String>();
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Named for this process, so runs at the same time don't write over each other's
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(format!(".{}.tmp", process::id()));
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let path = std::env::temp_dir()
+            .join(format!("tz-history-{}", std::process::id()))
+            .join("history");
+
+        let entry = |hour| Entry {
+            datetime: Tz::Asia__Kolkata.ymd(2021, 7, 9).and_hms(hour % 24, 30, 0),
+            targets: vec![Tz::Asia__Tokyo, Tz::UTC],
+        };
+
+        assert_eq!(load(&path).unwrap(), vec![]);
+
+        for hour in 0..HISTORY_SIZE as u32 + 5 {
+            record(&path, &entry(hour)).unwrap();
+        }
+
+        let entries = load(&path).unwrap();
+        assert_eq!(entries.len(), HISTORY_SIZE);
+        assert_eq!(entries.last(), Some(&entry(HISTORY_SIZE as u32 + 4)));
+        assert_eq!(
+            entry(1).to_line(),
+            "2021-07-09T01:30:00+05:30\tAsia/Kolkata\tAsia/Tokyo,UTC"
+        );
+        assert_eq!(
+            Entry::from_line("2021-07-09T01:30:00+05:30\tNowhere\tUTC"),
+            None
+        );
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,401 @@
+//! National public holidays, to notice when a time being scheduled falls on one where it's being
+//! converted to (with the `holidays` feature).
+//!
+//! The dataset is bundled and covers a handful of countries, found from the zones in each. Only
+//! holidays kept on a fixed date, on a weekday of a month, or relative to Easter are included:
+//! ones that follow a lunar calendar (Diwali, Eid, Lunar New Year, ...), regional ones, and the
+//! weekdays given in lieu of holidays that fall on a weekend aren't.
+
+use chrono::Weekday::{Mon, Thu};
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use chrono_tz::Tz;
+
+use Rule::{Date, Easter, Weekday as Nth};
+
+/// A public holiday, and the country it's observed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Holiday {
+    pub name: &'static str,
+    pub country: &'static str,
+}
+
+#[derive(Clone, Copy)]
+enum Rule {
+    /// A month and day
+    Date(u32, u32),
+    /// The nth (or with a negative n, the nth from last) weekday of a month
+    Weekday(u32, Weekday, i8),
+    /// Days after Easter Sunday
+    Easter(i64),
+}
+
+struct Observance {
+    rule: Rule,
+    name: &'static str,
+    /// The first year it was observed, for holidays introduced recently
+    since: i32,
+}
+
+struct Country {
+    name: &'static str,
+    zones: &'static [Tz],
+    holidays: &'static [Observance],
+}
+
+const fn on(rule: Rule, name: &'static str) -> Observance {
+    Observance {
+        rule,
+        name,
+        since: i32::MIN,
+    }
+}
+
+const fn since(since: i32, rule: Rule, name: &'static str) -> Observance {
+    Observance { rule, name, since }
+}
+
+const NEW_YEAR: Observance = on(Date(1, 1), "New Year's Day");
+const GOOD_FRIDAY: Observance = on(Easter(-2), "Good Friday");
+const EASTER_MONDAY: Observance = on(Easter(1), "Easter Monday");
+const ASCENSION: Observance = on(Easter(39), "Ascension Day");
+const WHIT_MONDAY: Observance = on(Easter(50), "Whit Monday");
+const LABOUR_DAY: Observance = on(Date(5, 1), "Labour Day");
+const CHRISTMAS: Observance = on(Date(12, 25), "Christmas Day");
+const BOXING_DAY: Observance = on(Date(12, 26), "Boxing Day");
+
+const COUNTRIES: &[Country] = &[
+    Country {
+        name: "the United States",
+        zones: &[
+            Tz::America__New_York,
+            Tz::America__Chicago,
+            Tz::America__Denver,
+            Tz::America__Phoenix,
+            Tz::America__Los_Angeles,
+            Tz::America__Anchorage,
+            Tz::America__Detroit,
+            Tz::America__Boise,
+            Tz::America__Indiana__Indianapolis,
+            Tz::America__Kentucky__Louisville,
+            Tz::Pacific__Honolulu,
+            Tz::US__Eastern,
+            Tz::US__Central,
+            Tz::US__Mountain,
+            Tz::US__Arizona,
+            Tz::US__Pacific,
+            Tz::US__Alaska,
+            Tz::US__Hawaii,
+        ],
+        holidays: &[
+            NEW_YEAR,
+            on(Nth(1, Mon, 3), "Martin Luther King Jr. Day"),
+            on(Nth(2, Mon, 3), "Washington's Birthday"),
+            on(Nth(5, Mon, -1), "Memorial Day"),
+            since(2021, Date(6, 19), "Juneteenth"),
+            on(Date(7, 4), "Independence Day"),
+            on(Nth(9, Mon, 1), "Labor Day"),
+            on(Nth(10, Mon, 2), "Columbus Day"),
+            on(Date(11, 11), "Veterans Day"),
+            on(Nth(11, Thu, 4), "Thanksgiving Day"),
+            CHRISTMAS,
+        ],
+    },
+    Country {
+        name: "Canada",
+        zones: &[
+            Tz::America__Toronto,
+            Tz::America__Montreal,
+            Tz::America__Vancouver,
+            Tz::America__Edmonton,
+            Tz::America__Winnipeg,
+            Tz::America__Regina,
+            Tz::America__Halifax,
+            Tz::America__St_Johns,
+        ],
+        holidays: &[
+            NEW_YEAR,
+            GOOD_FRIDAY,
+            on(Date(7, 1), "Canada Day"),
+            on(Nth(9, Mon, 1), "Labour Day"),
+            CHRISTMAS,
+        ],
+    },
+    Country {
+        name: "Brazil",
+        zones: &[
+            Tz::America__Sao_Paulo,
+            Tz::America__Bahia,
+            Tz::America__Fortaleza,
+            Tz::America__Recife,
+            Tz::America__Manaus,
+            Tz::America__Belem,
+            Tz::Brazil__East,
+        ],
+        holidays: &[
+            NEW_YEAR,
+            GOOD_FRIDAY,
+            on(Date(4, 21), "Tiradentes"),
+            LABOUR_DAY,
+            on(Date(9, 7), "Independence Day"),
+            on(Date(10, 12), "Our Lady of Aparecida"),
+            on(Date(11, 2), "All Souls' Day"),
+            on(Date(11, 15), "Republic Proclamation Day"),
+            CHRISTMAS,
+        ],
+    },
+    Country {
+        name: "the United Kingdom",
+        zones: &[Tz::Europe__London, Tz::GB],
+        holidays: &[
+            NEW_YEAR,
+            GOOD_FRIDAY,
+            EASTER_MONDAY,
+            on(Nth(5, Mon, 1), "Early May Bank Holiday"),
+            on(Nth(5, Mon, -1), "Spring Bank Holiday"),
+            on(Nth(8, Mon, -1), "Summer Bank Holiday"),
+            CHRISTMAS,
+            BOXING_DAY,
+        ],
+    },
+    Country {
+        name: "Ireland",
+        zones: &[Tz::Europe__Dublin, Tz::Eire],
+        holidays: &[
+            NEW_YEAR,
+            on(Date(3, 17), "St. Patrick's Day"),
+            EASTER_MONDAY,
+            on(Nth(5, Mon, 1), "May Bank Holiday"),
+            on(Nth(6, Mon, 1), "June Bank Holiday"),
+            on(Nth(8, Mon, 1), "August Bank Holiday"),
+            on(Nth(10, Mon, -1), "October Bank Holiday"),
+            CHRISTMAS,
+            on(Date(12, 26), "St. Stephen's Day"),
+        ],
+    },
+    Country {
+        name: "Germany",
+        zones: &[Tz::Europe__Berlin],
+        holidays: &[
+            NEW_YEAR,
+            GOOD_FRIDAY,
+            EASTER_MONDAY,
+            LABOUR_DAY,
+            ASCENSION,
+            WHIT_MONDAY,
+            on(Date(10, 3), "German Unity Day"),
+            CHRISTMAS,
+            on(Date(12, 26), "St. Stephen's Day"),
+        ],
+    },
+    Country {
+        name: "France",
+        zones: &[Tz::Europe__Paris],
+        holidays: &[
+            NEW_YEAR,
+            EASTER_MONDAY,
+            LABOUR_DAY,
+            on(Date(5, 8), "Victory in Europe Day"),
+            ASCENSION,
+            WHIT_MONDAY,
+            on(Date(7, 14), "Bastille Day"),
+            on(Date(8, 15), "Assumption Day"),
+            on(Date(11, 1), "All Saints' Day"),
+            on(Date(11, 11), "Armistice Day"),
+            CHRISTMAS,
+        ],
+    },
+    Country {
+        name: "the Netherlands",
+        zones: &[Tz::Europe__Amsterdam],
+        holidays: &[
+            NEW_YEAR,
+            EASTER_MONDAY,
+            since(2014, Date(4, 27), "King's Day"),
+            ASCENSION,
+            WHIT_MONDAY,
+            CHRISTMAS,
+            on(Date(12, 26), "Second Day of Christmas"),
+        ],
+    },
+    Country {
+        name: "India",
+        zones: &[Tz::Asia__Kolkata, Tz::Asia__Calcutta],
+        holidays: &[
+            on(Date(1, 26), "Republic Day"),
+            on(Date(8, 15), "Independence Day"),
+            on(Date(10, 2), "Gandhi Jayanti"),
+        ],
+    },
+    Country {
+        name: "Singapore",
+        zones: &[Tz::Asia__Singapore, Tz::Singapore],
+        holidays: &[
+            NEW_YEAR,
+            GOOD_FRIDAY,
+            LABOUR_DAY,
+            on(Date(8, 9), "National Day"),
+            CHRISTMAS,
+        ],
+    },
+    Country {
+        name: "Japan",
+        zones: &[Tz::Asia__Tokyo, Tz::Japan],
+        holidays: &[
+            NEW_YEAR,
+            on(Nth(1, Mon, 2), "Coming of Age Day"),
+            on(Date(2, 11), "National Foundation Day"),
+            since(2020, Date(2, 23), "The Emperor's Birthday"),
+            on(Date(4, 29), "Shōwa Day"),
+            on(Date(5, 3), "Constitution Memorial Day"),
+            on(Date(5, 4), "Greenery Day"),
+            on(Date(5, 5), "Children's Day"),
+            on(Nth(7, Mon, 3), "Marine Day"),
+            since(2016, Date(8, 11), "Mountain Day"),
+            on(Nth(9, Mon, 3), "Respect for the Aged Day"),
+            on(Nth(10, Mon, 2), "Sports Day"),
+            on(Date(11, 3), "Culture Day"),
+            on(Date(11, 23), "Labour Thanksgiving Day"),
+        ],
+    },
+    Country {
+        name: "Australia",
+        zones: &[
+            Tz::Australia__Sydney,
+            Tz::Australia__Melbourne,
+            Tz::Australia__Brisbane,
+            Tz::Australia__Adelaide,
+            Tz::Australia__Perth,
+            Tz::Australia__Darwin,
+            Tz::Australia__Hobart,
+            Tz::Australia__Canberra,
+        ],
+        holidays: &[
+            NEW_YEAR,
+            on(Date(1, 26), "Australia Day"),
+            GOOD_FRIDAY,
+            EASTER_MONDAY,
+            on(Date(4, 25), "Anzac Day"),
+            CHRISTMAS,
+            BOXING_DAY,
+        ],
+    },
+    Country {
+        name: "New Zealand",
+        zones: &[Tz::Pacific__Auckland, Tz::NZ],
+        holidays: &[
+            NEW_YEAR,
+            on(Date(1, 2), "Day after New Year's Day"),
+            on(Date(2, 6), "Waitangi Day"),
+            GOOD_FRIDAY,
+            EASTER_MONDAY,
+            on(Date(4, 25), "Anzac Day"),
+            on(Nth(10, Mon, 4), "Labour Day"),
+            CHRISTMAS,
+            BOXING_DAY,
+        ],
+    },
+];
+
+/// The public holiday on `date` in the country `tz` is in, if there's one (and tz knows the
+/// country's holidays)
+pub fn holiday(date: NaiveDate, tz: Tz) -> Option<Holiday> {
+    let country = COUNTRIES
+        .iter()
+        .find(|country| country.zones.contains(&tz))?;
+
+    country
+        .holidays
+        .iter()
+        .filter(|holiday| date.year() >= holiday.since)
+        .find(|holiday| holiday.rule.date(date.year()) == Some(date))
+        .map(|holiday| Holiday {
+            name: holiday.name,
+            country: country.name,
+        })
+}
+
+impl Rule {
+    fn date(self, year: i32) -> Option<NaiveDate> {
+        match self {
+            Rule::Date(month, day) => NaiveDate::from_ymd_opt(year, month, day),
+            Rule::Weekday(month, weekday, n) if n > 0 => {
+                NaiveDate::from_weekday_of_month_opt(year, month, weekday, n as u8)
+            }
+            Rule::Weekday(month, weekday, n) => {
+                let (next_year, next_month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                let last = NaiveDate::from_ymd_opt(next_year, next_month, 1)?.pred();
+                let back = (7 + last.weekday().num_days_from_monday()
+                    - weekday.num_days_from_monday())
+                    % 7;
+                Some(last - Duration::days(i64::from(back) + 7 * i64::from(-n - 1)))
+            }
+            Rule::Easter(days) => Some(easter(year) + Duration::days(days)),
+        }
+    }
+}
+
+// Easter Sunday in the Gregorian calendar (the anonymous algorithm, as Meeus gives it)
+fn easter(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let (b, c) = (year / 100, year % 100);
+    let (d, e) = (b / 4, b % 4);
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let (i, k) = (c / 4, c % 4);
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd(year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_easter() {
+        assert_eq!(easter(2021), NaiveDate::from_ymd(2021, 4, 4));
+        assert_eq!(easter(2024), NaiveDate::from_ymd(2024, 3, 31));
+        assert_eq!(easter(2038), NaiveDate::from_ymd(2038, 4, 25));
+    }
+
+    #[test]
+    fn test_holiday() {
+        let on = |year, month, day, tz| holiday(NaiveDate::from_ymd(year, month, day), tz);
+
+        assert_eq!(
+            on(2021, 8, 15, Tz::Asia__Calcutta),
+            Some(Holiday {
+                name: "Independence Day",
+                country: "India"
+            })
+        );
+        assert_eq!(
+            on(2021, 11, 25, Tz::America__Denver).map(|h| h.name),
+            Some("Thanksgiving Day")
+        );
+        assert_eq!(
+            on(2021, 5, 31, Tz::Europe__London).map(|h| h.name),
+            Some("Spring Bank Holiday")
+        );
+        assert_eq!(
+            on(2021, 4, 2, Tz::Australia__Sydney).map(|h| h.name),
+            Some("Good Friday")
+        );
+        assert_eq!(
+            on(2021, 5, 13, Tz::Europe__Berlin).map(|h| h.name),
+            Some("Ascension Day")
+        );
+
+        // Not yet a holiday, not one there, and no data for the country
+        assert_eq!(on(2020, 6, 19, Tz::America__New_York), None);
+        assert_eq!(on(2021, 7, 4, Tz::Europe__London), None);
+        assert_eq!(on(2021, 1, 1, Tz::Africa__Lagos), None);
+    }
+}
@@ -0,0 +1,674 @@
+// iCalendar files (RFC 5545). `tz ics` reads the events out of one, like the ones attached to
+// meeting invitations, so their times can be converted. Event times name their zone with a TZID,
+// which is an IANA name, a Windows one (from Outlook and Exchange), or the name of a VTIMEZONE
+// in the same file that spells out the zone's offsets and daylight saving time rules.
+//
+// `tz --ics` goes the other way, writing a converted time as an event to import into a calendar.
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use tz::resolve::{windows_to_iana, PosixTz};
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub summary: String,
+    pub start: When,
+    pub end: Option<When>,
+    /// Whether the event has a recurrence rule (only its first occurrence is read)
+    pub repeats: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum When {
+    /// All-day events only have dates, which aren't in any zone
+    Date(NaiveDate),
+    Time(DateTime<Utc>),
+}
+
+/// Every event in an iCalendar file, in the order they appear. Times without a zone ("floating"
+/// times) are taken to be in `floating`.
+pub fn parse(contents: &str, floating: Tz) -> Result<Vec<Event>, String> {
+    let calendar = Component::parse(contents)?;
+
+    let mut definitions = HashMap::new();
+    for vtimezone in calendar.all("VTIMEZONE") {
+        if let (Some(tzid), Some(zone)) = (vtimezone.value("TZID"), definition(vtimezone)) {
+            definitions.insert(tzid.to_owned(), zone);
+        }
+    }
+
+    let when = |property: &Property| -> Result<When, String> {
+        let value = &property.value;
+
+        if property.param("VALUE") == Some("DATE") || value.len() == 8 {
+            return NaiveDate::parse_from_str(value, "%Y%m%d")
+                .map(When::Date)
+                .map_err(|_| format!("invalid date {:?}", value));
+        }
+
+        let (local, utc) = match value.strip_suffix('Z') {
+            Some(value) => (value, true),
+            None => (value.as_str(), false),
+        };
+        let local = NaiveDateTime::parse_from_str(local, "%Y%m%dT%H%M%S")
+            .map_err(|_| format!("invalid date/time {:?}", value))?;
+
+        let utc = match (utc, property.param("TZID")) {
+            (true, _) => local,
+            (false, Some(tzid)) => match zone(tzid, &definitions) {
+                Some(Zone::Tz(tz)) => local_to_utc(tz, &local),
+                Some(Zone::Posix(posix)) => posix.to_utc(&local),
+                None => return Err(format!("unknown TZID {:?}", tzid)),
+            },
+            (false, None) => local_to_utc(floating, &local),
+        };
+
+        Ok(When::Time(Utc.from_utc_datetime(&utc)))
+    };
+
+    calendar
+        .all("VEVENT")
+        .into_iter()
+        .map(|vevent| {
+            let start = vevent
+                .property("DTSTART")
+                .ok_or_else(|| "an event has no DTSTART".to_owned())?;
+            let start = when(start)?;
+
+            let end = match (vevent.property("DTEND"), vevent.value("DURATION")) {
+                (Some(end), _) => Some(when(end)?),
+                (None, Some(duration)) => match start {
+                    When::Time(start) => Some(When::Time(
+                        parse_duration(duration)
+                            .and_then(|duration| start.checked_add_signed(duration))
+                            .ok_or_else(|| format!("invalid DURATION {:?}", duration))?,
+                    )),
+                    When::Date(_) => None,
+                },
+                (None, None) => None,
+            };
+
+            Ok(Event {
+                summary: vevent.value("SUMMARY").map(unescape).unwrap_or_default(),
+                start,
+                end,
+                repeats: vevent.property("RRULE").is_some(),
+            })
+        })
+        .collect()
+}
+
+/// A calendar holding a single event at `start` in its zone, ending `duration` later (if given).
+/// `now` is when it's created, which calendars use to tell versions of an event apart.
+pub fn event(
+    start: DateTime<Tz>,
+    duration: Option<Duration>,
+    summary: Option<&str>,
+    now: DateTime<Utc>,
+) -> String {
+    let stamp = |datetime: DateTime<Tz>| {
+        if datetime.timezone() == Tz::UTC {
+            format!(":{}", datetime.format("%Y%m%dT%H%M%SZ"))
+        } else {
+            format!(
+                ";TZID={}:{}",
+                datetime.timezone().name(),
+                datetime.format("%Y%m%dT%H%M%S")
+            )
+        }
+    };
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_owned(),
+        "VERSION:2.0".to_owned(),
+        format!("PRODID:-//tz//tz {}//EN", env!("CARGO_PKG_VERSION")),
+        "BEGIN:VEVENT".to_owned(),
+        // Nanoseconds since 1970 overflow an i64 outside 1677–2262, so they're written out
+        // rather than counted
+        format!(
+            "UID:{}-{}{:09}@tz",
+            start.timestamp(),
+            now.timestamp(),
+            now.timestamp_subsec_nanos()
+        ),
+        format!("DTSTAMP:{}", now.format("%Y%m%dT%H%M%SZ")),
+        format!("DTSTART{}", stamp(start)),
+    ];
+    if let Some(duration) = duration {
+        lines.push(format!("DTEND{}", stamp(start + duration)));
+    }
+    if let Some(summary) = summary {
+        lines.push(format!("SUMMARY:{}", escape(summary)));
+    }
+    lines.push("END:VEVENT".to_owned());
+    lines.push("END:VCALENDAR".to_owned());
+
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+/// A duration given on the command line: either RFC 5545's (`PT1H30M`) or a shorthand for it
+/// without the prefix (`1h30m`, `45m`)
+pub fn parse_length(length: &str) -> Option<Duration> {
+    let length = length.to_ascii_uppercase();
+    if length.starts_with('P') {
+        parse_duration(&length)
+    } else {
+        parse_duration(&format!("PT{}", length))
+    }
+}
+
+enum Zone<'a> {
+    Tz(Tz),
+    Posix(&'a PosixTz),
+}
+
+// Zone names are often prefixed with a vendor's path (like Mozilla's
+// "/mozilla.org/20050126_1/America/New_York"), so the IANA name may just be the end of the TZID
+fn zone<'a>(tzid: &str, definitions: &'a HashMap<String, PosixTz>) -> Option<Zone<'a>> {
+    let parts = tzid.split('/').collect::<Vec<_>>();
+    (0..parts.len())
+        .find_map(|i| parts[i..].join("/").parse::<Tz>().ok())
+        .or_else(|| windows_to_iana(tzid).and_then(|zone| zone.parse().ok()))
+        .map(Zone::Tz)
+        .or_else(|| definitions.get(tzid).map(Zone::Posix))
+}
+
+// A local time that happens twice is taken to be the earlier one, and one that's skipped is
+// taken to be in the offset from before the change
+fn local_to_utc(tz: Tz, local: &NaiveDateTime) -> NaiveDateTime {
+    match tz.from_local_datetime(local).earliest() {
+        Some(datetime) => datetime.naive_utc(),
+        None => tz
+            .from_local_datetime(&(*local - Duration::hours(1)))
+            .earliest()
+            .map_or(*local, |datetime| datetime.naive_utc() + Duration::hours(1)),
+    }
+}
+
+// A VTIMEZONE's current rules as a POSIX TZ string: its latest STANDARD and DAYLIGHT
+// observances, which change over every year following their (yearly) RRULEs
+fn definition(vtimezone: &Component) -> Option<PosixTz> {
+    let latest = |name: &str| {
+        vtimezone
+            .children
+            .iter()
+            .filter(|child| child.name == name)
+            .max_by_key(|child| child.value("DTSTART").unwrap_or_default().to_owned())
+    };
+
+    let standard = latest("STANDARD")?;
+    let mut tz = observance(standard)?;
+
+    // Without a rule for each change (e.g. because the zone has stopped observing daylight
+    // saving time), only standard time is left
+    if let Some(daylight) = latest("DAYLIGHT") {
+        if let (Some(start), Some(end)) = (rule(daylight), rule(standard)) {
+            tz = format!("{}{},{},{}", tz, observance(daylight)?, start, end);
+        }
+    }
+
+    PosixTz::parse(&tz)
+}
+
+// An observance's name and offset, like `<CET>-01:00:00`
+fn observance(component: &Component) -> Option<String> {
+    let offset = component.value("TZOFFSETTO")?;
+    let (sign, digits) = match offset.as_bytes().first()? {
+        b'+' => ('-', &offset[1..]),
+        b'-' => ('+', &offset[1..]),
+        _ => return None,
+    };
+    if !(digits.len() == 4 || digits.len() == 6) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let seconds = if digits.len() == 6 {
+        &digits[4..]
+    } else {
+        "00"
+    };
+    let name = component
+        .value("TZNAME")
+        .filter(|name| name.len() >= 3 && !name.contains('>'))
+        .unwrap_or(if component.name == "DAYLIGHT" {
+            "DST"
+        } else {
+            "STD"
+        });
+
+    Some(format!(
+        "<{}>{}{}:{}:{}",
+        name,
+        sign,
+        &digits[..2],
+        &digits[2..4],
+        seconds
+    ))
+}
+
+// When an observance starts as a POSIX rule, like `M3.5.0/02:00:00`, from a yearly RRULE
+// naming a weekday of a month (`FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU`)
+fn rule(component: &Component) -> Option<String> {
+    let rrule = component
+        .value("RRULE")?
+        .split(';')
+        .filter_map(|part| part.split_once('='))
+        .collect::<HashMap<_, _>>();
+
+    if rrule.get("FREQ") != Some(&"YEARLY") {
+        return None;
+    }
+
+    let month = rrule.get("BYMONTH")?.parse::<u32>().ok()?;
+    let byday = rrule.get("BYDAY")?;
+    let (week, weekday) = byday.split_at(byday.len().checked_sub(2)?);
+    let week = match week.parse::<i32>().ok()? {
+        -1 => 5,
+        week @ 1..=4 => week,
+        _ => return None,
+    };
+    let weekday = ["SU", "MO", "TU", "WE", "TH", "FR", "SA"]
+        .iter()
+        .position(|day| *day == weekday)?;
+
+    let start = component.value("DTSTART")?;
+    let time = NaiveDateTime::parse_from_str(start, "%Y%m%dT%H%M%S").ok()?;
+
+    Some(format!(
+        "M{}.{}.{}/{}",
+        month,
+        week,
+        weekday,
+        time.format("%H:%M:%S")
+    ))
+}
+
+// RFC 5545 durations, like `PT1H30M` or `P1D`, or `None` for ones too long for a `Duration`
+fn parse_duration(duration: &str) -> Option<Duration> {
+    let (sign, duration) = match duration.strip_prefix('-') {
+        Some(duration) => (-1, duration),
+        None => (1, duration.strip_prefix('+').unwrap_or(duration)),
+    };
+
+    let mut seconds = 0i64;
+    let mut number = String::new();
+    let mut time = false;
+
+    for c in duration.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if number.is_empty() => time = true,
+            _ => {
+                let n = number.parse::<i64>().ok()?;
+                number.clear();
+                let unit = match (c, time) {
+                    ('W', false) => 7 * 24 * 60 * 60,
+                    ('D', false) => 24 * 60 * 60,
+                    ('H', true) => 60 * 60,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+                seconds = seconds.checked_add(n.checked_mul(unit)?)?;
+            }
+        }
+    }
+
+    // Duration::seconds panics past this
+    if !number.is_empty() || seconds > Duration::max_value().num_seconds() {
+        return None;
+    }
+    Some(Duration::seconds(seconds * sign))
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => unescaped.push(' '),
+                Some(escaped) => unescaped.push(escaped),
+                None => {}
+            },
+            c => unescaped.push(c),
+        }
+    }
+
+    unescaped
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Lines longer than 75 bytes are folded onto continuation lines, without splitting a character
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+
+    folded
+}
+
+#[derive(Debug, Default)]
+struct Component {
+    name: String,
+    properties: Vec<Property>,
+    children: Vec<Component>,
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Component {
+    // The whole file, as a component holding the top-level ones (usually a single VCALENDAR)
+    fn parse(contents: &str) -> Result<Component, String> {
+        let mut stack = vec![Component::default()];
+
+        for (number, line) in unfold(contents) {
+            let property = Property::parse(&line)
+                .ok_or_else(|| format!("line {}: invalid line {:?}", number, line))?;
+
+            match property.name.as_str() {
+                "BEGIN" => stack.push(Component {
+                    name: property.value.to_ascii_uppercase(),
+                    ..Component::default()
+                }),
+                "END" if stack.len() > 1 => {
+                    let component = stack.pop().unwrap_or_default();
+                    if !component.name.eq_ignore_ascii_case(&property.value) {
+                        return Err(format!(
+                            "line {}: END:{} doesn't match BEGIN:{}",
+                            number, property.value, component.name
+                        ));
+                    }
+                    stack.last_mut().unwrap().children.push(component);
+                }
+                "END" => return Err(format!("line {}: END without a BEGIN", number)),
+                _ => stack.last_mut().unwrap().properties.push(property),
+            }
+        }
+
+        match stack.pop() {
+            Some(calendar) if stack.is_empty() => Ok(calendar),
+            _ => Err("the file ends in the middle of a component".to_owned()),
+        }
+    }
+
+    /// Every component called `name` under this one, at any depth
+    fn all(&self, name: &str) -> Vec<&Component> {
+        let mut found = Vec::new();
+        for child in &self.children {
+            if child.name == name {
+                found.push(child);
+            }
+            found.extend(child.all(name));
+        }
+        found
+    }
+
+    fn property(&self, name: &str) -> Option<&Property> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        self.property(name).map(|property| property.value.as_str())
+    }
+}
+
+impl Property {
+    // `NAME;PARAM=value;PARAM="quoted:value":VALUE`
+    fn parse(line: &str) -> Option<Property> {
+        let mut quoted = false;
+        let colon = line.char_indices().find_map(|(i, c)| match c {
+            '"' => {
+                quoted = !quoted;
+                None
+            }
+            ':' if !quoted => Some(i),
+            _ => None,
+        })?;
+
+        let mut parts = split_unquoted(&line[..colon], ';').into_iter();
+        let name = parts.next()?.to_ascii_uppercase();
+        if name.is_empty() {
+            return None;
+        }
+
+        let params = parts
+            .filter_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                Some((
+                    name.to_ascii_uppercase(),
+                    value.trim_matches('"').to_owned(),
+                ))
+            })
+            .collect();
+
+        Some(Property {
+            name,
+            params,
+            value: line[colon + 1..].to_owned(),
+        })
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn split_unquoted(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+
+    for (i, c) in text.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&text[start..i]);
+            start = i + 1;
+        }
+    }
+
+    parts.push(&text[start..]);
+    parts
+}
+
+// Long lines are folded onto several, each continuation starting with a space or tab. Returns
+// the unfolded lines with the (1-based) numbers they start on.
+fn unfold(contents: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some((_, last))) => last.push_str(continuation),
+            _ if line.trim().is_empty() => {}
+            _ => lines.push((i + 1, line.to_owned())),
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVITE: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VTIMEZONE\r
+TZID:Customized Time Zone\r
+BEGIN:STANDARD\r
+DTSTART:16010101T030000\r
+TZOFFSETFROM:+0200\r
+TZOFFSETTO:+0100\r
+RRULE:FREQ=YEARLY;BYDAY=-1SU;BYMONTH=10\r
+END:STANDARD\r
+BEGIN:DAYLIGHT\r
+DTSTART:16010101T020000\r
+TZOFFSETFROM:+0100\r
+TZOFFSETTO:+0200\r
+RRULE:FREQ=YEARLY;BYDAY=-1SU;BYMONTH=3\r
+END:DAYLIGHT\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+SUMMARY:Quarterly review\\, part 1\r
+DTSTART;TZID=Customized Time Zone:20210709T170000\r
+DTEND;TZID=Customized Time Zone:20210709T180000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Stand-up\r
+DTSTART;TZID=\"Pacific Standard Time\":20211215T090000\r
+DURATION:PT15M\r
+RRULE:FREQ=DAILY\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:A very long summary that goes on for long enough\r
+  to be folded\r
+DTSTART;TZID=/mozilla.org/20050126_1/Asia/Kolkata:20210709T170000\r
+DTEND:20210709T123000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Offsite\r
+DTSTART;VALUE=DATE:20210720\r
+DTEND;VALUE=DATE:20210721\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART:20210709T170000\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    fn time(datetime: &str) -> When {
+        When::Time(Utc.datetime_from_str(datetime, "%Y-%m-%d %H:%M").unwrap())
+    }
+
+    #[test]
+    fn test_parse() {
+        let events = parse(INVITE, Tz::Asia__Tokyo).unwrap();
+
+        let starts = events.iter().map(|event| event.start).collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            vec![
+                time("2021-07-09 15:00"),
+                time("2021-12-15 17:00"),
+                time("2021-07-09 11:30"),
+                When::Date(NaiveDate::from_ymd(2021, 7, 20)),
+                time("2021-07-09 08:00"),
+            ]
+        );
+
+        assert_eq!(events[0].summary, "Quarterly review, part 1");
+        assert_eq!(events[0].end, Some(time("2021-07-09 16:00")));
+        assert_eq!(events[1].end, Some(time("2021-12-15 17:15")));
+        assert!(events[1].repeats);
+        assert_eq!(
+            events[2].summary,
+            "A very long summary that goes on for long enough to be folded"
+        );
+        assert_eq!(events[2].end, Some(time("2021-07-09 12:30")));
+        assert_eq!(events[4].summary, "");
+        assert_eq!(events[4].end, None);
+
+        let unknown = "BEGIN:VEVENT\nDTSTART;TZID=Nowhere:20210709T170000\nEND:VEVENT\n";
+        assert_eq!(
+            parse(unknown, Tz::UTC),
+            Err("unknown TZID \"Nowhere\"".to_owned())
+        );
+        assert!(parse("BEGIN:VEVENT\nEND:VTODO\n", Tz::UTC).is_err());
+        assert!(parse("BEGIN:VEVENT\n", Tz::UTC).is_err());
+
+        // Too long to add to the start
+        let endless =
+            "BEGIN:VEVENT\nDTSTART:20210709T170000Z\nDURATION:P9999999999999W\nEND:VEVENT\n";
+        assert_eq!(
+            parse(endless, Tz::UTC),
+            Err("invalid DURATION \"P9999999999999W\"".to_owned())
+        );
+        let endless = "BEGIN:VEVENT\nDTSTART:20210709T170000Z\nDURATION:P999999999D\nEND:VEVENT\n";
+        assert!(parse(endless, Tz::UTC).is_err());
+    }
+
+    #[test]
+    fn test_definition() {
+        let calendar = Component::parse(INVITE).unwrap();
+        let vtimezone = calendar.all("VTIMEZONE")[0];
+        assert_eq!(
+            definition(vtimezone),
+            PosixTz::parse("<STD>-01:00:00<DST>-02:00:00,M3.5.0/02:00:00,M10.5.0/03:00:00")
+        );
+
+        assert_eq!(
+            parse_duration("P1W2DT3H4M5S"),
+            Some(Duration::seconds(788645))
+        );
+        assert_eq!(parse_duration("-PT15M"), Some(Duration::minutes(-15)));
+        assert_eq!(parse_duration("PT15"), None);
+        assert_eq!(parse_duration("P1H"), None);
+        assert_eq!(parse_duration("P9999999999999W"), None);
+        assert_eq!(parse_length("99999999999999999h"), None);
+    }
+
+    #[test]
+    fn test_event() {
+        let start = Tz::Asia__Kolkata.ymd(2021, 7, 9).and_hms(17, 0, 0);
+        let now = Utc.ymd(2021, 7, 1).and_hms(12, 0, 0);
+        let summary =
+            "Planning; budgets, hiring, and a summary long enough that it has to be folded";
+
+        let calendar = event(start, parse_length("1h30m"), Some(summary), now);
+        assert!(calendar.contains("\r\nDTSTART;TZID=Asia/Kolkata:20210709T170000\r\n"));
+        assert!(calendar.contains("\r\nDTEND;TZID=Asia/Kolkata:20210709T183000\r\n"));
+        assert!(calendar.contains("\r\nDTSTAMP:20210701T120000Z\r\n"));
+        assert!(calendar.lines().all(|line| line.len() <= 76));
+
+        // What's written reads back the same
+        let events = parse(&calendar, Tz::UTC).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].summary, summary);
+        assert_eq!(events[0].start, When::Time(start.with_timezone(&Utc)));
+
+        let calendar = event(start.with_timezone(&Tz::UTC), None, None, now);
+        assert!(calendar.contains("\r\nDTSTART:20210709T113000Z\r\n"));
+        assert!(!calendar.contains("DTEND") && !calendar.contains("SUMMARY"));
+
+        assert_eq!(parse_length("PT15M"), Some(Duration::minutes(15)));
+        assert_eq!(parse_length("2h"), Some(Duration::hours(2)));
+        assert_eq!(parse_length("an hour"), None);
+    }
+}
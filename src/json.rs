@@ -0,0 +1,204 @@
+// `tz json --path <path>`: convert the timestamps at jq-style paths in a JSON document read from
+// stdin, for massaging API responses while debugging.
+//
+// tz json --path '.events[].timestamp' --from utc --to ist < response.json
+//
+// Paths are made of `.key` (or `["key"]` for keys with other characters), `[n]` for an element
+// of an array (counting from the end if negative), and `[]` for every element or value. Strings at
+// a path have their timestamps rewritten the same way as `tz filter` does, so stamps with an
+// offset (or `Z`) keep one; anything else (numbers, say, which are epochs and have no zone) is
+// left alone, as are paths that don't exist in the document.
+
+use serde_json::Value;
+use tz::convert::TimestampRewriter;
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(i64),
+    Each,
+}
+
+/// Parse a path like `.events[].timestamp` into its segments
+pub fn parse_path(path: &str) -> Result<Vec<Segment>, String> {
+    let invalid = |reason: &str| format!("Invalid path {:?}: {}", path, reason);
+
+    let mut rest = path.trim();
+    if !rest.starts_with('.') {
+        return Err(invalid("it should start with ."));
+    }
+
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(|| invalid("unclosed ["))?;
+            let inside = after[..end].trim();
+            segments.push(if inside.is_empty() {
+                Segment::Each
+            } else if inside.starts_with('"') {
+                match serde_json::from_str(inside) {
+                    Ok(Value::String(key)) => Segment::Key(key),
+                    _ => return Err(invalid(&format!("bad key {}", inside))),
+                }
+            } else {
+                Segment::Index(
+                    inside
+                        .parse()
+                        .map_err(|_| invalid(&format!("bad index {}", inside)))?,
+                )
+            });
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let key = &after[..end];
+            // `.` alone is the whole document, and `.[]` is the same as `[]`
+            if !key.is_empty() {
+                segments.push(Segment::Key(key.to_owned()));
+            } else if !after.is_empty() && !after.starts_with('[') {
+                return Err(invalid("empty key"));
+            }
+            rest = &after[end..];
+        } else {
+            return Err(invalid(&format!("unexpected {:?}", rest)));
+        }
+    }
+    Ok(segments)
+}
+
+/// What converting the values at a path found
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Converted {
+    /// Values with timestamps that were rewritten
+    pub rewritten: usize,
+    /// Values at the path without any timestamps in them
+    pub skipped: usize,
+}
+
+/// Rewrite the timestamps in every string at `path` in `document`
+pub fn convert(
+    document: &mut Value,
+    path: &[Segment],
+    rewriter: &TimestampRewriter,
+    converted: &mut Converted,
+) {
+    let (segment, rest) = match path.split_first() {
+        Some(split) => split,
+        None => {
+            let rewritten = match document {
+                Value::String(text) => match rewriter.rewrite(text) {
+                    Cow::Owned(rewritten) if rewritten != *text => rewritten,
+                    _ => {
+                        converted.skipped += 1;
+                        return;
+                    }
+                },
+                _ => {
+                    converted.skipped += 1;
+                    return;
+                }
+            };
+            *document = Value::String(rewritten);
+            converted.rewritten += 1;
+            return;
+        }
+    };
+
+    match (segment, document) {
+        (Segment::Key(key), Value::Object(object)) => {
+            if let Some(value) = object.get_mut(key) {
+                convert(value, rest, rewriter, converted);
+            }
+        }
+        (Segment::Index(index), Value::Array(array)) => {
+            let index = if *index < 0 {
+                array.len() as i64 + index
+            } else {
+                *index
+            };
+            if let Some(value) = usize::try_from(index).ok().and_then(|i| array.get_mut(i)) {
+                convert(value, rest, rewriter, converted);
+            }
+        }
+        (Segment::Each, Value::Array(array)) => {
+            for value in array {
+                convert(value, rest, rewriter, converted);
+            }
+        }
+        (Segment::Each, Value::Object(object)) => {
+            for value in object.values_mut() {
+                convert(value, rest, rewriter, converted);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Tz;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_path() {
+        use Segment::*;
+
+        assert_eq!(parse_path("."), Ok(vec![]));
+        assert_eq!(
+            parse_path(".events[].timestamp"),
+            Ok(vec![Key("events".into()), Each, Key("timestamp".into())])
+        );
+        assert_eq!(
+            parse_path(r#".[]["created at"][-1]"#),
+            Ok(vec![Each, Key("created at".into()), Index(-1)])
+        );
+        assert!(parse_path("events").is_err());
+        assert!(parse_path(".events[").is_err());
+        assert!(parse_path(".events[x]").is_err());
+        assert!(parse_path(".events..at").is_err());
+    }
+
+    #[test]
+    fn test_convert() {
+        let rewriter = TimestampRewriter::new(Tz::UTC, Tz::Asia__Kolkata);
+        let mut document = json!({
+            "events": [
+                { "timestamp": "2021-03-10T09:30:00Z", "name": "2021-03-10 09:30" },
+                { "timestamp": "2021-03-10 09:30" },
+                { "timestamp": 1615368600 },
+                { "name": "no timestamp" },
+            ],
+            "updated": "2021-03-10T09:30:00+01:00",
+        });
+
+        let mut converted = Converted::default();
+        let path = parse_path(".events[].timestamp").unwrap();
+        convert(&mut document, &path, &rewriter, &mut converted);
+        assert_eq!(
+            converted,
+            Converted {
+                rewritten: 2,
+                skipped: 1
+            }
+        );
+
+        let path = parse_path(".updated").unwrap();
+        convert(&mut document, &path, &rewriter, &mut converted);
+        assert_eq!(
+            document,
+            json!({
+                "events": [
+                    { "timestamp": "2021-03-10T15:00:00+05:30", "name": "2021-03-10 09:30" },
+                    { "timestamp": "2021-03-10 15:00" },
+                    { "timestamp": 1615368600 },
+                    { "name": "no timestamp" },
+                ],
+                "updated": "2021-03-10T14:00:00+05:30",
+            })
+        );
+    }
+}
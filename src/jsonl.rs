@@ -0,0 +1,105 @@
+// `tz --jsonl`: answer conversions one JSON object per line on stdin/stdout, so an editor plugin
+// can keep one tz process running instead of starting one per conversion.
+//
+// {"from": "utc", "to": "tokyo", "at": "2021-03-10 09:30", "id": 1}
+//
+// `from` and `at` are optional, as with --from and DATETIME. Each response is what `tz serve`
+// answers for /convert (or an `error`), with the request's `id` (of any type) copied onto it.
+
+use chrono::{DateTime, SubsecRound, Utc};
+use serde_json::{json, Map, Value};
+
+use std::io::{self, BufRead, Write};
+
+use crate::serve::Api;
+
+/// Answer requests from stdin until it's closed
+pub fn run(api: &Api) -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        writeln!(
+            stdout,
+            "{}",
+            respond(api, &line, Utc::now().trunc_subsecs(0))
+        )?;
+        // The other end is waiting on this response before sending the next request
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+fn respond(api: &Api, line: &str, now: DateTime<Utc>) -> Value {
+    let request = match serde_json::from_str(line) {
+        Ok(Value::Object(request)) => request,
+        Ok(_) => return json!({ "error": "Each request must be a JSON object" }),
+        Err(e) => return json!({ "error": format!("Invalid JSON: {}", e) }),
+    };
+
+    let mut response = match (
+        field(&request, "from"),
+        field(&request, "to"),
+        field(&request, "at"),
+    ) {
+        (Ok(from), Ok(to), Ok(at)) => api.convert(from, to, at, now).1,
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => json!({ "error": e }),
+    };
+    if let Some(id) = request.get("id") {
+        response["id"] = id.clone();
+    }
+    response
+}
+
+fn field<'a>(request: &'a Map<String, Value>, name: &str) -> Result<Option<&'a str>, String> {
+    match request.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(_) => Err(format!("{} must be a string", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use chrono_tz::Tz;
+
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_respond() {
+        let api = Api::new(Config::default(), Tz::UTC);
+        let now = Utc.ymd(2021, 3, 10).and_hms(9, 30, 0);
+        let respond = |line: &str| respond(&api, line, now);
+
+        let response = respond(r#"{"to": "Asia/Tokyo", "id": 7}"#);
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["from"]["datetime"], "2021-03-10T09:30:00+00:00");
+        assert_eq!(response["to"][0]["datetime"], "2021-03-10T18:30:00+09:00");
+
+        let response =
+            respond(r#"{"from": "kolkata", "to": "utc", "at": "2021-03-10 17:00", "id": "a"}"#);
+        assert_eq!(response["to"][0]["datetime"], "2021-03-10T11:30:00+00:00");
+        assert_eq!(response["id"], "a");
+
+        assert_eq!(
+            respond(r#"{"to": "utc", "at": 1700}"#),
+            json!({ "error": "at must be a string" })
+        );
+        assert_eq!(
+            respond(r#"{"at": "17:00", "id": null}"#),
+            json!({ "error": "to is required", "id": null })
+        );
+        assert_eq!(
+            respond(r#"["utc"]"#),
+            json!({ "error": "Each request must be a JSON object" })
+        );
+        assert!(respond("{").get("error").is_some());
+    }
+}
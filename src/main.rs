@@ -1,11 +1,60 @@
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::FixedOffset;
 use chrono::Local;
 use chrono::TimeZone;
+use chrono::Timelike;
+use chrono::Utc;
 
-use chrono_tz::TZ_VARIANTS;
-use clap::{App, Arg};
-use tz::parse_datetime_in_tz;
-use tz::parse_tz;
-use tz::{convert, current_tz};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use clap::{crate_version, App, AppSettings, Arg, ArgGroup, ArgMatches, Error, ErrorKind};
+#[cfg(feature = "icu")]
+use tz::calendar;
+use tz::convert::{
+    convert, mean_time_offset, projection, ConversionCache, Projection, TimestampRewriter,
+    LAST_TRANSITION_YEAR,
+};
+use tz::format::{check_format, format_datetime_localized, Locale};
+#[cfg(feature = "holidays")]
+use tz::holiday;
+use tz::parse::{is_business_days, try_parse_datetime_with};
+use tz::resolve::{
+    current_tz, detection_sources, system_tzdata_version, tzdata_older, DetectError,
+    BUNDLED_TZDATA_VERSION,
+};
+use tz::scale;
+use tz::solar;
+
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use config::{Config, Template};
+use history::Entry;
+
+mod chat;
+mod check;
+mod config;
+mod cron;
+mod drift;
+mod exif;
+mod gcal;
+mod history;
+mod ics;
+mod json;
+mod jsonl;
+mod metrics;
+mod oncalendar;
+mod outlook;
+mod serve;
+mod share;
+mod sort;
+mod wizard;
+mod worldclock;
 
 // Command-line API
 //
@@ -13,8 +62,57 @@ use tz::{convert, current_tz};
 // tz <tz_id> <datetime> ← Convert time in current TZ to this TZ
 // tz <tz_id> <datetime> --from <to_tz_id> ← Convert time from the TZ to the to TZ
 // tz <tz_id> --from <to_tz_id> ← Convert current time from the from TZ to the to TZ
+// tz [<datetime>] ← Convert time in current TZ to each of the favorite TZs from the config
+// tz "next business day 9am" / "+3 business days" ← Count business days from today in the from TZ, skipping the config's holidays
+// tz batch [--column <n>] ← Convert every line (or CSV column) read from stdin
+// tz filter ← Rewrite timestamps embedded in stdin as lines stream through
+// tz sort [--utc] ← Put lines from stdin in the order their timestamps happened, whatever their zones
+// tz cron <expression> ← Rewrite a cron schedule to run at the same times in another TZ
+// tz crontab [--file <crontab>] ← Rewrite every schedule in a crontab (or stdin) for another TZ
+// tz oncalendar <expression> [--next <n>] ← The same for a systemd timer's OnCalendar= expression
+// tz rewrite <file>... ← Rewrite the timestamps in files in place, keeping a .bak copy of each
+// tz exif <photo.jpg>... [--dry-run] ← Fix the time photos were taken with a camera set to --from, keeping a .bak copy of each
+// tz json --path <path>... ← Convert the timestamps at jq-style paths in JSON read from stdin
+// tz ics <file.ics> ← Convert the start and end of each event in an iCalendar file
+// tz serve [--port <port>] [--address <ip>] ← Answer conversions over HTTP as JSON (see serve.rs)
+// tz --jsonl ← Answer conversions requested as JSON lines on stdin (see jsonl.rs)
+// tz [<datetime>] --json ← Print the conversion as JSON, the same as --jsonl's answers
+// tz --schema ← Print the JSON Schema for --json, --jsonl, and `tz serve`
+// tz [--to <tz_id>] --waybar ← Print the conversion as a Waybar custom module's JSON
+// tz <datetime> --export <NAME> ← Print `export NAME='<converted time>'`, to eval in a shell script
+// tz <datetime> --scale tai|gps ← Print the instant in TAI or GPS time, which count leap seconds
+// tz <datetime> --jd|--mjd ← Print the instant as a (Modified) Julian Date
+// tz <datetime> --calendar <name> ← Also show the date in the Hebrew, Islamic, Persian, or Buddhist calendar (with the icu feature)
+// tz <datetime> --solar <lat>,<long> ← Also show the sun's time of day there, and when it's noon by the sun
+// tz <datetime> --slack ← Print the time as Slack markup, shown in each reader's timezone
+// tz <datetime> --discord [--discord-style <style>] ← The same for Discord
+// tz [-u] [-d <datetime>] [+FORMAT] ← GNU date's flags for the date/time, UTC, and the format
+// tz metrics [--zones <tz_id>,...] [--out <file.prom>] ← Write Prometheus gauges for each zone's offset and DST
+// tz drift [--server <host>] ← Compare the system clock with an NTP server's
+// tz config path ← Print where the config file is read from
+// tz config check ← Point out mistakes in the config file
+// tz config export ← Print the shareable parts of the config (favorites, aliases, groups, ...)
+// tz config import <file-or-url> ← Merge an exported config into this one (URLs with the `import-url` feature)
+// tz <tz_id> <datetime> --ics [--duration 1h] [--summary <text>] ← Print the converted time as a calendar event
+// tz <tz_id> <datetime> --gcal [--duration 1h] [--summary <text>] ← Print a link to add it to Google Calendar
+// tz <tz_id> <datetime> --outlook [--duration 1h] [--summary <text>] ← The same for Outlook on the web
+// tz <datetime> --url [--duration 1h] [--summary <text>] ← Print a timeanddate.com link showing it in any zone
+// tz --last ← Repeat the most recent conversion
+// tz history-list ← List recent conversions
+// tz doctor ← Check the local timezone detection and how old the bundled tzdata is
+// tz <datetime> --to <zone> ← Also notes public holidays where it's converted to, with the `holidays` feature
+// tz <datetime> --lmt=minute ← Round times in local mean time (before a zone standardized) to the minute
+// tz <datetime> --strict-future ← Fail rather than convert a time so far ahead that rule changes could move it
+// tz --profile <name> ... ← Use the favorites, groups, labels, and business hours from a config profile
 //
 // Timezone IDs can be shortcodes (edt, pst, etc.) or country/city names
+//
+// TZ_TO, TZ_FROM, and TZ_FORMAT stand in for --to, --from, and --format when those aren't given,
+// and take precedence over the config
+//
+// The locale (from the config, or LC_ALL/LC_TIME/LANG) applies wherever times are printed with the
+// format. JSON, cron schedules, iCalendar files, and tz sort's --utc prefix are for other programs,
+// so they're the same in every locale.
 
 // TODO:
 // - [x] Basic operation
@@ -25,15 +123,745 @@ use tz::{convert, current_tz};
 // - [ ] Convert time ranges
 
 fn main() {
-    let matches = App::new("tz")
-        .version("0.1")
+    let args = env::args().collect::<Vec<_>>();
+    let matches = app(&args).get_matches_from(&args);
+
+    if matches.occurrences_of("list") == 1 {
+        TZ_VARIANTS.iter().for_each(|tz| println!("{}", tz));
+        return;
+    }
+
+    if matches.is_present("schema") {
+        print!("{}", serve::SCHEMA);
+        return;
+    }
+
+    let verbose = matches.occurrences_of("verbose") == 1;
+    let round_lmt = matches.value_of("lmt") == Some("minute");
+
+    // --config replaces the default location, and unlike the default has to exist
+    let explicit_path = matches.value_of_os("config").map(PathBuf::from);
+    let config_path = explicit_path.clone().or_else(Config::path);
+
+    if let Some(config) = matches.subcommand_matches("config") {
+        run_config(config, explicit_path, config_path);
+        return;
+    }
+
+    if matches.subcommand_matches("doctor").is_some() {
+        run_doctor();
+        return;
+    }
+
+    // Offer to set up a config the first time tz is run by hand (but not from scripts, or
+    // when stdin is being piped into a subcommand)
+    let config = match (explicit_path, config_path) {
+        _ if matches.is_present("no-config") => Ok(Config::default()),
+        (Some(path), _) => Config::read(&path),
+        (None, Some(path))
+            if !path.exists()
+                && matches.subcommand_name().is_none()
+                && wizard::is_interactive() =>
+        {
+            wizard::run(&path)
+        }
+        (None, _) => Config::load(),
+    };
+
+    let config = config.unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        eprintln!("-> Pass --no-config to ignore it");
+        process::exit(1);
+    });
+
+    let config = match matches.value_of("profile") {
+        Some(profile) => config.with_profile(profile).unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }),
+        None => config,
+    };
+
+    // Like GNU date's, -u means UTC on both sides, though --to and --from given on the command line
+    // (rather than through TZ_TO and TZ_FROM) still win
+    let zone_arg = |name: &str| match matches.value_of(name) {
+        Some(_) if matches.is_present("utc") && matches.occurrences_of(name) == 0 => Some("UTC"),
+        None if matches.is_present("utc") => Some("UTC"),
+        zone => zone,
+    };
+    let (to_arg, from_arg) = (zone_arg("to"), zone_arg("from"));
+
+    // GNU date's `+FORMAT` is whichever positional argument starts with a `+` (other than
+    // `+3 business days` and the like), which is the first one when the date/time is given with -d
+    let (datetime_arg, date_format) = match (
+        matches.value_of("DATETIME"),
+        matches.value_of("FORMAT"),
+        matches.value_of("date"),
+    ) {
+        (Some(format), None, date) if format.starts_with('+') && !is_business_days(format) => {
+            (date, Some(&format[1..]))
+        }
+        (Some(_), Some(_), Some(_)) | (Some(_), None, Some(_)) => {
+            eprintln!("error: Give the date/time either as DATETIME or with -d, not both");
+            process::exit(1);
+        }
+        (datetime, Some(format), None) if format.starts_with('+') => (datetime, Some(&format[1..])),
+        (_, Some(format), _) => {
+            eprintln!(
+                "error: Unexpected argument {:?} (a format to print with starts with a +, like date's)",
+                format
+            );
+            process::exit(1);
+        }
+        (datetime, None, date) => (datetime.or(date), None),
+    };
+
+    // An alias or group given as --to may have its own format and template, which --format (or
+    // TZ_FORMAT) still takes precedence over
+    let style = to_arg
+        .and_then(|to| config.style(to))
+        .cloned()
+        .unwrap_or_default();
+
+    let default_format = config.output_format();
+    let format = date_format
+        .or_else(|| matches.value_of("format"))
+        .or(style.format.as_deref())
+        .unwrap_or(&default_format);
+
+    if let Err(e) = check_format(format) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+
+    let locale = config.locale().unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    });
+
+    if matches.subcommand_matches("history-list").is_some() {
+        run_history_list(format, locale);
+        return;
+    }
+
+    if let Some(metrics) = matches.subcommand_matches("metrics") {
+        run_metrics(
+            &config,
+            metrics.value_of("zones"),
+            metrics.value_of_os("out"),
+        );
+        return;
+    }
+
+    if let Some(drift) = matches.subcommand_matches("drift") {
+        let server = drift.value_of("server").expect("server has a default");
+        run_drift(server, format, locale);
+        return;
+    }
+
+    if matches.is_present("last") {
+        let last = history::path()
+            .and_then(|path| history::load(&path).ok())
+            .and_then(|mut entries| entries.pop());
+
+        match last {
+            Some(entry) => print_conversion(
+                &config,
+                entry.datetime,
+                &entry.targets,
+                format,
+                locale,
+                Template::Plain,
+                round_lmt,
+            ),
+            None => {
+                eprintln!("error: No conversions have been recorded yet");
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // --to (or TZ_TO) may name a group of zones; plain conversions without a --to go to the configured
+    // favorites, if there are any
+    let mut targets = match (to_arg, matches.subcommand_name()) {
+        (Some(to), _) => config.resolve_zones(to).unwrap_or_default(),
+        (None, None) => config.favorite_zones(),
+        (None, Some(_)) => Vec::new(),
+    };
+
+    let from_tz = match from_arg.and_then(|tz| config.resolve_zones(tz)) {
+        Some(zones) if zones.len() > 1 => {
+            eprintln!("error: --from must be a single timezone, not a group");
+            process::exit(1);
+        }
+        zones => zones.and_then(|zones| zones.first().copied()),
+    };
+
+    // The server and --jsonl take their zones from each request, with --from only standing in for
+    // a missing one
+    let request_from_tz = || {
+        from_tz.unwrap_or_else(|| {
+            current_tz().unwrap_or_else(|e| {
+                warn_undetected(&e);
+                Tz::UTC
+            })
+        })
+    };
+
+    if matches.is_present("jsonl") {
+        let api = serve::Api::new(config, request_from_tz());
+        jsonl::run(&api).expect("Failed to answer requests");
+        return;
+    }
+
+    if let Some(serve) = matches.subcommand_matches("serve") {
+        let from_tz = request_from_tz();
+        let address = format!(
+            "{}:{}",
+            serve.value_of("address").expect("address has a default"),
+            serve.value_of("port").expect("port has a default")
+        );
+        if let Err(e) = serve::run(&address, serve::Api::new(config, from_tz)) {
+            eprintln!("error: Couldn't serve on {}: {}", address, e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Checked here rather than with `required_unless_present_any`, which clap also enforces on
+    // subcommands that inherit these (global) arguments
+    if to_arg.is_none() && from_arg.is_none() && targets.is_empty() {
+        Error::with_description(
+            "At least one of --to or --from must be provided (or set `favorites` in your \
+             config)\n\nFor more information try --help\n"
+                .to_owned(),
+            ErrorKind::MissingRequiredArgument,
+        )
+        .exit();
+    }
+
+    // Detecting the current timezone hits the filesystem, so only do it if a zone is missing.
+    // Minimal containers often have no timezone configured at all, so that isn't fatal.
+    let detected = OnceCell::new();
+    let local_tz = || {
+        *detected.get_or_init(|| {
+            current_tz().unwrap_or_else(|e| {
+                warn_undetected(&e);
+                Tz::UTC
+            })
+        })
+    };
+
+    let from_tz = from_tz.unwrap_or_else(local_tz);
+    if targets.is_empty() {
+        targets.push(local_tz());
+    }
+
+    if let Some(subcommand) = matches.subcommand_name() {
+        if targets.len() > 1 {
+            eprintln!(
+                "error: `{}` can only convert to a single timezone",
+                subcommand
+            );
+            process::exit(1);
+        }
+    }
+
+    let to_tz = || targets[0];
+
+    if let Some(batch) = matches.subcommand_matches("batch") {
+        let column = batch
+            .value_of("column")
+            .map(|column| match column.parse::<usize>() {
+                Ok(column) if column > 0 => column,
+                _ => {
+                    eprintln!("error: Invalid column {:?}", column);
+                    eprintln!("-> Columns are numbered from 1");
+                    process::exit(1);
+                }
+            });
+        run_batch(from_tz, to_tz(), column, format, locale).expect("Failed to convert batch");
+        return;
+    }
+
+    if matches.subcommand_matches("filter").is_some() {
+        run_filter(from_tz, to_tz()).expect("Failed to filter stdin");
+        return;
+    }
+
+    if let Some(sort) = matches.subcommand_matches("sort") {
+        let rewriter = TimestampRewriter::new(from_tz, Tz::UTC);
+        sort::run(&rewriter, sort.is_present("utc")).expect("Failed to sort stdin");
+        return;
+    }
+
+    if let Some(cron) = matches.subcommand_matches("cron") {
+        let expression = cron.value_of("EXPRESSION").expect("EXPRESSION is required");
+        run_cron(expression, from_tz, to_tz());
+        return;
+    }
+
+    if let Some(crontab) = matches.subcommand_matches("crontab") {
+        run_crontab(crontab.value_of("file"), from_tz, to_tz());
+        return;
+    }
+
+    if let Some(oncalendar) = matches.subcommand_matches("oncalendar") {
+        let expression = oncalendar
+            .value_of("EXPRESSION")
+            .expect("EXPRESSION is required");
+        let next = oncalendar.value_of("next").map_or(0, |next| {
+            next.parse().unwrap_or_else(|_| {
+                eprintln!("error: --next must be a number");
+                process::exit(1);
+            })
+        });
+        run_oncalendar(expression, from_tz, to_tz(), next, format, locale);
+        return;
+    }
+
+    if let Some(rewrite) = matches.subcommand_matches("rewrite") {
+        let files = rewrite.values_of("FILE").expect("FILE is required");
+        run_rewrite(files, from_tz, to_tz());
+        return;
+    }
+
+    if let Some(exif) = matches.subcommand_matches("exif") {
+        let files = exif.values_of("FILE").expect("FILE is required");
+        run_exif(files, from_tz, to_tz(), exif.is_present("dry-run"));
+        return;
+    }
+
+    if let Some(json) = matches.subcommand_matches("json") {
+        let paths = json.values_of("path").expect("path is required");
+        run_json(paths, from_tz, to_tz());
+        return;
+    }
+
+    if let Some(ics) = matches.subcommand_matches("ics") {
+        let file = ics.value_of("FILE").expect("FILE is required");
+        run_ics(file, from_tz, to_tz(), format, locale);
+        return;
+    }
+
+    let datetime = if let Some(datetime) = datetime_arg {
+        try_parse_datetime_with(from_tz, datetime, &config.business_days()).unwrap_or_else(|e| {
+            eprintln!("error: Invalid DATETIME: {}", e);
+            process::exit(1);
+        })
+    } else {
+        from_tz
+            .from_local_datetime(&Local::now().naive_local())
+            .single()
+            .expect("Couldn't determine <now>")
+    };
+
+    // Epochs given to the millisecond or finer keep that precision in the default format
+    let precise_format;
+    let format = if datetime_arg.is_some() && datetime.nanosecond() != 0 && format == default_format
+    {
+        precise_format = default_format.replacen("%S", "%S%.f", 1);
+        precise_format.as_str()
+    } else {
+        format
+    };
+
+    if verbose {
+        warn_stale_tzdata();
+        let targets = targets.iter().map(|tz| tz.name()).collect::<Vec<_>>();
+        eprintln!("-> Converting from {} to {}", from_tz, targets.join(", "));
+        eprintln!("-> Pre-conversion time: {}\n", datetime);
+    }
+
+    warn_projected(
+        datetime,
+        &targets,
+        Utc::now(),
+        matches.is_present("strict-future"),
+    );
+    note_mean_time(datetime, &targets, verbose);
+    #[cfg(feature = "holidays")]
+    note_holidays(datetime, &targets);
+
+    if matches.is_present("event") {
+        let flag = if matches.is_present("ics") {
+            "--ics"
+        } else {
+            "--gcal"
+        };
+        let to_tz = match targets[..] {
+            [to_tz] => to_tz,
+            // The link shows the time in every zone anyway
+            [to_tz, ..] if matches.is_present("url") => to_tz,
+            _ => {
+                eprintln!("error: {} can only convert to a single timezone", flag);
+                process::exit(1);
+            }
+        };
+        let start = convert(datetime, to_tz);
+        // Every output adds the duration to the start, so one that would end past the last date
+        // chrono can hold is as invalid as one that doesn't parse
+        let duration = matches.value_of("duration").map(|duration| {
+            ics::parse_length(duration)
+                .filter(|length| start.checked_add_signed(*length).is_some())
+                .unwrap_or_else(|| {
+                    eprintln!("error: Invalid duration {:?}", duration);
+                    process::exit(1);
+                })
+        });
+
+        let summary = matches.value_of("summary");
+        if matches.is_present("ics") {
+            print!("{}", ics::event(start, duration, summary, Utc::now()));
+        } else if matches.is_present("url") {
+            println!("{}", worldclock::link(start, duration, summary));
+        } else if matches.is_present("outlook") {
+            println!("{}", outlook::link(start, duration, summary));
+        } else {
+            println!("{}", gcal::link(start, duration, summary));
+        }
+    } else if let Some(name) = matches.value_of("export") {
+        let to_tz = match targets[..] {
+            [to_tz] => to_tz,
+            _ => {
+                eprintln!("error: --export can only convert to a single timezone");
+                process::exit(1);
+            }
+        };
+        let value = format_datetime_localized(&shown(datetime, to_tz, round_lmt), format, locale);
+        println!("export {}={}", name, shell_quote(&value));
+    } else if let Some(scale @ ("tai" | "gps")) = matches.value_of("scale") {
+        print_scale(datetime, scale);
+    } else if matches.is_present("jd") || matches.is_present("mjd") {
+        let utc = datetime.with_timezone(&Utc);
+        let dates = [
+            ("JD", matches.is_present("jd"), scale::julian_date(utc)),
+            (
+                "MJD",
+                matches.is_present("mjd"),
+                scale::modified_julian_date(utc),
+            ),
+        ];
+        let dates = dates.iter().filter(|(_, wanted, _)| *wanted);
+        // A lone date is printed bare, to use in scripts
+        if matches.is_present("jd") && matches.is_present("mjd") {
+            for (name, _, date) in dates {
+                println!("{} {:.6}", name, date);
+            }
+        } else {
+            for (_, _, date) in dates {
+                println!("{:.6}", date);
+            }
+        }
+    } else if matches.is_present("json") {
+        println!("{}", serve::conversion(datetime, &targets));
+    } else if matches.is_present("slack") {
+        // Only the fallback text is in a zone, so more than one would go unused
+        let to_tz = match targets[..] {
+            [to_tz] => to_tz,
+            _ => {
+                eprintln!("error: --slack can only convert to a single timezone");
+                eprintln!("-> Pass --to to pick the zone for its fallback text");
+                process::exit(1);
+            }
+        };
+        let converted = convert(datetime, to_tz);
+        let fallback = format_datetime_localized(&converted, format, locale);
+        println!("{}", chat::slack(&converted, &fallback));
+    } else if matches.is_present("discord") {
+        let style = matches.value_of("discord-style").unwrap_or("F");
+        println!("{}", chat::discord(&datetime, style));
+    } else if matches.is_present("waybar") {
+        print_waybar(&config, datetime, &targets, format, locale);
+    } else {
+        print_conversion(
+            &config,
+            datetime,
+            &targets,
+            format,
+            locale,
+            style.template,
+            round_lmt,
+        );
+        #[cfg(feature = "icu")]
+        if let Some(calendar) = matches.value_of("calendar") {
+            let calendar = calendar.parse().expect("calendar is one of CALENDARS");
+            print_calendar_dates(&config, datetime, &targets, calendar);
+        }
+        if let Some(place) = matches.value_of("solar") {
+            let place = place.parse().expect("--solar was validated");
+            print_solar(datetime, targets[0], place, format, locale);
+        }
+
+        // Only conversions someone reads at a terminal are worth repeating with --last. Scripts and
+        // status bars run often enough to push them out, and shouldn't pay to rewrite the file.
+        // The history is a convenience, so failing to record a conversion doesn't fail it.
+        if io::stdout().is_terminal() {
+            if let Some(path) = history::path() {
+                let _ = history::record(&path, &Entry { datetime, targets });
+            }
+        }
+    }
+}
+
+fn print_conversion(
+    config: &Config,
+    datetime: DateTime<Tz>,
+    targets: &[Tz],
+    format: &str,
+    locale: Locale,
+    template: Template,
+    round_lmt: bool,
+) {
+    let color = template == Template::Plain && config.color.enabled();
+
+    // Notes the day a converted time is on, relative to the original, if it's a different one
+    let day_delta = |converted: &DateTime<Tz>| {
+        let days = (converted.naive_local().date() - datetime.naive_local().date()).num_days();
+        if config.day_delta && days != 0 {
+            let plural = if days.abs() == 1 { "" } else { "s" };
+            paint(&format!(" ({:+} day{})", days, plural), YELLOW, color)
+        } else {
+            String::new()
+        }
+    };
+
+    let off_hours = |converted: &DateTime<Tz>| match config.is_business_time(converted) {
+        Some(false) => paint("  (outside business hours)", DIM, color),
+        _ => String::new(),
+    };
+
+    if template == Template::Markdown {
+        println!("| Zone | Time |\n| --- | --- |");
+        for &to_tz in targets {
+            let converted = shown(datetime, to_tz, round_lmt);
+            let result = format_datetime_localized(&converted, format, locale);
+            let time = format!(
+                "{}{}{}",
+                result,
+                day_delta(&converted),
+                off_hours(&converted)
+            );
+            let cell = |text: &str| text.trim().replace('|', "\\|");
+            println!("| {} | {} |", cell(config.label(to_tz)), cell(&time));
+        }
+    } else if let [to_tz] = targets[..] {
+        let converted = shown(datetime, to_tz, round_lmt);
+        let result = format_datetime_localized(&converted, format, locale);
+        println!("{}{}", result, day_delta(&converted));
+    } else {
+        let labels = targets
+            .iter()
+            .map(|&tz| config.label(tz))
+            .collect::<Vec<_>>();
+        let width = labels
+            .iter()
+            .map(|label| label.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        for (&to_tz, label) in targets.iter().zip(labels) {
+            let converted = shown(datetime, to_tz, round_lmt);
+            let result = format_datetime_localized(&converted, format, locale);
+            let label = format!("{:width$}", label, width = width);
+
+            println!(
+                "{}  {}{}{}",
+                paint(&label, BOLD, color),
+                result,
+                day_delta(&converted),
+                off_hours(&converted)
+            );
+        }
+    }
+}
+
+// The date in each of `targets` in another calendar, below the conversion
+#[cfg(feature = "icu")]
+fn print_calendar_dates(
+    config: &Config,
+    datetime: DateTime<Tz>,
+    targets: &[Tz],
+    calendar: calendar::Calendar,
+) {
+    if let [to_tz] = targets[..] {
+        let date = convert(datetime, to_tz).naive_local().date();
+        println!("{}", calendar::format_date(date, calendar));
+        return;
+    }
+
+    let labels = targets
+        .iter()
+        .map(|&tz| config.label(tz))
+        .collect::<Vec<_>>();
+    let width = labels
+        .iter()
+        .map(|label| label.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (&to_tz, label) in targets.iter().zip(labels) {
+        let date = convert(datetime, to_tz).naive_local().date();
+        println!(
+            "{:width$}  {}",
+            label,
+            calendar::format_date(date, calendar),
+            width = width
+        );
+    }
+}
+
+// The sun's time of day at `place`, and when it's highest there that day in `to_tz`
+fn print_solar(
+    datetime: DateTime<Tz>,
+    to_tz: Tz,
+    place: solar::Coordinates,
+    format: &str,
+    locale: Locale,
+) {
+    let utc = datetime.with_timezone(&Utc);
+    let mean = solar::mean_solar_time(utc, place.longitude);
+    let noon = solar::solar_noon(mean.date(), place.longitude);
+
+    println!(
+        "Mean solar time      {}  ({})",
+        mean.format("%Y-%m-%d %H:%M:%S"),
+        place
+    );
+    println!(
+        "Apparent solar time  {}",
+        solar::apparent_solar_time(utc, place.longitude).format("%Y-%m-%d %H:%M:%S")
+    );
+    println!(
+        "Solar noon           {}",
+        format_datetime_localized(
+            &convert(noon.with_timezone(&Tz::UTC), to_tz),
+            format,
+            locale
+        )
+    );
+}
+
+// The instant on a time scale that counts leap seconds, which is the same whatever the zone
+fn print_scale(datetime: DateTime<Tz>, scale: &str) {
+    let utc = datetime.with_timezone(&Utc);
+    let printed = match scale {
+        "tai" => scale::tai(utc).map(|tai| {
+            format!(
+                "{} TAI  (UTC + {} s)",
+                tai.format("%Y-%m-%d %H:%M:%S"),
+                scale::tai_minus_utc(utc).unwrap_or_default()
+            )
+        }),
+        _ => scale::gps(utc).map(|gps| {
+            format!(
+                "{} GPS  ({} s since 1980-01-06; week {}, second {})",
+                gps.datetime.format("%Y-%m-%d %H:%M:%S"),
+                gps.seconds,
+                gps.week,
+                gps.seconds_of_week
+            )
+        }),
+    };
+
+    match printed {
+        Some(printed) => println!("{}", printed),
+        None if scale == "tai" => {
+            eprintln!(
+                "error: TAI can only be given from 1972, when UTC started using leap seconds"
+            );
+            process::exit(1);
+        }
+        None => {
+            eprintln!("error: GPS time started on 1980-01-06");
+            process::exit(1);
+        }
+    }
+}
+
+// The first target as Waybar's `text`, with it and the favorites listed in the `tooltip`, for a
+// custom module with `"return-type": "json"`
+fn print_waybar(
+    config: &Config,
+    datetime: DateTime<Tz>,
+    targets: &[Tz],
+    format: &str,
+    locale: Locale,
+) {
+    let mut zones = targets.to_vec();
+    for tz in config.favorite_zones() {
+        if !zones.contains(&tz) {
+            zones.push(tz);
+        }
+    }
+
+    let time = |tz: Tz| format_datetime_localized(&convert(datetime, tz), format, locale);
+    // Waybar reads tooltips as Pango markup
+    let escape = |text: &str| {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+    let tooltip = zones
+        .iter()
+        .map(|&tz| format!("{}  {}", escape(config.label(tz)), escape(&time(tz))))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    println!(
+        "{}",
+        serde_json::json!({ "text": time(targets[0]), "tooltip": tooltip })
+    );
+}
+
+// Single quotes keep everything literal, except single quotes themselves, which have to be closed
+// over
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+const BOLD: &str = "1";
+const DIM: &str = "2";
+const YELLOW: &str = "33";
+
+fn paint(text: &str, style: &str, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", style, text)
+    } else {
+        text.to_owned()
+    }
+}
+
+const SUBCOMMANDS: &[&str] = &[
+    "batch",
+    "filter",
+    "sort",
+    "cron",
+    "crontab",
+    "oncalendar",
+    "ics",
+    "rewrite",
+    "exif",
+    "json",
+    "serve",
+    "drift",
+    "metrics",
+    "config",
+    "doctor",
+    "history-list",
+];
+
+fn app(args: &[String]) -> App<'static> {
+    let app = App::new("tz")
+        .version(crate_version!())
         .about("Convert between timezones")
         .arg(
             Arg::new("to")
                 .short('t')
                 .long("to")
                 .takes_value(true)
-                .required_unless_present_any(&["from"])
+                .env("TZ_TO")
+                .global(true)
                 .about("Timezone to convert to (defaults to your current TZ)")
         )
         .arg(
@@ -41,9 +869,25 @@ fn main() {
                 .short('f')
                 .long("from")
                 .takes_value(true)
-                .required_unless_present_any(&["to"])
+                .env("TZ_FROM")
+                .global(true)
                 .about("Timezone to convert from (defaults to your current TZ)")
         )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .takes_value(true)
+                .env("TZ_FORMAT")
+                .global(true)
+                .about("strftime-style format for converted times, e.g. \"%H:%M %Z\", with %EC, %Ey, and %EY for Japanese eras (Reiwa 3) (overrides the config)"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .takes_value(true)
+                .global(true)
+                .about("Config file to use instead of the default one (see `tz config path`)"),
+        )
         .arg(
             Arg::new("verbose")
                 .short('v')
@@ -57,50 +901,1154 @@ fn main() {
                 .takes_value(false)
                 .about("List all available timezones"),
         )
+        .arg(
+            Arg::new("no-config")
+                .long("no-config")
+                .takes_value(false)
+                .global(true)
+                .conflicts_with("config")
+                .about("Ignore the config file and use the defaults"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .takes_value(true)
+                .env("TZ_PROFILE")
+                .global(true)
+                .about("Use the favorites, groups, labels, and business hours from this profile in the config"),
+        )
+        .arg(
+            Arg::new("last")
+                .long("last")
+                .takes_value(false)
+                .about("Repeat the most recent plain conversion printed to a terminal"),
+        )
+        .arg(
+            Arg::new("ics")
+                .long("ics")
+                .takes_value(false)
+                .about("Print the converted time as an iCalendar event, to import into a calendar"),
+        )
+        .arg(
+            Arg::new("jsonl")
+                .long("jsonl")
+                .takes_value(false)
+                .conflicts_with_all(&["ics", "gcal", "outlook", "url", "DATETIME", "date"])
+                .about("Read conversions as JSON lines on stdin ({\"from\": .., \"to\": .., \"at\": ..}) and answer each with a JSON line"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .takes_value(false)
+                .conflicts_with_all(&["ics", "gcal", "outlook", "url", "jsonl", "waybar", "export"])
+                .about("Print the conversion as JSON, in the same shape as --jsonl's answers (see --schema)"),
+        )
+        .arg(
+            Arg::new("schema")
+                .long("schema")
+                .takes_value(false)
+                .about("Print the JSON Schema for --json and --jsonl output, and `tz serve`'s conversions"),
+        )
+        .arg(
+            Arg::new("export")
+                .long("export")
+                .takes_value(true)
+                .value_name("NAME")
+                .validator(|name| {
+                    let mut chars = name.chars();
+                    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+                    if valid {
+                        Ok(())
+                    } else {
+                        Err(format!("{:?} isn't a valid variable name", name))
+                    }
+                })
+                .conflicts_with_all(&["ics", "gcal", "outlook", "url", "jsonl", "waybar"])
+                .about("Print a shell command setting the variable NAME to the converted time (e.g. with --format %s for the epoch), for `eval \"$(tz ... --export NAME)\"`"),
+        )
+        .arg(
+            Arg::new("scale")
+                .long("scale")
+                .takes_value(true)
+                .possible_values(&["utc", "tai", "gps"])
+                .conflicts_with_all(&["ics", "gcal", "outlook", "url", "jsonl", "waybar", "export", "json"])
+                .about("Time scale to print the instant on: utc (the default, in --to), or tai or gps, which count leap seconds, with GPS's week and seconds"),
+        )
+        .arg(
+            Arg::new("jd")
+                .long("jd")
+                .takes_value(false)
+                .conflicts_with_all(&["ics", "gcal", "outlook", "url", "jsonl", "waybar", "export", "json", "scale"])
+                .about("Print the instant as a Julian Date"),
+        )
+        .arg(
+            Arg::new("mjd")
+                .long("mjd")
+                .takes_value(false)
+                .conflicts_with_all(&["ics", "gcal", "outlook", "url", "jsonl", "waybar", "export", "json", "scale"])
+                .about("Print the instant as a Modified Julian Date (after the Julian Date, with --jd)"),
+        )
+        .arg(
+            Arg::new("solar")
+                .long("solar")
+                .takes_value(true)
+                .value_name("LAT,LONG")
+                .allow_hyphen_values(true)
+                .validator(|place| place.parse::<solar::Coordinates>().map(|_| ()))
+                .conflicts_with_all(&["ics", "gcal", "outlook", "url", "jsonl", "waybar", "export", "json", "scale", "jd", "mjd", "slack", "discord"])
+                .about("Also show the mean and apparent solar time at these coordinates (in degrees, e.g. 12.97,77.59), and when the sun is highest there in --to"),
+        )
+        .arg(
+            Arg::new("lmt")
+                .long("lmt")
+                .takes_value(true)
+                .value_name("ROUNDING")
+                .possible_values(&["exact", "minute"])
+                .about("How to show times in local mean time, which zones kept before adopting a standard offset and is off UTC by seconds: exact (the default), or rounded to the nearest minute"),
+        )
+        .arg(
+            Arg::new("strict-future")
+                .long("strict-future")
+                .takes_value(false)
+                .about("Fail instead of converting times far enough ahead that a change to the zones' rules could change the answer (more than a year, or six months in zones that changed recently)"),
+        )
+        .arg(
+            Arg::new("slack")
+                .long("slack")
+                .takes_value(false)
+                .conflicts_with_all(&["ics", "gcal", "outlook", "url", "jsonl", "waybar", "export", "json"])
+                .about("Print Slack markup that shows the converted time in each reader's own timezone"),
+        )
+        .arg(
+            Arg::new("discord")
+                .long("discord")
+                .takes_value(false)
+                .conflicts_with_all(&["ics", "gcal", "outlook", "url", "jsonl", "waybar", "slack", "export", "json"])
+                .about("Print Discord markup that shows the converted time in each reader's own timezone"),
+        )
+        .arg(
+            Arg::new("discord-style")
+                .long("discord-style")
+                .takes_value(true)
+                .value_name("STYLE")
+                .possible_values(chat::DISCORD_STYLES)
+                .requires("discord")
+                .about("How Discord shows the time: t, T, d, D, f, F (the default), or R (relative)"),
+        )
+        .arg(
+            Arg::new("waybar")
+                .long("waybar")
+                .takes_value(false)
+                .conflicts_with_all(&["ics", "gcal", "outlook", "url", "jsonl"])
+                .about("Print the conversion as JSON for a Waybar custom module: the first zone as the text, with every zone (and favorite) in the tooltip"),
+        )
+        .arg(
+            Arg::new("gcal")
+                .long("gcal")
+                .takes_value(false)
+                .about("Print a link that creates a Google Calendar event at the converted time"),
+        )
+        .arg(
+            Arg::new("url")
+                .long("url")
+                .takes_value(false)
+                .about("Print a timeanddate.com link that shows the converted time in the zone of whoever opens it"),
+        )
+        .arg(
+            Arg::new("outlook")
+                .long("outlook")
+                .takes_value(false)
+                .about("Print a link that creates an Outlook (or Teams) meeting at the converted time"),
+        )
+        .group(ArgGroup::new("event").args(&["ics", "gcal", "outlook", "url"]))
+        .arg(
+            Arg::new("duration")
+                .long("duration")
+                .takes_value(true)
+                .requires("event")
+                .about("How long the --ics, --gcal, --outlook, or --url event lasts, e.g. 1h30m or 45m (--gcal and --outlook default to 1h)"),
+        )
+        .arg(
+            Arg::new("summary")
+                .long("summary")
+                .takes_value(true)
+                .requires("event")
+                .about("Title of the --ics, --gcal, --outlook, or --url event"),
+        )
+        .arg(
+            Arg::new("date")
+                .short('d')
+                .long("date")
+                .takes_value(true)
+                .about("Date/time to convert, as with GNU date's -d (the same as DATETIME)"),
+        )
+        .arg(
+            Arg::new("utc")
+                .short('u')
+                .long("utc")
+                .takes_value(false)
+                .about("Convert from and to UTC unless --from or --to say otherwise, as with GNU date's -u"),
+        )
         .arg(
             Arg::new("DATETIME")
-                .about("Date or time (or both) to convert, defaults to <now> (allowed formats are YYYY-MM-DD, HH:MM, YYYY-MM-DD HH:MM, and HHam/pm)")
+                .about("Date or time (or both) to convert, defaults to <now> (allowed formats are YYYY-MM-DD, HH:MM, YYYY-MM-DD HH:MM, HHam/pm, and Unix epochs in seconds, ms, µs, or ns by their 10, 13, 16, or 19 digits, or @<seconds>)")
                 .required(false)
                 .index(1),
         )
-        .get_matches();
+        .arg(
+            Arg::new("FORMAT")
+                .about("+FORMAT, a strftime-style format to print the converted time with, as with GNU date (overrides --format)")
+                .required(false)
+                .index(2),
+        );
 
-    if matches.occurrences_of("list") == 1 {
-        TZ_VARIANTS.iter().for_each(|tz| println!("{}", tz));
-        return;
+    #[cfg(feature = "icu")]
+    let app = app.arg(
+        Arg::new("calendar")
+            .long("calendar")
+            .takes_value(true)
+            .possible_values(calendar::CALENDARS)
+            .conflicts_with_all(&["ics", "gcal", "outlook", "url", "jsonl", "waybar", "export", "json", "scale", "jd", "mjd", "slack", "discord"])
+            .about("Also show the converted date in another calendar: hebrew, islamic (Umm al-Qura), persian, or buddhist"),
+    );
+
+    // Status bars run plain conversions several times a minute, so only build the definitions
+    // of subcommands that are actually named on the command line (or all of them for help).
+    let wants_help = args
+        .iter()
+        .skip(1)
+        .any(|arg| arg == "help" || arg == "-h" || arg == "--help");
+
+    SUBCOMMANDS
+        .iter()
+        .filter(|&&name| wants_help || args.iter().skip(1).any(|arg| arg == name))
+        .fold(app, |app, &name| app.subcommand(subcommand(name)))
+}
+
+fn subcommand(name: &'static str) -> App<'static> {
+    match name {
+        "batch" => App::new("batch")
+            .about("Convert date/times read from stdin, one per line")
+            .arg(
+                Arg::new("column")
+                    .short('c')
+                    .long("column")
+                    .takes_value(true)
+                    .about("Treat each line as CSV and only convert this (1-based) column"),
+            ),
+        "filter" => App::new("filter")
+            .about("Rewrite timestamps embedded in lines read from stdin, one line at a time (ISO 8601, syslog, Apache/nginx, java.util.logging, and Org mode stamps)"),
+        "sort" => App::new("sort")
+            .about("Put lines read from stdin in the order they happened, by the first timestamp on each (stamps without an offset are in --from)")
+            .arg(
+                Arg::new("utc")
+                    .long("utc")
+                    .about("Prefix each line with its timestamp in UTC"),
+            ),
+        "cron" => App::new("cron")
+            .about("Rewrite a cron schedule for --from to run at the same times in --to")
+            .arg(
+                Arg::new("EXPRESSION")
+                    .about("The schedule, e.g. \"0 9 * * 1-5\" (quoted, as one argument)")
+                    .required(true)
+                    .index(1),
+            ),
+        "crontab" => App::new("crontab")
+            .about("Rewrite every schedule in a crontab for --from to run at the same times in --to, keeping the commands")
+            .arg(
+                Arg::new("file")
+                    .long("file")
+                    .takes_value(true)
+                    .about("The crontab to read (defaults to stdin)"),
+            ),
+        "oncalendar" => App::new("oncalendar")
+            .about("Rewrite a systemd timer's OnCalendar= expression for --from to elapse at the same times in --to")
+            .arg(
+                Arg::new("EXPRESSION")
+                    .about("The expression, e.g. \"Mon..Fri 09:00\" (quoted, as one argument)")
+                    .required(true)
+                    .index(1),
+            )
+            .arg(
+                Arg::new("next")
+                    .long("next")
+                    .takes_value(true)
+                    .about("Also list the next <next> times the timer elapses, in both zones"),
+            ),
+        "ics" => App::new("ics")
+            .about("Convert the start and end of each event in an iCalendar (.ics) file")
+            .arg(
+                Arg::new("FILE")
+                    .about("The .ics file to read")
+                    .required(true)
+                    .index(1),
+            ),
+        "rewrite" => App::new("rewrite")
+            .about("Rewrite the timestamps in files (Markdown, Org, logs, ...) in place, keeping the originals as <file>.bak (or <file>.bak.1, ... if that exists)")
+            .arg(
+                Arg::new("FILE")
+                    .about("The files to rewrite")
+                    .required(true)
+                    .multiple(true)
+                    .index(1),
+            ),
+        "exif" => App::new("exif")
+            .about("Rewrite when JPEG photos were taken (DateTimeOriginal and OffsetTimeOriginal) for a camera whose clock was set to --from, as a time in --to, keeping the originals as <file>.bak")
+            .arg(
+                Arg::new("FILE")
+                    .about("The photos to rewrite")
+                    .required(true)
+                    .multiple(true)
+                    .index(1),
+            )
+            .arg(
+                Arg::new("dry-run")
+                    .long("dry-run")
+                    .takes_value(false)
+                    .about("Only show what the times would change to"),
+            ),
+        "json" => App::new("json")
+            .about("Convert the timestamps at jq-style paths (like .events[].timestamp) in JSON read from stdin, and print the result")
+            .arg(
+                Arg::new("path")
+                    .long("path")
+                    .takes_value(true)
+                    .multiple_occurrences(true)
+                    .required(true)
+                    .about("Path to the values to convert: .key, [\"key\"], [n] for an array element, or [] for every element (repeatable)"),
+            ),
+        "serve" => App::new("serve")
+            .about("Serve a JSON API for conversions (/convert?from=&to=&at=) and zone lookups (/zones?q=) over HTTP")
+            .arg(
+                Arg::new("port")
+                    .long("port")
+                    .takes_value(true)
+                    .default_value("8080")
+                    .about("Port to listen on"),
+            )
+            .arg(
+                Arg::new("address")
+                    .long("address")
+                    .takes_value(true)
+                    .default_value("127.0.0.1")
+                    .about("Address to listen on; 0.0.0.0 serves other machines too"),
+            ),
+        "drift" => App::new("drift")
+            .about("Check the system clock against an NTP server, since every conversion of <now> depends on it")
+            .arg(
+                Arg::new("server")
+                    .long("server")
+                    .takes_value(true)
+                    .default_value(drift::DEFAULT_SERVER)
+                    .about("NTP server to ask"),
+            ),
+        "metrics" => App::new("metrics")
+            .about("Print (or write) Prometheus gauges for each zone's UTC offset, daylight saving time, and seconds until its next transition")
+            .arg(
+                Arg::new("zones")
+                    .long("zones")
+                    .takes_value(true)
+                    .about("Comma-separated zones (or aliases, or groups) to report on (defaults to the favorites)"),
+            )
+            .arg(
+                Arg::new("out")
+                    .long("out")
+                    .takes_value(true)
+                    .about("File to write the metrics to, e.g. in node_exporter's textfile collector directory"),
+            ),
+        "config" => App::new("config")
+            .about("Inspect the config file")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(App::new("path").about("Print where the config file is read from"))
+            .subcommand(App::new("check").about(
+                "Point out unknown zones, clashing aliases, bad formats, and other mistakes in the config",
+            ))
+            .subcommand(App::new("export").about(
+                "Print the favorites, format, aliases, groups, and labels from the config, to share or import elsewhere",
+            ))
+            .subcommand(
+                App::new("import")
+                    .about("Merge an exported config into this one")
+                    .arg(
+                        Arg::new("SOURCE")
+                            .about("File or http(s) URL to import (URLs need the import-url feature)")
+                            .required(true)
+                            .index(1),
+                    ),
+            ),
+        "doctor" => App::new("doctor")
+            .about("Check the things conversions depend on: the detected local timezone, and whether the bundled tzdata is older than the system's"),
+        "history-list" => App::new("history-list").about("List recent conversions, oldest first"),
+        _ => unreachable!("Unknown subcommand {}", name),
     }
+}
 
-    let verbose = matches.occurrences_of("verbose") == 1;
-    let current_tz = current_tz().expect("Failed to determine current timezone");
-
-    let to_tz = matches
-        .value_of("to")
-        .map(|tz| parse_tz(tz))
-        .flatten()
-        .unwrap_or(current_tz);
-
-    let from_tz = matches
-        .value_of("from")
-        .map(|tz| parse_tz(tz))
-        .flatten()
-        .unwrap_or(current_tz);
-
-    let datetime = matches.value_of("DATETIME");
-    let datetime = if let Some(datetime) = datetime {
-        parse_datetime_in_tz(from_tz, datetime).expect("Invalid DATETIME")
+// Offsets far enough ahead depend on rules that may change (or, past the tzdata's last
+// transitions, on ones it doesn't have). With --strict-future, that's an error.
+fn warn_projected(datetime: DateTime<Tz>, targets: &[Tz], now: DateTime<Utc>, strict: bool) {
+    let mut zones = vec![datetime.timezone()];
+    zones.extend(targets.iter().filter(|&&tz| tz != datetime.timezone()));
+
+    let mut projected = false;
+    for tz in zones {
+        match projection(&datetime.with_timezone(&tz), now) {
+            Some(Projection::PastTransitions) => {
+                eprintln!(
+                    "warning: The bundled tzdata has no transitions after {}, so {} is shown \
+                     without daylight saving time",
+                    LAST_TRANSITION_YEAR, tz
+                );
+                projected = true;
+            }
+            Some(Projection::VolatileRules { since }) => {
+                eprintln!(
+                    "warning: {} has only kept its current offsets since {}, so its time could \
+                     change when tzdata is updated",
+                    tz, since
+                );
+                projected = true;
+            }
+            Some(_) => projected = true,
+            None => {}
+        }
+    }
+    if projected {
+        eprintln!(
+            "warning: {} is projected from current rules, which may change before then",
+            datetime.format("%Y-%m-%d")
+        );
+        if strict {
+            eprintln!("error: Not converting a projected time with --strict-future");
+            process::exit(1);
+        }
+    }
+}
+
+// `datetime` in `tz`, to show. Before a zone adopted a standard offset its clocks kept local mean
+// time, to the second, which --lmt=minute rounds to the nearest minute.
+fn shown(datetime: DateTime<Tz>, tz: Tz, round_lmt: bool) -> DateTime<Tz> {
+    let converted = convert(datetime, tz);
+    if !round_lmt || mean_time_offset(&converted).is_none() {
+        return converted;
+    }
+    let seconds = i64::from(converted.second());
+    let rounded = converted
+        .with_nanosecond(0)
+        .expect("0 is a valid nanosecond");
+    if seconds >= 30 {
+        rounded + Duration::seconds(60 - seconds)
     } else {
-        from_tz
-            .from_local_datetime(&Local::now().naive_local())
-            .single()
-            .expect("Couldn't determine <now>")
+        rounded - Duration::seconds(seconds)
+    }
+}
+
+// An offset that's otherwise printed without its seconds (as %z does), like +05:21:10
+fn offset_with_seconds(offset: FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let seconds = seconds.abs();
+    format!(
+        "{}{:02}:{:02}:{:02}",
+        sign,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+// Offsets with seconds in them look like mistakes, so say where they come from
+fn note_mean_time(datetime: DateTime<Tz>, targets: &[Tz], verbose: bool) {
+    let mut zones = vec![datetime.timezone()];
+    zones.extend(targets.iter().filter(|&&tz| tz != datetime.timezone()));
+
+    let mut noted = false;
+    for tz in zones {
+        let converted = convert(datetime, tz);
+        if let Some(offset) = mean_time_offset(&converted) {
+            eprintln!(
+                "note: In {}, {} kept local mean time ({}, {}) rather than a standard offset",
+                converted.format("%Y"),
+                tz,
+                converted.format("%Z"),
+                offset_with_seconds(offset)
+            );
+            noted = true;
+        }
+    }
+    if noted && verbose {
+        eprintln!(
+            "-> Before standard time, clocks were set by the sun at a nearby city, and so were off \
+             UTC by minutes and seconds. tzdata's offsets for those times are approximate."
+        );
+        eprintln!("-> Pass --lmt=minute to round times kept that way to the nearest minute");
+    }
+}
+
+// Public holidays matter as much as business hours when scheduling, so note any the converted
+// time falls on where it's converted to
+#[cfg(feature = "holidays")]
+fn note_holidays(datetime: DateTime<Tz>, targets: &[Tz]) {
+    let mut noted = Vec::new();
+    for &tz in targets {
+        let date = datetime.with_timezone(&tz).date().naive_local();
+        if let Some(holiday) = holiday::holiday(date, tz) {
+            if !noted.contains(&(date, holiday)) {
+                eprintln!(
+                    "note: {} is a public holiday in {} ({})",
+                    date.format("%b %-d"),
+                    holiday.country,
+                    holiday.name
+                );
+                noted.push((date, holiday));
+            }
+        }
+    }
+}
+
+// Whether the zone rules built into tz are older than the system's, and so may be missing changes
+// it has. Returns whether they are.
+fn warn_stale_tzdata() -> bool {
+    match system_tzdata_version() {
+        Some(system) if tzdata_older(BUNDLED_TZDATA_VERSION, &system) => {
+            eprintln!(
+                "warning: The bundled tzdata ({}) is older than the system's ({}), so conversions \
+                 may miss recent changes to zones' rules",
+                BUNDLED_TZDATA_VERSION, system
+            );
+            eprintln!("-> A newer release of tz will have newer tzdata");
+            true
+        }
+        _ => false,
+    }
+}
+
+fn run_doctor() {
+    let mut problems = 0;
+
+    match current_tz() {
+        Ok(tz) => println!("Local timezone: {}", tz),
+        Err(e) => {
+            println!("Local timezone: not detected");
+            warn_undetected(&e);
+            problems += 1;
+        }
+    }
+
+    println!("Bundled tzdata: {}", BUNDLED_TZDATA_VERSION);
+    match system_tzdata_version() {
+        Some(system) => println!("System tzdata:  {}", system),
+        None => println!("System tzdata:  unknown"),
+    }
+    if warn_stale_tzdata() {
+        problems += 1;
+    }
+
+    if problems > 0 {
+        process::exit(1);
+    }
+}
+
+fn warn_undetected(error: &DetectError) {
+    eprintln!("warning: Using UTC as the local timezone: {}", error);
+    eprintln!("-> Looked in {}", detection_sources().join(", "));
+    eprintln!(
+        "-> Pass --from and --to (or set TZ_FROM and TZ_TO), set `favorites` in your config, or \
+         configure the system's timezone"
+    );
+}
+
+fn run_metrics(config: &Config, zones: Option<&str>, out: Option<&std::ffi::OsStr>) {
+    let mut zones: Vec<Tz> = match zones {
+        Some(zones) => zones
+            .split(',')
+            .flat_map(|name| {
+                config.resolve_zones(name.trim()).unwrap_or_else(|| {
+                    eprintln!("error: Unknown timezone {:?}", name);
+                    process::exit(1);
+                })
+            })
+            .collect(),
+        None => config.favorite_zones(),
     };
+    // Prometheus rejects the whole file if a series appears twice
+    let mut seen = Vec::new();
+    zones.retain(|tz| {
+        let new = !seen.contains(tz);
+        seen.push(*tz);
+        new
+    });
+    if zones.is_empty() {
+        eprintln!("error: No zones to report on");
+        eprintln!("-> Pass --zones, or set `favorites` in your config");
+        process::exit(1);
+    }
 
-    if verbose {
-        eprintln!("-> Converting from {} to {}", from_tz, to_tz);
-        eprintln!("-> Pre-conversion time: {}\n", datetime);
+    let text = metrics::render(&zones, Utc::now());
+    match out {
+        Some(path) => metrics::write(path.as_ref(), &text).unwrap_or_else(|e| {
+            eprintln!("error: Couldn't write {}: {}", path.to_string_lossy(), e);
+            process::exit(1);
+        }),
+        None => print!("{}", text),
+    }
+}
+
+// A clock more than this far off makes conversions of <now> visibly wrong
+const DRIFT_WARNING_MILLISECONDS: i64 = 1000;
+
+fn run_drift(server: &str, format: &str, locale: Locale) {
+    let drift = drift::measure(server).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    });
+
+    let seconds = |duration: chrono::Duration| duration.num_milliseconds() as f64 / 1000.0;
+    let direction = if drift.ahead < chrono::Duration::zero() {
+        "behind"
+    } else {
+        "ahead of"
+    };
+    println!(
+        "The system clock is {:.3}s {} {} (±{:.3}s)",
+        seconds(drift.ahead).abs(),
+        direction,
+        server,
+        seconds(drift.round_trip) / 2.0
+    );
+
+    match current_tz() {
+        Ok(tz) => {
+            let now = Utc::now().with_timezone(&tz) - drift.ahead;
+            println!(
+                "The time in {} is {}",
+                tz,
+                format_datetime_localized(&now, format, locale)
+            );
+        }
+        Err(e) => warn_undetected(&e),
+    }
+
+    if drift.ahead.num_milliseconds().abs() > DRIFT_WARNING_MILLISECONDS {
+        eprintln!("warning: Conversions of <now> will be off by as much as the clock is");
+        eprintln!("-> Turn on time synchronization (e.g. `timedatectl set-ntp true`)");
+    }
+}
+
+fn run_history_list(format: &str, locale: Locale) {
+    let entries = history::path().map_or(Ok(Vec::new()), |path| history::load(&path));
+    let entries = entries.unwrap_or_else(|e| {
+        eprintln!("error: Couldn't read the history: {}", e);
+        process::exit(1);
+    });
+
+    for entry in entries {
+        let targets = entry.targets.iter().map(|tz| tz.name()).collect::<Vec<_>>();
+        println!(
+            "{} -> {}",
+            format_datetime_localized(&entry.datetime, format, locale),
+            targets.join(", ")
+        );
+    }
+}
+
+fn run_config(matches: &ArgMatches, explicit_path: Option<PathBuf>, path: Option<PathBuf>) {
+    let path = path.unwrap_or_else(|| {
+        eprintln!("error: Couldn't find a home directory to look for a config in");
+        process::exit(1);
+    });
+
+    let fail = |e: &dyn std::fmt::Display| -> ! {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    };
+
+    if matches.subcommand_matches("path").is_some() {
+        println!("{}", path.display());
+        if !path.exists() {
+            eprintln!("-> No config file exists there yet, so the defaults are used");
+        }
+    } else if matches.subcommand_matches("check").is_some() {
+        let contents = fs::read_to_string(&path)
+            .unwrap_or_else(|e| fail(&format!("Couldn't read {}: {}", path.display(), e)));
+
+        let problems = check::check(&contents);
+        for problem in &problems {
+            eprintln!("{}: {}", path.display(), problem);
+        }
+
+        match problems.len() {
+            0 => println!("{}: no problems found", path.display()),
+            1 => process::exit(1),
+            n => {
+                eprintln!("-> {} problems found", n);
+                process::exit(1);
+            }
+        }
+    } else if matches.subcommand_matches("export").is_some() {
+        let config = match explicit_path {
+            Some(path) => Config::read(&path),
+            None => Config::load(),
+        };
+        let config = config.unwrap_or_else(|e| fail(&e));
+        print!("{}", share::Shared::from_config(&config).to_toml());
+    } else if let Some(import) = matches.subcommand_matches("import") {
+        let source = import.value_of("SOURCE").expect("SOURCE is required");
+        let document = share::fetch(source).unwrap_or_else(|e| fail(&e));
+
+        let existing = if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .unwrap_or_else(|e| fail(&format!("Couldn't read {}: {}", path.display(), e)));
+            Some(contents)
+        } else {
+            None
+        };
+
+        let merged = share::import(existing.as_deref(), &document).unwrap_or_else(|e| fail(&e));
+
+        // Merging doesn't keep the existing file's comments or layout, so keep a copy of it (without
+        // replacing the copy from an earlier import, which may be the only hand-written one)
+        if let Some(existing) = &existing {
+            let backup = backup(&path, existing.as_bytes())
+                .unwrap_or_else(|e| fail(&format!("Couldn't back up {}: {}", path.display(), e)));
+            eprintln!("-> Saved the previous config to {}", backup.display());
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).unwrap_or_else(|e| fail(&e));
+        }
+        // Replaced in one go, so an interrupted import can't leave half a config
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, merged)
+            .and_then(|_| fs::rename(&temporary, &path))
+            .unwrap_or_else(|e| fail(&format!("Couldn't write {}: {}", path.display(), e)));
+        eprintln!("-> Imported {} into {}", source, path.display());
+    }
+}
+
+// Lines are converted in chunks so memory stays bounded on huge inputs while each chunk is
+// still large enough to keep every core busy.
+const BATCH_SIZE: usize = 16 * 1024;
+
+fn run_batch(
+    from_tz: Tz,
+    to_tz: Tz,
+    column: Option<usize>,
+    format: &str,
+    locale: Locale,
+) -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut lines = stdin.lock().lines();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut cache = ConversionCache::default();
+    let mut line_number = 0;
+
+    loop {
+        let chunk = lines
+            .by_ref()
+            .take(BATCH_SIZE)
+            .collect::<io::Result<Vec<String>>>()?;
+
+        if chunk.is_empty() {
+            break;
+        }
+
+        let fields = chunk
+            .iter()
+            .map(|line| csv_field(line, column).unwrap_or(""))
+            .collect::<Vec<_>>();
+
+        for (line, result) in chunk
+            .iter()
+            .zip(cache.convert_batch(&fields, from_tz, to_tz))
+        {
+            line_number += 1;
+
+            match result {
+                Some(result) => writeln!(
+                    out,
+                    "{}",
+                    replace_csv_field(
+                        line,
+                        column,
+                        &format_datetime_localized(&result, format, locale)
+                    )
+                )?,
+                None => {
+                    eprintln!(
+                        "-> Line {}: couldn't parse a date/time, leaving as-is",
+                        line_number
+                    );
+                    writeln!(out, "{}", line)?;
+                }
+            }
+        }
     }
 
-    let result = convert(datetime, to_tz);
+    out.flush()
+}
+
+// The schedule is converted for the coming year. If the difference between the zones changes in
+// that time, each stretch of it gets its own expressions, headed by a comment with its dates.
+fn run_cron(expression: &str, from_tz: Tz, to_tz: Tz) {
+    let today = Utc::now().with_timezone(&from_tz).date().naive_local();
+    let periods = cron::Schedule::parse(expression)
+        .and_then(|schedule| schedule.convert(from_tz, to_tz, today, 366))
+        .unwrap_or_else(|e| {
+            eprintln!("error: Can't convert {:?}: {}", expression, e);
+            process::exit(1);
+        });
 
-    println!("{}", result);
+    print_periods(expression, &periods, from_tz, to_tz, |schedule| {
+        schedule.to_string()
+    });
+}
+
+fn run_crontab(file: Option<&str>, from_tz: Tz, to_tz: Tz) {
+    let crontab = match file {
+        Some(file) => {
+            fs::read_to_string(file).map_err(|e| format!("Couldn't read {}: {}", file, e))
+        }
+        None => io::read_to_string(io::stdin()).map_err(|e| format!("Couldn't read stdin: {}", e)),
+    };
+
+    let today = Utc::now().with_timezone(&from_tz).date().naive_local();
+    let converted = crontab
+        .and_then(|crontab| cron::convert_crontab(&crontab, from_tz, to_tz, today, 366))
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        });
+
+    print!("{}", converted);
+    if converted.contains("\n# tz: ") {
+        eprintln!(
+            "warning: Some schedules change with daylight saving time, so only the current lines \
+             are active"
+        );
+        eprintln!("-> Look for `# tz:` comments to see when to switch to the others");
+    }
+}
+
+// Like `tz cron`, but a zone at the end of the expression takes the place of --from. Timers can
+// also be kept in the original zone by naming it there, so that's suggested when the
+// conversion changes through the year.
+fn run_oncalendar(
+    expression: &str,
+    from_tz: Tz,
+    to_tz: Tz,
+    next: usize,
+    format: &str,
+    locale: Locale,
+) {
+    let calendar = oncalendar::Calendar::parse(expression).unwrap_or_else(|e| {
+        eprintln!("error: Can't convert {:?}: {}", expression, e);
+        process::exit(1);
+    });
+    let from_tz = calendar.zone.unwrap_or(from_tz);
+
+    let today = Utc::now().with_timezone(&from_tz).date().naive_local();
+    let periods = calendar
+        .schedule
+        .convert(from_tz, to_tz, today, 366)
+        .unwrap_or_else(|e| {
+            eprintln!("error: Can't convert {:?}: {}", expression, e);
+            process::exit(1);
+        });
+
+    print_periods(expression, &periods, from_tz, to_tz, |schedule| {
+        format!(
+            "OnCalendar={}",
+            oncalendar::format(schedule, &calendar.seconds)
+        )
+    });
+
+    if periods.len() > 1 {
+        eprintln!(
+            "-> Or, where systemd takes a timezone in the expression, keep the timer in {} with \
+             OnCalendar={} {}",
+            from_tz,
+            oncalendar::format(&calendar.schedule, &calendar.seconds),
+            from_tz
+        );
+    }
+
+    if next > 0 {
+        println!("# Next {} elapses", next);
+        for elapse in calendar.next(from_tz, Utc::now(), next) {
+            println!(
+                "# {} = {}",
+                format_datetime_localized(&elapse, format, locale),
+                format_datetime_localized(&convert(elapse, to_tz), format, locale)
+            );
+        }
+    }
+}
+
+// The converted schedule, and if it changes over the year, each version of it with its dates
+fn print_periods(
+    expression: &str,
+    periods: &[cron::Period],
+    from_tz: Tz,
+    to_tz: Tz,
+    render: impl Fn(&cron::Schedule) -> String,
+) {
+    match periods {
+        [] => {
+            eprintln!("error: {:?} doesn't run in the coming year", expression);
+            process::exit(1);
+        }
+        [period] => period
+            .schedules
+            .iter()
+            .for_each(|s| println!("{}", render(s))),
+        periods => {
+            eprintln!(
+                "warning: The difference between {} and {} changes during the year (daylight \
+                 saving time), so no one schedule fits all of it",
+                from_tz, to_tz
+            );
+            eprintln!("-> Switch between these on the dates given");
+
+            for period in periods {
+                println!("# {} to {}", period.first, period.last);
+                period
+                    .schedules
+                    .iter()
+                    .for_each(|s| println!("{}", render(s)));
+            }
+        }
+    }
+
+    if periods.iter().any(|period| period.schedules.len() > 1) {
+        eprintln!("-> More than one line is needed where a single expression can't list the times");
+    }
+}
+
+// Events are listed by when they start, each with its summary. Only the first occurrence of a
+// repeating event is shown.
+fn run_ics(file: &str, from_tz: Tz, to_tz: Tz, format: &str, locale: Locale) {
+    let mut events = fs::read_to_string(file)
+        .map_err(|e| format!("Couldn't read {}: {}", file, e))
+        .and_then(|contents| ics::parse(&contents, from_tz))
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        });
+
+    events.sort_by_key(|event| match event.start {
+        ics::When::Date(date) => date.and_hms(0, 0, 0),
+        ics::When::Time(time) => time.with_timezone(&to_tz).naive_local(),
+    });
+
+    let when = |when: ics::When| match when {
+        ics::When::Date(date) => format!("{} (all day)", date.format("%Y-%m-%d")),
+        ics::When::Time(time) => {
+            format_datetime_localized(&time.with_timezone(&to_tz), format, locale)
+        }
+    };
+
+    for event in events {
+        let time = match event.end {
+            Some(end @ ics::When::Time(_)) => format!("{} - {}", when(event.start), when(end)),
+            _ => when(event.start),
+        };
+        let repeats = if event.repeats { " (repeats)" } else { "" };
+        let line = format!("{}  {}{}", time, event.summary, repeats);
+        println!("{}", line.trim_end());
+    }
+}
+
+// Each file is only touched if it has timestamps to rewrite, and then its original is kept
+// alongside it first
+fn run_rewrite<'a>(files: impl Iterator<Item = &'a str>, from_tz: Tz, to_tz: Tz) {
+    let rewriter = TimestampRewriter::new(from_tz, to_tz);
+    let mut failed = false;
+
+    for file in files {
+        let rewritten =
+            fs::read_to_string(file).and_then(|contents| match rewriter.rewrite(&contents) {
+                Cow::Borrowed(_) => Ok(None),
+                Cow::Owned(rewritten) if rewritten == contents => Ok(None),
+                Cow::Owned(rewritten) => backup(file, contents.as_bytes())
+                    .and_then(|backup| fs::write(file, rewritten).map(|_| Some(backup))),
+            });
+
+        match rewritten {
+            Ok(Some(backup)) => eprintln!(
+                "-> Rewrote {} (the original is in {})",
+                file,
+                backup.display()
+            ),
+            Ok(None) => eprintln!("-> {} has no timestamps to rewrite", file),
+            Err(e) => {
+                eprintln!("error: Couldn't rewrite {}: {}", file, e);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+// Keep `contents`, the original of `file`, in `<file>.bak`, or in `<file>.bak.1` and so on if
+// that's taken: an existing backup is the original from before an earlier rewrite
+fn backup(file: impl AsRef<Path>, contents: &[u8]) -> io::Result<PathBuf> {
+    let mut n = 0;
+    loop {
+        let mut backup = file.as_ref().as_os_str().to_owned();
+        match n {
+            0 => backup.push(".bak"),
+            n => backup.push(format!(".bak.{}", n)),
+        }
+        let backup = PathBuf::from(backup);
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&backup)
+        {
+            Ok(mut out) => return out.write_all(contents).map(|_| backup),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn run_json<'a>(paths: impl Iterator<Item = &'a str>, from_tz: Tz, to_tz: Tz) {
+    let paths = paths
+        .map(|path| {
+            let segments = json::parse_path(path).unwrap_or_else(|e| {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            });
+            (path, segments)
+        })
+        .collect::<Vec<_>>();
+
+    let mut document = serde_json::from_reader::<_, serde_json::Value>(io::stdin().lock())
+        .unwrap_or_else(|e| {
+            eprintln!("error: Couldn't read JSON from stdin: {}", e);
+            process::exit(1);
+        });
+
+    let rewriter = TimestampRewriter::new(from_tz, to_tz);
+    for (path, segments) in &paths {
+        let mut converted = json::Converted::default();
+        json::convert(&mut document, segments, &rewriter, &mut converted);
+        if converted.rewritten == 0 {
+            eprintln!("warning: No timestamps at {} to convert", path);
+        } else if converted.skipped > 0 {
+            eprintln!(
+                "warning: {} value(s) at {} had no timestamp to convert",
+                converted.skipped, path
+            );
+        }
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&document).expect("Failed to write JSON")
+    );
+}
+
+// As with `tz rewrite`, each photo's original is kept alongside it before it's changed
+fn run_exif<'a>(files: impl Iterator<Item = &'a str>, from_tz: Tz, to_tz: Tz, dry_run: bool) {
+    let mut failed = false;
+
+    for file in files {
+        let shifted = fs::read(file)
+            .map_err(|e| e.to_string())
+            .and_then(|original| {
+                let mut photo = original.clone();
+                let shifted = exif::shift(&mut photo, from_tz, to_tz)?;
+                if dry_run {
+                    return Ok((shifted, None));
+                }
+                let backup = backup(file, &original)
+                    .and_then(|backup| fs::write(file, photo).map(|_| backup))
+                    .map_err(|e| e.to_string())?;
+                Ok((shifted, Some(backup)))
+            });
+
+        match shifted {
+            Ok((shifted, backup)) => {
+                println!(
+                    "{}: {} -> {}",
+                    file,
+                    shifted.original,
+                    shifted.shifted.format("%Y-%m-%d %H:%M:%S %:z")
+                );
+                if !shifted.offset_updated {
+                    eprintln!(
+                        "warning: {} has no OffsetTimeOriginal, so only the local time was changed",
+                        file
+                    );
+                }
+                if let Some(backup) = backup {
+                    eprintln!("-> The original is in {}", backup.display());
+                }
+            }
+            Err(e) => {
+                eprintln!("error: Couldn't rewrite {}: {}", file, e);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+// Unlike `batch`, this handles a single line at a time and flushes after each one, so memory
+// stays constant and output keeps up with input in `tail -f | tz filter | less` pipelines.
+fn run_filter(from_tz: Tz, to_tz: Tz) -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut input = stdin.lock();
+    let mut out = stdout.lock();
+    let rewriter = TimestampRewriter::new(from_tz, to_tz);
+    let mut line = Vec::new();
+
+    while input.read_until(b'\n', &mut line)? > 0 {
+        let written = match std::str::from_utf8(&line) {
+            Ok(text) => out.write_all(rewriter.rewrite(text).as_bytes()),
+            Err(_) => out.write_all(&line),
+        }
+        .and_then(|_| out.flush());
+
+        match written {
+            // The reader went away (e.g. `less` was closed), so there's nobody left to write to
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            written => written?,
+        }
+
+        line.clear();
+    }
+
+    Ok(())
+}
+
+// CSV handling is deliberately naive: fields are split on commas and quoting isn't supported.
+fn csv_field(line: &str, column: Option<usize>) -> Option<&str> {
+    match column {
+        Some(column) => line.split(',').nth(column.checked_sub(1)?).map(str::trim),
+        None => Some(line.trim()),
+    }
+}
+
+fn replace_csv_field(line: &str, column: Option<usize>, value: &str) -> String {
+    match column {
+        Some(column) => line
+            .split(',')
+            .enumerate()
+            .map(|(i, field)| if i + 1 == column { value } else { field })
+            .collect::<Vec<_>>()
+            .join(","),
+        None => value.to_owned(),
+    }
 }
@@ -0,0 +1,119 @@
+// `tz metrics`: each zone's offset, whether it's on daylight saving time, and how long until its
+// offset next changes, as Prometheus gauges. Written to a file in node_exporter's textfile
+// collector directory (from cron or a timer), that lets dashboards warn ahead of changeovers.
+
+use chrono::{DateTime, Duration, Offset, TimeZone, Utc};
+use chrono_tz::{OffsetComponents, Tz};
+
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// How far ahead to look for the next transition; zones without one in that time don't get a
+// `tz_next_transition_seconds` sample
+const HORIZON_DAYS: i64 = 2 * 366;
+
+/// The metrics for `zones` at `now`, in Prometheus's text format
+pub fn render(zones: &[Tz], now: DateTime<Utc>) -> String {
+    let mut text = String::new();
+    let mut gauge = |name: &str, help: &str, value: &dyn Fn(Tz) -> Option<i64>| {
+        let _ = writeln!(text, "# HELP {} {}", name, help);
+        let _ = writeln!(text, "# TYPE {} gauge", name);
+        for &tz in zones {
+            if let Some(value) = value(tz) {
+                let _ = writeln!(text, "{}{{zone=\"{}\"}} {}", name, tz.name(), value);
+            }
+        }
+    };
+
+    let offset = |tz: Tz| now.with_timezone(&tz).offset().fix().local_minus_utc();
+    gauge("tz_utc_offset_seconds", "Current offset from UTC.", &|tz| {
+        Some(i64::from(offset(tz)))
+    });
+    gauge(
+        "tz_dst",
+        "Whether daylight saving time is in effect (1) or not (0).",
+        &|tz| {
+            let dst = now.with_timezone(&tz).offset().dst_offset();
+            Some(i64::from(dst != Duration::zero()))
+        },
+    );
+    gauge(
+        "tz_next_transition_seconds",
+        "Seconds until the offset from UTC next changes.",
+        &|tz| next_transition(tz, now).map(|next| (next - now).num_seconds()),
+    );
+
+    text
+}
+
+/// Write the metrics to `path` in one go, so the collector never reads a half-written file
+pub fn write(path: &Path, metrics: &str) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, metrics)?;
+    fs::rename(&temporary, path)
+}
+
+// The first instant after `now` with a different offset, found a day at a time and then narrowed
+// down to the second
+fn next_transition(tz: Tz, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let offset = |instant: DateTime<Utc>| instant.with_timezone(&tz).offset().fix();
+    let current = offset(now);
+
+    let after = (1..=HORIZON_DAYS)
+        .map(|day| now + Duration::days(day))
+        .find(|&day| offset(day) != current)?;
+
+    let (mut before, mut after) = ((after - Duration::days(1)).timestamp(), after.timestamp());
+    while after - before > 1 {
+        let middle = before + (after - before) / 2;
+        if offset(Utc.timestamp(middle, 0)) == current {
+            before = middle;
+        } else {
+            after = middle;
+        }
+    }
+    Some(Utc.timestamp(after, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let now = Utc.ymd(2021, 3, 1).and_hms(0, 0, 0);
+        assert_eq!(
+            render(&[Tz::America__Los_Angeles, Tz::Asia__Kolkata], now),
+            "\
+# HELP tz_utc_offset_seconds Current offset from UTC.
+# TYPE tz_utc_offset_seconds gauge
+tz_utc_offset_seconds{zone=\"America/Los_Angeles\"} -28800
+tz_utc_offset_seconds{zone=\"Asia/Kolkata\"} 19800
+# HELP tz_dst Whether daylight saving time is in effect (1) or not (0).
+# TYPE tz_dst gauge
+tz_dst{zone=\"America/Los_Angeles\"} 0
+tz_dst{zone=\"Asia/Kolkata\"} 0
+# HELP tz_next_transition_seconds Seconds until the offset from UTC next changes.
+# TYPE tz_next_transition_seconds gauge
+tz_next_transition_seconds{zone=\"America/Los_Angeles\"} 1159200
+"
+        );
+    }
+
+    #[test]
+    fn test_next_transition() {
+        let now = Utc.ymd(2021, 3, 1).and_hms(0, 0, 0);
+        assert_eq!(
+            next_transition(Tz::Europe__London, now),
+            Some(Utc.ymd(2021, 3, 28).and_hms(1, 0, 0))
+        );
+        assert_eq!(
+            next_transition(Tz::Australia__Sydney, now),
+            Some(Utc.ymd(2021, 4, 3).and_hms(16, 0, 0))
+        );
+        assert_eq!(next_transition(Tz::Asia__Tokyo, now), None);
+    }
+}
@@ -0,0 +1,387 @@
+// `tz oncalendar`: convert the `OnCalendar=` expression of a systemd timer (see systemd.time(7))
+// between zones. Timers run in the machine's zone, while the times they should run at are often
+// given in another. An expression is a schedule like cron's, down to the second, so it's
+// converted as one (see `cron`).
+
+use chrono::{DateTime, Duration, LocalResult, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use std::collections::BTreeSet;
+
+use crate::cron::Schedule;
+
+const WEEKDAYS: &[&str] = &[
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// How far ahead to look for elapses, so a schedule that never runs (like February 30th) ends
+const SEARCH_DAYS: i64 = 366 * 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Calendar {
+    pub schedule: Schedule,
+    pub seconds: BTreeSet<u32>,
+    /// The zone the expression names, if it ends with one
+    pub zone: Option<Tz>,
+}
+
+impl Calendar {
+    /// Parse an expression, e.g. `Mon..Fri 09:00`, `*-*-01 00:00:00 Europe/Berlin`, or `daily`
+    pub fn parse(expression: &str) -> Result<Calendar, String> {
+        let mut tokens = expression.split_whitespace().collect::<Vec<_>>();
+        let zone = match tokens.last().map(|token| token.parse::<Tz>()) {
+            Some(Ok(zone)) => {
+                tokens.pop();
+                Some(zone)
+            }
+            _ => None,
+        };
+
+        let shorthand = match tokens[..] {
+            ["minutely"] => "*-*-* *:*:00",
+            ["hourly"] => "*-*-* *:00:00",
+            ["daily"] => "*-*-* 00:00:00",
+            ["weekly"] => "Mon *-*-* 00:00:00",
+            ["monthly"] => "*-*-01 00:00:00",
+            ["yearly"] | ["annually"] => "*-01-01 00:00:00",
+            ["quarterly"] => "*-01,04,07,10-01 00:00:00",
+            ["semiannually"] => "*-01,07-01 00:00:00",
+            _ => "",
+        };
+        if !shorthand.is_empty() {
+            tokens = shorthand.split_whitespace().collect();
+        }
+
+        let mut weekdays = None;
+        let mut date = None;
+        let mut time = None;
+        for (i, token) in tokens.iter().enumerate() {
+            let slot = if i == 0 && token.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                &mut weekdays
+            } else if token.contains(':') {
+                &mut time
+            } else {
+                &mut date
+            };
+
+            if slot.replace(*token).is_some() {
+                return Err(format!("unexpected {:?}", token));
+            }
+        }
+
+        let (months, days) = parse_date(date.unwrap_or("*-*-*"))?;
+        let (hours, minutes, seconds) = parse_time(time.unwrap_or("00:00:00"))?;
+        let weekdays = match weekdays {
+            Some(weekdays) => parse_weekdays(weekdays)?,
+            None => (0..7).collect(),
+        };
+
+        // Unlike cron, the date and the weekdays always both have to match
+        let schedule = Schedule {
+            minutes,
+            hours,
+            days,
+            months,
+            weekdays,
+            any_day: true,
+            any_weekday: true,
+        };
+
+        Ok(Calendar {
+            schedule,
+            seconds,
+            zone,
+        })
+    }
+
+    /// The next `count` times the timer elapses after `after`, for a timer running in `zone`
+    pub fn next(&self, zone: Tz, after: DateTime<Utc>, count: usize) -> Vec<DateTime<Tz>> {
+        let start = after.with_timezone(&zone).date().naive_local();
+        let mut elapses = Vec::new();
+
+        for date in (0..SEARCH_DAYS).map(|day| start + Duration::days(day)) {
+            if !self.schedule.runs_on(date) {
+                continue;
+            }
+
+            for &hour in &self.schedule.hours {
+                for &minute in &self.schedule.minutes {
+                    for &second in &self.seconds {
+                        let local = date.and_hms(hour, minute, second);
+                        let elapse = match zone.from_local_datetime(&local) {
+                            LocalResult::None => continue,
+                            elapse => elapse.earliest().expect("the time exists"),
+                        };
+
+                        if elapse > after {
+                            elapses.push(elapse);
+                            if elapses.len() == count {
+                                return elapses;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        elapses
+    }
+}
+
+/// An expression in the normalized form systemd itself writes them in
+pub fn format(schedule: &Schedule, seconds: &BTreeSet<u32>) -> String {
+    let date = format!(
+        "*-{}-{} {}:{}:{}",
+        list(&schedule.months, 1, 12),
+        list(&schedule.days, 1, 31),
+        list(&schedule.hours, 0, 23),
+        list(&schedule.minutes, 0, 59),
+        list(seconds, 0, 59)
+    );
+
+    if schedule.weekdays.len() == 7 {
+        return date;
+    }
+
+    // Monday first, as systemd has it
+    let mut weekdays = schedule
+        .weekdays
+        .iter()
+        .map(|&weekday| (weekday + 6) % 7)
+        .collect::<Vec<_>>();
+    weekdays.sort_unstable();
+
+    let name = |weekday: u32| &WEEKDAYS[((weekday + 1) % 7) as usize][..3];
+    let weekdays = runs(&weekdays)
+        .into_iter()
+        .map(|(first, last)| match last - first {
+            0 => name(first).to_owned(),
+            1 => format!("{},{}", name(first), name(last)),
+            _ => format!("{}..{}", name(first), name(last)),
+        })
+        .collect::<Vec<_>>();
+
+    format!("{} {}", weekdays.join(","), date)
+}
+
+// `[year-]month-day`, where the year can only be `*`
+fn parse_date(date: &str) -> Result<(BTreeSet<u32>, BTreeSet<u32>), String> {
+    if date.contains('~') {
+        return Err("days counted from the end of the month (~) aren't supported".to_owned());
+    }
+
+    match date.split('-').collect::<Vec<_>>()[..] {
+        ["*", month, day] | [month, day] => Ok((values(month, 1, 12)?, values(day, 1, 31)?)),
+        [_, _, _] => Err("only `*` is supported for the year".to_owned()),
+        _ => Err(format!("invalid date {:?}", date)),
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn parse_time(time: &str) -> Result<(BTreeSet<u32>, BTreeSet<u32>, BTreeSet<u32>), String> {
+    let (hours, minutes, seconds) = match time.split(':').collect::<Vec<_>>()[..] {
+        [hours, minutes] => (hours, minutes, "00"),
+        [hours, minutes, seconds] => (hours, minutes, seconds),
+        _ => return Err(format!("invalid time {:?}", time)),
+    };
+
+    if seconds.replace("..", "").contains('.') {
+        return Err("fractions of a second aren't supported".to_owned());
+    }
+
+    Ok((
+        values(hours, 0, 23)?,
+        values(minutes, 0, 59)?,
+        values(seconds, 0, 59)?,
+    ))
+}
+
+// A component: `*`, a number, a range (`9..17`), any of those repeating (`*/15`, `0/15`,
+// `0..30/10`), or a comma-separated list of them
+fn values(text: &str, min: u32, max: u32) -> Result<BTreeSet<u32>, String> {
+    let invalid = || format!("invalid value {:?}", text);
+    let value = |value: &str| {
+        value
+            .parse::<u32>()
+            .ok()
+            .filter(|value| (min..=max).contains(value))
+            .ok_or_else(invalid)
+    };
+
+    let mut values = BTreeSet::new();
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse::<usize>().map_err(|_| invalid())?)),
+            None => (part, None),
+        };
+
+        let (first, last) = match range.split_once("..") {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (value(first)?, value(last)?),
+            None if step.is_some() => (value(range)?, max),
+            None => (value(range)?, value(range)?),
+        };
+
+        if step == Some(0) || first > last {
+            return Err(invalid());
+        }
+        values.extend((first..=last).step_by(step.unwrap_or(1)));
+    }
+
+    Ok(values)
+}
+
+// `Mon`, `Monday`, `Mon..Fri`, or a comma-separated list of them (0 is Sunday)
+fn parse_weekdays(text: &str) -> Result<BTreeSet<u32>, String> {
+    let weekday = |name: &str| {
+        WEEKDAYS
+            .iter()
+            .position(|weekday| {
+                weekday.eq_ignore_ascii_case(name) || weekday[..3].eq_ignore_ascii_case(name)
+            })
+            .map(|weekday| weekday as u32)
+            .ok_or_else(|| format!("invalid weekday {:?}", name))
+    };
+
+    let mut weekdays = BTreeSet::new();
+    for part in text.split(',') {
+        match part.split_once("..") {
+            Some((first, last)) => {
+                // Ranges run Monday to Sunday
+                let (first, last) = ((weekday(first)? + 6) % 7, (weekday(last)? + 6) % 7);
+                if first > last {
+                    return Err(format!("invalid weekdays {:?}", part));
+                }
+                weekdays.extend((first..=last).map(|weekday| (weekday + 1) % 7));
+            }
+            None => {
+                weekdays.insert(weekday(part)?);
+            }
+        }
+    }
+
+    Ok(weekdays)
+}
+
+// A component written as systemd normalizes it
+fn list(values: &BTreeSet<u32>, min: u32, max: u32) -> String {
+    let values = values.iter().copied().collect::<Vec<_>>();
+
+    if values.len() as u32 == max - min + 1 {
+        return "*".to_owned();
+    }
+
+    if let [first, second, ..] = values[..] {
+        let step = second - first;
+        let stepped = (first..=max).step_by(step as usize).collect::<Vec<_>>();
+        if step > 1 && values.len() > 2 && stepped == values {
+            return format!("{:02}/{}", first, step);
+        }
+    }
+
+    runs(&values)
+        .into_iter()
+        .map(|(first, last)| match last - first {
+            0 => format!("{:02}", first),
+            1 => format!("{:02},{:02}", first, last),
+            _ => format!("{:02}..{:02}", first, last),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// Sorted values as runs of consecutive ones, each given by its first and last value
+fn runs(values: &[u32]) -> Vec<(u32, u32)> {
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for &value in values {
+        match runs.last_mut() {
+            Some((_, last)) if *last + 1 == value => *last = value,
+            _ => runs.push((value, value)),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn normalize(expression: &str) -> String {
+        let calendar = Calendar::parse(expression).unwrap();
+        format(&calendar.schedule, &calendar.seconds)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(normalize("Mon..Fri 9:00"), "Mon..Fri *-*-* 09:00:00");
+        assert_eq!(normalize("daily"), "*-*-* 00:00:00");
+        assert_eq!(
+            normalize("Sat,Sun *-*-1,15 *:0/15"),
+            "Sat,Sun *-*-01,15 *:00/15:00"
+        );
+        assert_eq!(normalize("monday,wed 12:30:05"), "Mon,Wed *-*-* 12:30:05");
+        assert_eq!(normalize("*-1..3-1 8..10:00"), "*-01..03-01 08..10:00:00");
+        assert_eq!(normalize("Sun 10:00"), "Sun *-*-* 10:00:00");
+
+        let calendar = Calendar::parse("quarterly Asia/Tokyo").unwrap();
+        assert_eq!(calendar.zone, Some(Tz::Asia__Tokyo));
+        assert_eq!(
+            format(&calendar.schedule, &calendar.seconds),
+            "*-01/3-01 00:00:00"
+        );
+
+        for invalid in &[
+            "2022-*-01",
+            "*-*~01",
+            "12:00:00.5",
+            "Fri..Mon",
+            "Funday",
+            "12:00 13:00",
+            "*-13-01",
+        ] {
+            assert!(Calendar::parse(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_next() {
+        let calendar = Calendar::parse("Mon..Fri 09:00").unwrap();
+        let after = Utc.ymd(2021, 7, 9).and_hms(12, 0, 0);
+        let elapses = calendar
+            .next(Tz::America__New_York, after, 2)
+            .iter()
+            .map(|elapse| elapse.to_rfc3339())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            elapses,
+            vec!["2021-07-09T09:00:00-04:00", "2021-07-12T09:00:00-04:00"]
+        );
+
+        let never = Calendar::parse("*-02-30").unwrap();
+        assert!(never.next(Tz::UTC, after, 1).is_empty());
+    }
+
+    #[test]
+    fn test_convert() {
+        let calendar = Calendar::parse("Mon..Fri 22:00").unwrap();
+        let start = NaiveDate::from_ymd(2021, 7, 1);
+        let periods = calendar
+            .schedule
+            .convert(Tz::America__New_York, Tz::UTC, start, 30)
+            .unwrap();
+        let converted = periods[0]
+            .schedules
+            .iter()
+            .map(|schedule| format(schedule, &calendar.seconds))
+            .collect::<Vec<_>>();
+        assert_eq!(converted, vec!["Tue..Sat *-*-* 02:00:00"]);
+    }
+}
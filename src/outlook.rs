@@ -0,0 +1,49 @@
+// `tz <tz_id> <datetime> --outlook`: the same as --gcal, for Outlook on the web (and so Teams
+// meetings, which are created from Outlook's new event form).
+
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+
+use crate::gcal::{encode, DEFAULT_LENGTH_MINUTES};
+
+const COMPOSE_URL: &str =
+    "https://outlook.office.com/calendar/0/deeplink/compose?path=%2Fcalendar%2Faction%2Fcompose&rru=addevent";
+
+/// A link to create an event starting at `start` and lasting `duration` (or an hour)
+pub fn link(start: DateTime<Tz>, duration: Option<Duration>, summary: Option<&str>) -> String {
+    let end = start + duration.unwrap_or_else(|| Duration::minutes(DEFAULT_LENGTH_MINUTES));
+    let stamp = |datetime: DateTime<Tz>| {
+        let stamp = datetime.with_timezone(&Utc).format("%Y-%m-%dT%H:%M:%SZ");
+        encode(&stamp.to_string())
+    };
+
+    let mut link = format!(
+        "{}&startdt={}&enddt={}",
+        COMPOSE_URL,
+        stamp(start),
+        stamp(end)
+    );
+    if let Some(summary) = summary {
+        link.push_str(&format!("&subject={}", encode(summary)));
+    }
+    link
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_link() {
+        let start = Tz::Europe__Berlin.ymd(2021, 7, 9).and_hms(17, 0, 0);
+
+        assert_eq!(
+            link(start, Some(Duration::minutes(30)), Some("Retro")),
+            "https://outlook.office.com/calendar/0/deeplink/compose?path=%2Fcalendar%2Faction%2Fcompose\
+             &rru=addevent&startdt=2021-07-09T15%3A00%3A00Z&enddt=2021-07-09T15%3A30%3A00Z&subject=Retro"
+        );
+        assert!(link(start, None, None).ends_with("&enddt=2021-07-09T16%3A00%3A00Z"));
+    }
+}
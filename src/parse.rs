@@ -0,0 +1,649 @@
+use chrono::offset::{LocalResult, TimeZone};
+use chrono::{
+    DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc, Weekday,
+};
+
+use chrono_tz::Tz;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+lazy_static! {
+    // Compiled once per process rather than once per parse, which matters for batch/filter modes.
+    // These only anchor at the start so parsing can report exactly where it stopped.
+    static ref DATE: Regex = Regex::new(r"^(\d{4})-(\d{2})-(\d{2})").unwrap();
+    static ref TIME: Regex = Regex::new(r"^(\d{1,2}):(\d{2})").unwrap();
+    static ref SHORT_TIME: Regex = Regex::new(r"(?i)^(\d+):?(\d+)?\s?(am|pm)").unwrap();
+    static ref EPOCH: Regex = Regex::new(r"^(@)?(-?)(\d+)(?:\.(\d*))?$").unwrap();
+    static ref BUSINESS_DAYS: Regex =
+        Regex::new(r"(?i)^(?:next business day|\+(\d+) business days?)(?: |$)").unwrap();
+}
+
+// More business days than this are almost certainly a typo, and would take a while to count
+const MAX_BUSINESS_DAYS: u32 = 10_000;
+
+/// Which days count as business days, for inputs like `next business day 9am`: the five days of
+/// the work week that aren't holidays
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusinessDays {
+    /// The first of the five days in the work week
+    pub week_start: Weekday,
+    pub holidays: Vec<NaiveDate>,
+}
+
+impl Default for BusinessDays {
+    fn default() -> BusinessDays {
+        BusinessDays {
+            week_start: Weekday::Mon,
+            holidays: Vec::new(),
+        }
+    }
+}
+
+impl BusinessDays {
+    pub fn is_business_day(&self, date: NaiveDate) -> bool {
+        let day = date.weekday().num_days_from_monday();
+        let start = self.week_start.num_days_from_monday();
+        (7 + day - start) % 7 < 5 && !self.holidays.contains(&date)
+    }
+
+    /// The business day `days` business days after `date`
+    pub fn add(&self, date: NaiveDate, days: u32) -> NaiveDate {
+        let mut date = date;
+        let mut left = days;
+        while left > 0 {
+            date = date.succ();
+            if self.is_business_day(date) {
+                left -= 1;
+            }
+        }
+        date
+    }
+}
+
+/// Explains why a date/time string couldn't be parsed, pointing at the part of the input that
+/// parsing stopped at. Displays as the reason followed by the input with that part underlined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ParseError {
+    /// The complete input that was being parsed
+    pub input: String,
+    /// Byte range of `input` that couldn't be understood
+    pub span: Range<usize>,
+    /// What was wrong with that part of the input
+    pub reason: String,
+}
+
+impl ParseError {
+    fn new<S: Into<String>>(input: &str, span: Range<usize>, reason: S) -> ParseError {
+        ParseError {
+            input: input.to_owned(),
+            span,
+            reason: reason.into(),
+        }
+    }
+
+    // Points at the next whitespace-delimited token at or after `start`
+    fn unexpected(input: &str, start: usize) -> ParseError {
+        let rest = input[start..].trim_start();
+        let token_start = input.len() - rest.len();
+
+        if rest.is_empty() {
+            return ParseError::new(input, start..input.len(), "unexpected trailing whitespace");
+        }
+
+        let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let reason = format!("unexpected {:?}", &rest[..len]);
+        ParseError::new(input, token_start..token_start + len, reason)
+    }
+
+    /// The leading part of the input that was understood before parsing failed
+    pub fn understood(&self) -> &str {
+        &self.input[..self.span.start]
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let padding = self.input[..self.span.start].chars().count();
+        let width = self.input[self.span.clone()].chars().count().max(1);
+
+        writeln!(f, "{}", self.reason)?;
+        writeln!(f, "  {}", self.input)?;
+        write!(f, "  {}{}", " ".repeat(padding), "^".repeat(width))
+    }
+}
+
+impl Error for ParseError {}
+
+// Given a `Tz`, convert the given date/time string to a DateTime in that timezone
+pub fn parse_datetime_in_tz(tz: Tz, datetime: &str) -> Option<DateTime<Tz>> {
+    try_parse_datetime_in_tz(tz, datetime).ok()
+}
+
+/// Like `parse_datetime_in_tz`, but explains where and why parsing failed
+pub fn try_parse_datetime_in_tz(tz: Tz, input: &str) -> Result<DateTime<Tz>, ParseError> {
+    try_parse_datetime_with(tz, input, &BusinessDays::default())
+}
+
+/// Like `try_parse_datetime_in_tz`, counting `next business day` and `+N business days` from
+/// today in `tz` with the given business days (rather than Monday to Friday)
+pub fn try_parse_datetime_with(
+    tz: Tz,
+    input: &str,
+    business_days: &BusinessDays,
+) -> Result<DateTime<Tz>, ParseError> {
+    if let Some(relative) = BUSINESS_DAYS.captures(input) {
+        let now = Utc::now().with_timezone(&tz);
+        return parse_business_days(tz, input, &relative, business_days, now.naive_local());
+    }
+
+    // Shorter numbers without an @ are more likely to be a time missing its colon than an epoch
+    let epoch = EPOCH
+        .captures(input)
+        .filter(|epoch| epoch.get(1).is_some() || epoch[3].len() >= 9);
+    if let Some(epoch) = epoch {
+        return parse_epoch(input, &epoch).map(|instant| instant.with_timezone(&tz));
+    }
+
+    let (date, time_start) = match DATE.captures(input) {
+        Some(date) => {
+            let end = date.get(0).unwrap().end();
+            let date = parse_date(input, &date)?;
+
+            if end == input.len() {
+                return resolve_local(tz, input, date.and_hms(0, 0, 0));
+            } else if !input[end..].starts_with(' ') {
+                return Err(ParseError::unexpected(input, end));
+            }
+
+            (Some(date), end + 1)
+        }
+        None => (None, 0),
+    };
+
+    let rest = &input[time_start..];
+
+    let (time, time_len) = match (date, SHORT_TIME.captures(rest), TIME.captures(rest)) {
+        (None, Some(short_time), _) => (
+            parse_short_time(input, time_start, &short_time)?,
+            short_time.get(0).unwrap().end(),
+        ),
+        (_, _, Some(time)) => (
+            parse_time(input, time_start, &time)?,
+            time.get(0).unwrap().end(),
+        ),
+        (Some(_), _, _) => {
+            let mut error = ParseError::unexpected(input, time_start);
+            error.reason = "expected a time (HH:MM) after the date".to_owned();
+            return Err(error);
+        }
+        (None, _, _) => {
+            let mut error = ParseError::unexpected(input, 0);
+            error.reason = "not a date or time (allowed formats are YYYY-MM-DD, HH:MM, \
+                            YYYY-MM-DD HH:MM, HHam/pm, Unix epochs, and business days like \
+                            next business day 9am)"
+                .to_owned();
+            return Err(error);
+        }
+    };
+
+    if time_start + time_len != input.len() {
+        return Err(ParseError::unexpected(input, time_start + time_len));
+    }
+
+    let date = date.unwrap_or_else(|| Local::now().date().naive_local());
+    resolve_local(tz, input, date.and_time(time))
+}
+
+// Byte range of capture group `i`, relative to the whole input rather than the slice matched
+fn span(captures: &Captures, i: usize, offset: usize) -> Range<usize> {
+    let group = captures.get(i).unwrap();
+    offset + group.start()..offset + group.end()
+}
+
+fn number(captures: &Captures, i: usize) -> u32 {
+    // Digits too long for a u32 are just as out-of-range as any other bad value
+    captures[i].parse().unwrap_or(u32::MAX)
+}
+
+fn parse_date(input: &str, date: &Captures) -> Result<NaiveDate, ParseError> {
+    let (year, month, day) = (number(date, 1) as i32, number(date, 2), number(date, 3));
+
+    if !(1..=12).contains(&month) {
+        return Err(ParseError::new(
+            input,
+            span(date, 2, 0),
+            "month must be between 01 and 12",
+        ));
+    }
+
+    NaiveDate::from_ymd_opt(year, month, day).ok_or_else(|| {
+        let month_name = NaiveDate::from_ymd(year, month, 1).format("%B %Y");
+        ParseError::new(
+            input,
+            span(date, 3, 0),
+            format!("{} doesn't have a day {:02}", month_name, day),
+        )
+    })
+}
+
+// A Unix epoch, which is in seconds, milliseconds, microseconds, or nanoseconds going by how many
+// digits it has: 10, 13, 16, or 19, which covers 2001-09-09 to 2286-11-20 at each precision.
+// Other lengths are ambiguous (or not epochs at all, like 0930), so epochs outside that range
+// are given in seconds with a leading `@`, as with GNU date (which also allows negative ones; those
+// need the `@` too, since a bare `-1625850000` is more likely a mistake than a time in 1918).
+// Only seconds can have a fraction, and it's kept down to the nanosecond.
+fn parse_epoch(input: &str, epoch: &Captures) -> Result<DateTime<Utc>, ParseError> {
+    let explicit = epoch.get(1).is_some();
+    let negative = !epoch[2].is_empty();
+    let digits = &epoch[3];
+    let fraction = epoch.get(4).map(|fraction| fraction.as_str());
+
+    if negative && !explicit {
+        return Err(ParseError::new(
+            input,
+            span(epoch, 2, 0),
+            format!("negative epochs need a leading @, like @-{}", digits),
+        ));
+    }
+
+    let digits_per_second = match digits.len() {
+        _ if explicit => 0,
+        10 => 0,
+        13 => 3,
+        16 => 6,
+        19 => 9,
+        len => {
+            return Err(ParseError::new(
+                input,
+                span(epoch, 3, 0),
+                format!(
+                    "{} digits is ambiguous: epochs have 10 digits in seconds, 13 in \
+                     milliseconds, 16 in microseconds, or 19 in nanoseconds (write seconds with a \
+                     leading @, like @{}, for other lengths)",
+                    len, digits
+                ),
+            ))
+        }
+    };
+
+    if fraction.is_some() && digits_per_second > 0 {
+        return Err(ParseError::new(
+            input,
+            span(epoch, 4, 0),
+            "only epochs in seconds can have a fractional part",
+        ));
+    }
+    let fraction = fraction.unwrap_or("");
+    if fraction.len() > 9 {
+        return Err(ParseError::new(
+            input,
+            span(epoch, 4, 0),
+            "the fraction can only go down to nanoseconds (9 digits)",
+        ));
+    }
+
+    let out_of_range = || ParseError::new(input, 0..input.len(), "the epoch is out of range");
+
+    // Whole seconds, and the digits after them as nanoseconds
+    let split = digits.len() - digits_per_second;
+    let seconds = digits[..split].parse::<i64>().map_err(|_| out_of_range())?;
+    let nanos = format!("{:0<9}", format!("{}{}", &digits[split..], fraction))
+        .parse::<i64>()
+        .expect("at most 9 digits");
+
+    let (seconds, nanos) = if negative && nanos > 0 {
+        (
+            seconds.checked_neg().and_then(|s| s.checked_sub(1)),
+            1_000_000_000 - nanos,
+        )
+    } else if negative {
+        (seconds.checked_neg(), 0)
+    } else {
+        (Some(seconds), nanos)
+    };
+
+    seconds
+        .and_then(|seconds| Utc.timestamp_opt(seconds, nanos as u32).single())
+        .ok_or_else(out_of_range)
+}
+
+/// Whether `input` counts business days from today, like `+3 business days 9am`, which (unlike
+/// a `+FORMAT` for GNU date) also starts with a `+`
+pub fn is_business_days(input: &str) -> bool {
+    BUSINESS_DAYS.is_match(input)
+}
+
+// `next business day [time]` or `+N business days [time]`, from `now` (local to `tz`). Without a
+// time, the time of day now is kept.
+fn parse_business_days(
+    tz: Tz,
+    input: &str,
+    relative: &Captures,
+    business_days: &BusinessDays,
+    now: NaiveDateTime,
+) -> Result<DateTime<Tz>, ParseError> {
+    let days = match relative.get(1) {
+        Some(_) if number(relative, 1) > MAX_BUSINESS_DAYS => {
+            return Err(ParseError::new(
+                input,
+                span(relative, 1, 0),
+                format!("at most {} business days can be added", MAX_BUSINESS_DAYS),
+            ))
+        }
+        Some(_) => number(relative, 1),
+        None => 1,
+    };
+    let date = business_days.add(now.date(), days);
+
+    let start = relative.get(0).unwrap().end();
+    let rest = &input[start..];
+    let (time, time_len) = if rest.is_empty() {
+        (
+            NaiveTime::from_hms(now.hour(), now.minute(), now.second()),
+            0,
+        )
+    } else if let Some(short_time) = SHORT_TIME.captures(rest) {
+        (
+            parse_short_time(input, start, &short_time)?,
+            short_time.get(0).unwrap().end(),
+        )
+    } else if let Some(time) = TIME.captures(rest) {
+        (parse_time(input, start, &time)?, time.get(0).unwrap().end())
+    } else {
+        let mut error = ParseError::unexpected(input, start);
+        error.reason = "expected a time (HH:MM or HHam/pm) after the business days".to_owned();
+        return Err(error);
+    };
+
+    if start + time_len != input.len() {
+        return Err(ParseError::unexpected(input, start + time_len));
+    }
+
+    resolve_local(tz, input, date.and_time(time))
+}
+
+fn parse_time(input: &str, offset: usize, time: &Captures) -> Result<NaiveTime, ParseError> {
+    let (hour, minute) = (number(time, 1), number(time, 2));
+
+    if hour > 23 {
+        Err(ParseError::new(
+            input,
+            span(time, 1, offset),
+            "hour must be between 0 and 23",
+        ))
+    } else if minute > 59 {
+        Err(ParseError::new(
+            input,
+            span(time, 2, offset),
+            "minute must be between 00 and 59",
+        ))
+    } else {
+        Ok(NaiveTime::from_hms(hour, minute, 0))
+    }
+}
+
+fn parse_short_time(
+    input: &str,
+    offset: usize,
+    short_time: &Captures,
+) -> Result<NaiveTime, ParseError> {
+    let hour = number(short_time, 1);
+    let minute = short_time.get(2).map_or(0, |_| number(short_time, 2));
+    let pm = short_time[3].eq_ignore_ascii_case("pm");
+
+    if !(1..=12).contains(&hour) {
+        Err(ParseError::new(
+            input,
+            span(short_time, 1, offset),
+            "hour must be between 1 and 12 when using am/pm",
+        ))
+    } else if minute > 59 {
+        Err(ParseError::new(
+            input,
+            span(short_time, 2, offset),
+            "minute must be between 00 and 59",
+        ))
+    } else {
+        // 12am is midnight and 12pm is noon
+        let hour = hour % 12 + if pm { 12 } else { 0 };
+        Ok(NaiveTime::from_hms(hour, minute, 0))
+    }
+}
+
+fn resolve_local(tz: Tz, input: &str, local: NaiveDateTime) -> Result<DateTime<Tz>, ParseError> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(dt) => Ok(dt),
+        LocalResult::None => Err(ParseError::new(
+            input,
+            0..input.len(),
+            format!(
+                "{} doesn't exist in {} (it's skipped by a daylight saving transition)",
+                local, tz
+            ),
+        )),
+        LocalResult::Ambiguous(..) => Err(ParseError::new(
+            input,
+            0..input.len(),
+            format!(
+                "{} is ambiguous in {} (it happens twice because of a daylight saving transition)",
+                local, tz
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Local;
+    use chrono_tz::{Asia::Kolkata, Europe::London};
+
+    use super::*;
+
+    #[test]
+    #[allow(clippy::zero_prefixed_literal)]
+    fn test_parse_datetime_in_tz() {
+        assert_eq!(
+            parse_datetime_in_tz(Kolkata, "2021-07-09 05:00"),
+            Some(Kolkata.ymd(2021, 07, 09).and_hms(5, 0, 0))
+        );
+
+        assert_eq!(
+            parse_datetime_in_tz(Kolkata, "2021-07-09 5:00"),
+            Some(Kolkata.ymd(2021, 07, 09).and_hms(5, 0, 0))
+        );
+
+        assert_eq!(
+            parse_datetime_in_tz(Kolkata, "2021-07-09"),
+            Some(Kolkata.ymd(2021, 07, 09).and_hms(0, 0, 0))
+        );
+
+        let today = Local::now();
+
+        assert_eq!(
+            parse_datetime_in_tz(Kolkata, "05:00"),
+            Some(
+                Kolkata
+                    .ymd(today.year(), today.month(), today.day())
+                    .and_hms(5, 0, 0)
+            )
+        );
+
+        assert_eq!(
+            parse_datetime_in_tz(Kolkata, "3am"),
+            Some(
+                Kolkata
+                    .ymd(today.year(), today.month(), today.day())
+                    .and_hms(3, 0, 0)
+            )
+        );
+
+        assert_eq!(
+            parse_datetime_in_tz(Kolkata, "10pm"),
+            Some(
+                Kolkata
+                    .ymd(today.year(), today.month(), today.day())
+                    .and_hms(22, 0, 0)
+            )
+        );
+
+        assert_eq!(
+            parse_datetime_in_tz(Kolkata, "5:30pm"),
+            Some(
+                Kolkata
+                    .ymd(today.year(), today.month(), today.day())
+                    .and_hms(17, 30, 0)
+            )
+        );
+
+        assert_eq!(
+            parse_datetime_in_tz(Kolkata, "12pm"),
+            Some(
+                Kolkata
+                    .ymd(today.year(), today.month(), today.day())
+                    .and_hms(12, 0, 0)
+            )
+        );
+
+        assert_eq!(
+            parse_datetime_in_tz(Kolkata, "12AM"),
+            Some(
+                Kolkata
+                    .ymd(today.year(), today.month(), today.day())
+                    .and_hms(0, 0, 0)
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_datetime_errors() {
+        let error = try_parse_datetime_in_tz(Kolkata, "2021-07-09 17:60").unwrap_err();
+        assert_eq!(error.span, 14..16);
+        assert_eq!(error.understood(), "2021-07-09 17:");
+        assert_eq!(
+            error.to_string(),
+            "minute must be between 00 and 59\n  2021-07-09 17:60\n                ^^"
+        );
+
+        let error = try_parse_datetime_in_tz(Kolkata, "2021-13-09").unwrap_err();
+        assert_eq!(error.span, 5..7);
+
+        let error = try_parse_datetime_in_tz(Kolkata, "2021-02-30 10:00").unwrap_err();
+        assert_eq!(error.span, 8..10);
+        assert_eq!(error.reason, "February 2021 doesn't have a day 30");
+
+        let error = try_parse_datetime_in_tz(Kolkata, "5pm tomorrow").unwrap_err();
+        assert_eq!(error.span, 4..12);
+        assert_eq!(error.reason, "unexpected \"tomorrow\"");
+
+        let error = try_parse_datetime_in_tz(Kolkata, "2021-07-09 noon").unwrap_err();
+        assert_eq!(error.span, 11..15);
+
+        let error = try_parse_datetime_in_tz(Kolkata, "13pm").unwrap_err();
+        assert_eq!(error.span, 0..2);
+
+        let error = try_parse_datetime_in_tz(Kolkata, "yesterday").unwrap_err();
+        assert_eq!(error.span, 0..9);
+
+        let error = try_parse_datetime_in_tz(London, "2021-03-28 01:30").unwrap_err();
+        assert_eq!(error.span, 0..16);
+    }
+
+    #[test]
+    fn test_business_days() {
+        let business_days = BusinessDays {
+            week_start: Weekday::Mon,
+            holidays: vec![NaiveDate::from_ymd(2021, 3, 15)],
+        };
+        // A Friday
+        let now = NaiveDate::from_ymd(2021, 3, 12).and_hms(16, 45, 10);
+        let parse = |input| {
+            BUSINESS_DAYS
+                .captures(input)
+                .ok_or(())
+                .and_then(|relative| {
+                    parse_business_days(Kolkata, input, &relative, &business_days, now)
+                        .map_err(|_| ())
+                })
+                .map(|dt| dt.naive_local().to_string())
+        };
+
+        // Skipping the weekend, and then the holiday on Monday
+        assert_eq!(
+            parse("next business day 9am"),
+            Ok("2021-03-16 09:00:00".into())
+        );
+        assert_eq!(
+            parse("+3 Business Days 14:30"),
+            Ok("2021-03-18 14:30:00".into())
+        );
+        assert_eq!(parse("+1 business day"), Ok("2021-03-16 16:45:10".into()));
+        assert_eq!(parse("+0 business days"), Ok("2021-03-12 16:45:10".into()));
+
+        let sunday_to_thursday = BusinessDays {
+            week_start: Weekday::Sun,
+            holidays: vec![],
+        };
+        assert_eq!(
+            sunday_to_thursday.add(NaiveDate::from_ymd(2021, 3, 11), 1),
+            NaiveDate::from_ymd(2021, 3, 14)
+        );
+
+        assert!(parse("next business day noon").is_err());
+        assert!(parse("+3 business days 9am tomorrow").is_err());
+        assert!(parse("+99999 business days").is_err());
+    }
+
+    #[test]
+    fn test_parse_epoch() {
+        let parse = |input| {
+            try_parse_datetime_in_tz(Kolkata, input)
+                .map(|dt| dt.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, false))
+        };
+
+        assert_eq!(parse("1615368600"), Ok("2021-03-10T15:00:00+05:30".into()));
+        assert_eq!(
+            parse("1615368600123"),
+            Ok("2021-03-10T15:00:00.123+05:30".into())
+        );
+        assert_eq!(
+            parse("1615368600123456"),
+            Ok("2021-03-10T15:00:00.123456+05:30".into())
+        );
+        assert_eq!(
+            parse("1615368600123456789"),
+            Ok("2021-03-10T15:00:00.123456789+05:30".into())
+        );
+        assert_eq!(
+            parse("1615368600.5"),
+            Ok("2021-03-10T15:00:00.500+05:30".into())
+        );
+        assert_eq!(parse("@0"), Ok("1970-01-01T05:30:00+05:30".into()));
+        assert_eq!(parse("@-1.25"), Ok("1970-01-01T05:29:58.750+05:30".into()));
+        assert_eq!(
+            parse("@99999999999"),
+            Ok("5138-11-16T15:16:39+05:30".into())
+        );
+
+        let error = try_parse_datetime_in_tz(Kolkata, "161536860012").unwrap_err();
+        assert_eq!(error.span, 0..12);
+        assert!(error.reason.starts_with("12 digits is ambiguous"));
+        let error = try_parse_datetime_in_tz(Kolkata, "1615368600123.4").unwrap_err();
+        assert_eq!(error.span, 14..15);
+        let error = try_parse_datetime_in_tz(Kolkata, "@99999999999999999").unwrap_err();
+        assert_eq!(error.reason, "the epoch is out of range");
+        for input in &["-1625850000", "-1625850000123"] {
+            let error = try_parse_datetime_in_tz(Kolkata, input).unwrap_err();
+            assert_eq!(error.span, 0..1);
+            assert!(error.reason.starts_with("negative epochs need a leading @"));
+        }
+
+        // Not long enough to be an epoch
+        let error = try_parse_datetime_in_tz(Kolkata, "0930").unwrap_err();
+        assert!(error.reason.starts_with("not a date or time"));
+    }
+}
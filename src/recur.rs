@@ -0,0 +1,310 @@
+//! Expand a recurring local time, like every Wednesday at 09:00 in Europe/Berlin, into the
+//! instants it happens at.
+//!
+//! A recurring meeting stays at the same time on the clock where it's set, so across a daylight
+//! saving transition it moves by an hour in UTC (and in every zone that doesn't change then).
+//! Times that don't exist on a day, because clocks skip over them, happen as much later as the
+//! clocks skip (02:30 becomes 03:30); times that happen twice, as clocks go back, happen the first
+//! time. This is what iCalendar (RFC 5545) does.
+
+use chrono::{
+    DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveTime, Offset, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::resolve::parse_tz;
+
+/// A time of day that recurs on some days, in a zone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recurrence {
+    pub days: Days,
+    pub time: NaiveTime,
+    pub tz: Tz,
+}
+
+/// Which days a `Recurrence` happens on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Days {
+    Daily,
+    /// On these days of the week
+    Weekly(Vec<Weekday>),
+    /// On this day of each month, skipping months without it
+    Monthly(u32),
+}
+
+/// A recurrence rule that couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RecurrenceError {
+    pub rule: String,
+    pub reason: String,
+}
+
+impl fmt::Display for RecurrenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid recurrence {:?}: {}", self.rule, self.reason)
+    }
+}
+
+impl Error for RecurrenceError {}
+
+impl FromStr for Recurrence {
+    type Err = RecurrenceError;
+
+    /// Parse a rule like `every wednesday 09:00 in Europe/Berlin`. The days can be `day`,
+    /// `weekday` (Monday to Friday), days of the week separated by commas (`mon,thu`), or a day
+    /// of the month (`15th`), and the zone can be anything `parse_tz` understands.
+    fn from_str(rule: &str) -> Result<Recurrence, RecurrenceError> {
+        let error = |reason: &str| RecurrenceError {
+            rule: rule.to_owned(),
+            reason: reason.to_owned(),
+        };
+
+        let words = rule.split_whitespace().collect::<Vec<_>>();
+        let (days, time, zone) = match words[..] {
+            ["every", days, time, "in", ref zone @ ..] if !zone.is_empty() => (days, time, zone),
+            _ => return Err(error("expected every <days> <HH:MM> in <zone>")),
+        };
+
+        let days = parse_days(&days.to_lowercase()).ok_or_else(|| {
+            error("expected day, weekday, days of the week like mon,thu, or a day of the month like 15th")
+        })?;
+        let time = NaiveTime::parse_from_str(time, "%H:%M")
+            .map_err(|_| error("expected a time like 09:00"))?;
+        let tz = parse_tz(&zone.join(" ")).ok_or_else(|| error("unknown timezone"))?;
+
+        Ok(Recurrence { days, time, tz })
+    }
+}
+
+fn parse_days(days: &str) -> Option<Days> {
+    match days {
+        "day" => return Some(Days::Daily),
+        "weekday" => {
+            let weekdays = [
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ];
+            return Some(Days::Weekly(weekdays.to_vec()));
+        }
+        _ => {}
+    }
+
+    let ordinal = ["st", "nd", "rd", "th"]
+        .iter()
+        .find_map(|suffix| days.strip_suffix(suffix));
+    if let Some(day) = ordinal {
+        return day
+            .parse()
+            .ok()
+            .filter(|day| (1..=31).contains(day))
+            .map(Days::Monthly);
+    }
+
+    days.split(',')
+        .map(|day| day.trim_end_matches('s').parse::<Weekday>().ok())
+        .collect::<Option<Vec<_>>>()
+        .map(Days::Weekly)
+}
+
+impl Recurrence {
+    /// Whether this happens on `date`, where it's set
+    pub fn happens_on(&self, date: NaiveDate) -> bool {
+        match &self.days {
+            Days::Daily => true,
+            Days::Weekly(weekdays) => weekdays.contains(&date.weekday()),
+            Days::Monthly(day) => date.day() == *day,
+        }
+    }
+
+    /// The instant this happens at on `date`, if it happens on that day
+    pub fn on(&self, date: NaiveDate) -> Option<DateTime<Utc>> {
+        if !self.happens_on(date) {
+            return None;
+        }
+
+        let local = date.and_time(self.time);
+        let instant = match self.tz.from_local_datetime(&local) {
+            LocalResult::Single(instant) | LocalResult::Ambiguous(instant, _) => instant,
+            // Skipped over, so taken with the offset from before the clocks changed, which puts
+            // it as far past the change as it would have been
+            LocalResult::None => {
+                let before = self
+                    .tz
+                    .offset_from_local_datetime(&(local - Duration::days(1)));
+                let offset = before.earliest()?.fix();
+                let utc = local - Duration::seconds(i64::from(offset.local_minus_utc()));
+                return Some(DateTime::from_utc(utc, Utc));
+            }
+        };
+        Some(instant.with_timezone(&Utc))
+    }
+
+    /// Every instant this happens at on the dates from `first` to `last` (inclusive), where it's
+    /// set
+    pub fn between(
+        &self,
+        first: NaiveDate,
+        last: NaiveDate,
+    ) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        let days = (last - first).num_days().max(-1) + 1;
+        (0..days).filter_map(move |day| self.on(first + Duration::days(day)))
+    }
+
+    /// The first instant this happens at after `after`
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_timezone(&self.tz).date().naive_local();
+        // Every day of the month, up to the 31st, comes round within a year
+        (0..=366)
+            .filter_map(|day| self.on(start + Duration::days(day)))
+            .find(|instant| *instant > after)
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let days = match &self.days {
+            Days::Daily => "day".to_owned(),
+            Days::Weekly(weekdays) => weekdays
+                .iter()
+                .map(|weekday| format!("{:?}", weekday).to_lowercase())
+                .collect::<Vec<_>>()
+                .join(","),
+            Days::Monthly(day) => {
+                let suffix = match day {
+                    1 | 21 | 31 => "st",
+                    2 | 22 => "nd",
+                    3 | 23 => "rd",
+                    _ => "th",
+                };
+                format!("{}{}", day, suffix)
+            }
+        };
+        write!(
+            f,
+            "every {} {} in {}",
+            days,
+            self.time.format("%H:%M"),
+            self.tz
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_recurrence() {
+        let rule = "every wednesday 09:00 in Europe/Berlin"
+            .parse::<Recurrence>()
+            .unwrap();
+        assert_eq!(
+            rule,
+            Recurrence {
+                days: Days::Weekly(vec![Weekday::Wed]),
+                time: NaiveTime::from_hms(9, 0, 0),
+                tz: Tz::Europe__Berlin,
+            }
+        );
+        assert_eq!(rule.to_string(), "every wed 09:00 in Europe/Berlin");
+
+        let rule = "every 15th 17:30 in new york"
+            .parse::<Recurrence>()
+            .unwrap();
+        assert_eq!(rule.days, Days::Monthly(15));
+        assert_eq!(rule.tz, Tz::America__New_York);
+        assert_eq!(
+            "every mon,thu 08:00 in UTC"
+                .parse::<Recurrence>()
+                .unwrap()
+                .days,
+            Days::Weekly(vec![Weekday::Mon, Weekday::Thu])
+        );
+
+        assert!("every wednesday 09:00".parse::<Recurrence>().is_err());
+        assert!("every blursday 09:00 in UTC".parse::<Recurrence>().is_err());
+        assert!("every 32nd 09:00 in UTC".parse::<Recurrence>().is_err());
+        assert!("every day 9am in UTC".parse::<Recurrence>().is_err());
+        assert!("every day 09:00 in Atlantis".parse::<Recurrence>().is_err());
+    }
+
+    #[test]
+    fn test_between() {
+        // Berlin's clocks went forward on 2021-03-28, so the meeting moved an hour earlier in UTC
+        let rule = "every wednesday 09:00 in Europe/Berlin"
+            .parse::<Recurrence>()
+            .unwrap();
+        let instants = rule
+            .between(
+                NaiveDate::from_ymd(2021, 3, 17),
+                NaiveDate::from_ymd(2021, 4, 7),
+            )
+            .map(|instant| instant.to_rfc3339())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            instants,
+            vec![
+                "2021-03-17T08:00:00+00:00",
+                "2021-03-24T08:00:00+00:00",
+                "2021-03-31T07:00:00+00:00",
+                "2021-04-07T07:00:00+00:00",
+            ]
+        );
+        assert_eq!(
+            rule.between(
+                NaiveDate::from_ymd(2021, 3, 18),
+                NaiveDate::from_ymd(2021, 3, 17)
+            )
+            .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_transitions() {
+        let rule = |time| Recurrence {
+            days: Days::Daily,
+            time,
+            tz: Tz::Europe__Berlin,
+        };
+
+        // Skipped as clocks go forward, so an hour later
+        let skipped = rule(NaiveTime::from_hms(2, 30, 0))
+            .on(NaiveDate::from_ymd(2021, 3, 28))
+            .unwrap();
+        assert_eq!(
+            skipped
+                .with_timezone(&Tz::Europe__Berlin)
+                .naive_local()
+                .to_string(),
+            "2021-03-28 03:30:00"
+        );
+
+        // Twice as clocks go back, so the first time
+        let repeated = rule(NaiveTime::from_hms(2, 30, 0))
+            .on(NaiveDate::from_ymd(2021, 10, 31))
+            .unwrap();
+        assert_eq!(repeated.to_rfc3339(), "2021-10-31T00:30:00+00:00");
+
+        let monthly = Recurrence {
+            days: Days::Monthly(31),
+            time: NaiveTime::from_hms(12, 0, 0),
+            tz: Tz::UTC,
+        };
+        assert_eq!(
+            monthly
+                .next_after(Utc.ymd(2021, 3, 31).and_hms(12, 0, 0))
+                .unwrap()
+                .to_rfc3339(),
+            "2021-05-31T12:00:00+00:00"
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use chrono_tz::{Tz, TZ_VARIANTS};
+use lazy_static::lazy_static;
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+mod posix;
+#[cfg(not(any(windows, all(target_arch = "wasm32", feature = "wasm"))))]
+mod unix;
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+mod wasm;
+mod windows;
+
+pub use posix::PosixTz;
+pub use windows::windows_to_iana;
+
+/// Given a timezone string (like 'Asia/Kolkata'), return a chrono `Tz` that represents it
+pub fn parse_tz(tz: &str) -> Option<Tz> {
+    let result = Tz::from_str(tz);
+
+    if let Ok(tz) = result {
+        Some(tz)
+    } else {
+        ZONE_INDEX.find(&normalize_zone_name(tz))
+    }
+}
+
+// Zone names are matched case-insensitively, with underscores and spaces treated as equivalent
+fn normalize_zone_name(name: &str) -> String {
+    name.to_lowercase().replace("_", " ")
+}
+
+/// Normalized zone names, computed once so lookups that miss an exact match don't rewrite
+/// every name in `TZ_VARIANTS` each time.
+struct ZoneIndex {
+    // In `TZ_VARIANTS` order, which is what matches are resolved against
+    names: Vec<(String, Tz)>,
+}
+
+impl ZoneIndex {
+    fn new() -> ZoneIndex {
+        let names = TZ_VARIANTS
+            .iter()
+            .map(|tz| (normalize_zone_name(tz.name()), *tz))
+            .collect();
+
+        ZoneIndex { names }
+    }
+
+    /// Look up an already-normalized name: the first zone in `TZ_VARIANTS` order whose name
+    /// contains it
+    fn find(&self, name: &str) -> Option<Tz> {
+        self.names
+            .iter()
+            .find(|(candidate, _)| candidate.contains(name))
+            .map(|&(_, tz)| tz)
+    }
+}
+
+lazy_static! {
+    static ref ZONE_INDEX: ZoneIndex = ZoneIndex::new();
+}
+
+/// Why the current timezone couldn't be determined
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DetectError {
+    /// Reading the system's timezone configuration failed
+    Io(io::Error),
+    /// The system has no timezone configuration that tz knows how to read
+    NotConfigured,
+    /// The system's timezone configuration doesn't name a known timezone
+    UnknownZone(String),
+}
+
+impl fmt::Display for DetectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DetectError::Io(e) => write!(f, "couldn't read the system timezone: {}", e),
+            DetectError::NotConfigured => write!(f, "the system timezone isn't configured"),
+            DetectError::UnknownZone(zone) => write!(f, "unknown system timezone {:?}", zone),
+        }
+    }
+}
+
+impl Error for DetectError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DetectError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DetectError {
+    fn from(e: io::Error) -> DetectError {
+        DetectError::Io(e)
+    }
+}
+
+/// Detect the timezone the system is configured to use. Built for wasm32 with the `wasm` feature,
+/// this is the zone the JavaScript host reports instead.
+pub fn current_tz() -> Result<Tz, DetectError> {
+    #[cfg(windows)]
+    let tz = windows::registry_zone()?;
+    #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+    let tz = wasm::intl_zone()?;
+    #[cfg(not(any(windows, all(target_arch = "wasm32", feature = "wasm"))))]
+    let tz = unix::system_zone()?;
+
+    tz.parse().map_err(|_| DetectError::UnknownZone(tz))
+}
+
+/// The tzdata release the zones in chrono-tz, and so every conversion, come from. chrono-tz
+/// doesn't say, so this needs updating along with it.
+pub const BUNDLED_TZDATA_VERSION: &str = "2020a";
+
+/// The tzdata release of the system's zoneinfo database, when there is one that says. Only Unix-likes
+/// have one.
+pub fn system_tzdata_version() -> Option<String> {
+    #[cfg(not(any(windows, all(target_arch = "wasm32", feature = "wasm"))))]
+    return unix::tzdata_version_in(std::path::Path::new("/"));
+    #[cfg(any(windows, all(target_arch = "wasm32", feature = "wasm")))]
+    return None;
+}
+
+/// Whether tzdata release `a` came out before `b`, like 2020a before 2020b and 2021a. Releases
+/// that aren't a year and letters are never older.
+pub fn tzdata_older(a: &str, b: &str) -> bool {
+    // A year, then letters: a to z, then za and on
+    let key = |version: &str| {
+        let letters = version.get(4..)?;
+        let year = version.get(..4)?.parse::<u32>().ok()?;
+        if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_lowercase()) {
+            return None;
+        }
+        Some((year, letters.len(), letters.to_owned()))
+    };
+    matches!((key(a), key(b)), (Some(a), Some(b)) if a < b)
+}
+
+/// Where `current_tz` looks for the timezone on this platform, in the order it looks, to point
+/// at in messages when detection fails
+pub fn detection_sources() -> Vec<&'static str> {
+    #[cfg(windows)]
+    return windows::SOURCES.to_vec();
+    #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+    return wasm::SOURCES.to_vec();
+    #[cfg(not(any(windows, all(target_arch = "wasm32", feature = "wasm"))))]
+    return unix::sources();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tz() {
+        assert_eq!(parse_tz("Asia/Kolkata"), Some(Tz::Asia__Kolkata));
+        assert_eq!(parse_tz("FooBar"), None);
+        assert_eq!(parse_tz("Europe/London"), Some(Tz::Europe__London));
+    }
+
+    #[test]
+    fn test_parse_tz_loose() {
+        assert_eq!(parse_tz("asia/kolkata"), Some(Tz::Asia__Kolkata));
+        assert_eq!(parse_tz("new york"), Some(Tz::America__New_York));
+        assert_eq!(parse_tz("New_York"), Some(Tz::America__New_York));
+        assert_eq!(parse_tz("europe/lond"), Some(Tz::Europe__London));
+        assert_eq!(parse_tz("kolkata"), Some(Tz::Asia__Kolkata));
+
+        // Names that several zones contain go to the first of them in TZ_VARIANTS order, even
+        // when a later zone starts with the name
+        assert_eq!(parse_tz("can"), Some(Tz::America__Cancun));
+        assert_eq!(parse_tz("us"), Some(Tz::Africa__Lusaka));
+        assert_eq!(parse_tz("pacific"), Some(Tz::Canada__Pacific));
+    }
+
+    #[test]
+    fn test_tzdata_older() {
+        assert!(tzdata_older("2020a", "2020b"));
+        assert!(tzdata_older("2020a", "2025b"));
+        assert!(tzdata_older("2020z", "2020za"));
+        assert!(!tzdata_older("2020a", "2020a"));
+        assert!(!tzdata_older("2025b", "2020a"));
+        assert!(!tzdata_older(BUNDLED_TZDATA_VERSION, "unknown"));
+    }
+
+    // Which zone is detected depends on the machine the tests run on, so this only checks that
+    // detection doesn't trip over whatever configuration it finds; the individual sources are
+    // tested against fixtures
+    #[test]
+    fn test_current_tz() {
+        match current_tz() {
+            Ok(_) | Err(DetectError::NotConfigured) => {}
+            Err(e) => panic!("Failed to detect the current timezone: {}", e),
+        }
+
+        assert!(!detection_sources().is_empty());
+    }
+}
@@ -0,0 +1,403 @@
+// POSIX TZ strings, like `CET-1CEST,M3.5.0,M10.5.0/3`, which describe a zone by its offsets and
+// the rules for when daylight saving time starts and ends rather than naming it. Embedded
+// systems without a zoneinfo database (OpenWrt, for one) configure the timezone this way.
+
+use chrono::{Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, Offset, TimeZone};
+use chrono_tz::{OffsetName, Tz, TZ_VARIANTS};
+
+/// A parsed POSIX TZ string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PosixTz {
+    std: Period,
+    dst: Option<Dst>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Period {
+    name: String,
+    /// Seconds east of UTC (the opposite of how the string writes it)
+    offset: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Dst {
+    period: Period,
+    start: Rule,
+    end: Rule,
+}
+
+/// When a change happens: on `date`, `time` seconds after midnight in the time it changes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rule {
+    date: Date,
+    time: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Date {
+    /// `Jn`: the nth day of the year (1-365), not counting February 29th
+    Julian(u32),
+    /// `n`: the nth day of the year (0-365), counting February 29th
+    Ordinal(u32),
+    /// `Mm.w.d`: weekday `d` (0 is Sunday) of week `w` (1-5, 5 being the last) of month `m`
+    Month { month: u32, week: u32, weekday: u32 },
+}
+
+// Without rules, daylight saving time follows the US's (as glibc does)
+const DEFAULT_START: Rule = Rule {
+    date: Date::Month {
+        month: 3,
+        week: 2,
+        weekday: 0,
+    },
+    time: 2 * 3600,
+};
+const DEFAULT_END: Rule = Rule {
+    date: Date::Month {
+        month: 11,
+        week: 1,
+        weekday: 0,
+    },
+    time: 2 * 3600,
+};
+
+impl PosixTz {
+    /// Parse a POSIX TZ string, like `IST-5:30` or `EST5EDT,M3.2.0,M11.1.0`
+    pub fn parse(tz: &str) -> Option<PosixTz> {
+        let mut rest = tz;
+
+        let std = Period {
+            name: name(&mut rest)?,
+            offset: -duration(&mut rest)?,
+        };
+        if rest.is_empty() {
+            return Some(PosixTz { std, dst: None });
+        }
+
+        let dst_name = name(&mut rest)?;
+        let dst_offset = if rest.is_empty() || rest.starts_with(',') {
+            std.offset + 3600
+        } else {
+            -duration(&mut rest)?
+        };
+
+        let (start, end) = match rest.strip_prefix(',') {
+            Some(rules) => {
+                rest = rules;
+                let start = rule(&mut rest)?;
+                rest = rest.strip_prefix(',')?;
+                (start, rule(&mut rest)?)
+            }
+            None => (DEFAULT_START, DEFAULT_END),
+        };
+        if !rest.is_empty() {
+            return None;
+        }
+
+        let period = Period {
+            name: dst_name,
+            offset: dst_offset,
+        };
+        Some(PosixTz {
+            std,
+            dst: Some(Dst { period, start, end }),
+        })
+    }
+
+    /// The abbreviation and offset in effect at `utc`
+    pub fn offset_at(&self, utc: &NaiveDateTime) -> (&str, FixedOffset) {
+        let period = self.period_at(utc);
+        (&period.name, FixedOffset::east(period.offset))
+    }
+
+    /// The UTC time of a local time. A local time that happens twice, as clocks go back, is taken
+    /// to be the earlier one; one that's skipped, as they go forward, is taken to be standard time.
+    pub fn to_utc(&self, local: &NaiveDateTime) -> NaiveDateTime {
+        let mut offsets = vec![self.std.offset];
+        if let Some(dst) = &self.dst {
+            offsets.push(dst.period.offset);
+        }
+        offsets.sort_unstable_by(|a, b| b.cmp(a));
+
+        offsets
+            .into_iter()
+            .map(|offset| (offset, *local - Duration::seconds(i64::from(offset))))
+            .find(|(offset, utc)| self.period_at(utc).offset == *offset)
+            .map_or_else(
+                || *local - Duration::seconds(i64::from(self.std.offset)),
+                |(_, utc)| utc,
+            )
+    }
+
+    fn period_at(&self, utc: &NaiveDateTime) -> &Period {
+        let dst = match &self.dst {
+            Some(dst) => dst,
+            None => return &self.std,
+        };
+
+        let year = utc.year();
+        let start = dst.start.utc(year, self.std.offset);
+        let end = dst.end.utc(year, dst.period.offset);
+
+        // In the southern hemisphere, daylight saving time spans the new year
+        let in_dst = if start < end {
+            start <= *utc && *utc < end
+        } else {
+            !(end <= *utc && *utc < start)
+        };
+
+        if in_dst {
+            &dst.period
+        } else {
+            &self.std
+        }
+    }
+
+    /// A zone that agrees with these rules throughout `year`. Several zones usually do (and so
+    /// convert times the same way), so this prefers the zone named after the rules (like `CET` or
+    /// `EST5EDT`), then any using the same abbreviations, then any with the same offsets.
+    pub fn matching_zone(&self, year: i32) -> Option<Tz> {
+        let mut instants = (0..366 * 2)
+            .filter_map(|half_days| {
+                NaiveDate::from_ymd_opt(year, 1, 1)
+                    .map(|date| date.and_hms(0, 0, 0) + Duration::hours(12 * half_days))
+            })
+            .filter(|instant| instant.year() == year)
+            .collect::<Vec<_>>();
+
+        // Both sides of each change, to catch zones that change at a different time of day
+        if let Some(dst) = &self.dst {
+            for change in &[
+                dst.start.utc(year, self.std.offset),
+                dst.end.utc(year, dst.period.offset),
+            ] {
+                instants.push(*change - Duration::seconds(1));
+                instants.push(*change);
+            }
+        }
+
+        let matches = |tz: &Tz, names: bool| {
+            instants.iter().all(|instant| {
+                let offset = tz.offset_from_utc_datetime(instant);
+                let period = self.period_at(instant);
+                offset.fix().local_minus_utc() == period.offset
+                    && (!names || offset.abbreviation() == period.name)
+            })
+        };
+
+        let named = [
+            self.std.name.clone(),
+            self.dst.as_ref().map_or_else(String::new, |dst| {
+                format!(
+                    "{}{}{}",
+                    self.std.name,
+                    -self.std.offset / 3600,
+                    dst.period.name
+                )
+            }),
+        ];
+
+        named
+            .iter()
+            .filter_map(|name| name.parse::<Tz>().ok())
+            .find(|tz| matches(tz, true))
+            .or_else(|| TZ_VARIANTS.iter().copied().find(|tz| matches(tz, true)))
+            .or_else(|| TZ_VARIANTS.iter().copied().find(|tz| matches(tz, false)))
+    }
+}
+
+impl Rule {
+    // When the change happens in `year`, in UTC, given the offset in effect before it
+    fn utc(self, year: i32, offset: i32) -> NaiveDateTime {
+        let date = match self.date {
+            Date::Julian(day) => {
+                let leap = NaiveDate::from_ymd_opt(year, 2, 29).is_some();
+                let day = if leap && day >= 60 { day + 1 } else { day };
+                NaiveDate::from_yo_opt(year, day)
+            }
+            Date::Ordinal(day) => NaiveDate::from_yo_opt(year, day + 1),
+            Date::Month {
+                month,
+                week,
+                weekday,
+            } => NaiveDate::from_ymd_opt(year, month, 1).map(|first| {
+                let first_weekday = first.weekday().num_days_from_sunday();
+                let mut day = 1 + (7 + weekday - first_weekday) % 7 + (week - 1) * 7;
+                while NaiveDate::from_ymd_opt(year, month, day).is_none() {
+                    day -= 7;
+                }
+                NaiveDate::from_ymd(year, month, day)
+            }),
+        };
+
+        // Day 365 of a non-leap year doesn't exist; treat it as the end of the year
+        let date = date.unwrap_or_else(|| NaiveDate::from_ymd(year, 12, 31));
+        date.and_hms(0, 0, 0) + Duration::seconds(i64::from(self.time - offset))
+    }
+}
+
+// A zone abbreviation: three or more letters, or anything (but '>') between angle brackets
+fn name(rest: &mut &str) -> Option<String> {
+    let (name, remaining) = match rest.strip_prefix('<') {
+        Some(quoted) => {
+            let end = quoted.find('>')?;
+            (&quoted[..end], &quoted[end + 1..])
+        }
+        None => {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        }
+    };
+
+    if name.len() < 3 {
+        return None;
+    }
+    *rest = remaining;
+    Some(name.to_owned())
+}
+
+// `[+-]hh[:mm[:ss]]` in seconds. Hours go up to 167, which rule times can use.
+fn duration(rest: &mut &str) -> Option<i32> {
+    let (sign, unsigned) = match rest.as_bytes().first() {
+        Some(b'-') => (-1, &rest[1..]),
+        Some(b'+') => (1, &rest[1..]),
+        _ => (1, *rest),
+    };
+
+    let end = unsigned
+        .find(|c: char| !c.is_ascii_digit() && c != ':')
+        .unwrap_or(unsigned.len());
+    let mut parts = unsigned[..end].split(':');
+
+    let hours = parts.next()?.parse::<i32>().ok().filter(|h| *h <= 167)?;
+    let mut seconds = hours * 3600;
+    for scale in &[60, 1] {
+        if let Some(part) = parts.next() {
+            seconds += scale * part.parse::<i32>().ok().filter(|n| *n < 60)?;
+        }
+    }
+    if parts.next().is_some() {
+        return None;
+    }
+
+    *rest = &unsigned[end..];
+    Some(sign * seconds)
+}
+
+// `date[/time]`
+fn rule(rest: &mut &str) -> Option<Rule> {
+    let end = rest.find([',', '/']).unwrap_or(rest.len());
+    let spec = &rest[..end];
+
+    let date = if let Some(day) = spec.strip_prefix('J') {
+        Date::Julian(day.parse().ok().filter(|day| (1..=365).contains(day))?)
+    } else if let Some(month) = spec.strip_prefix('M') {
+        let mut fields = month.split('.').map(|field| field.parse::<u32>().ok());
+        let (month, week, weekday) = (fields.next()??, fields.next()??, fields.next()??);
+        if fields.next().is_some()
+            || !(1..=12).contains(&month)
+            || !(1..=5).contains(&week)
+            || weekday > 6
+        {
+            return None;
+        }
+        Date::Month {
+            month,
+            week,
+            weekday,
+        }
+    } else {
+        Date::Ordinal(spec.parse().ok().filter(|day| *day <= 365)?)
+    };
+
+    *rest = &rest[end..];
+    let time = match rest.strip_prefix('/') {
+        Some(time) => {
+            *rest = time;
+            duration(rest)?
+        }
+        None => 2 * 3600,
+    };
+
+    Some(Rule { date, time })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(tz: &PosixTz, datetime: &str) -> (String, i32) {
+        let utc = NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M:%S").unwrap();
+        let (name, offset) = tz.offset_at(&utc);
+        (name.to_owned(), offset.local_minus_utc())
+    }
+
+    #[test]
+    fn test_parse() {
+        let tz = PosixTz::parse("IST-5:30").unwrap();
+        assert_eq!(at(&tz, "2021-07-01 00:00:00"), ("IST".to_owned(), 19800));
+
+        let tz = PosixTz::parse("<-03>3").unwrap();
+        assert_eq!(at(&tz, "2021-07-01 00:00:00"), ("-03".to_owned(), -10800));
+
+        for invalid in &[
+            "",
+            "UT0",
+            "CET",
+            "CET-1CEST,M3.5.0",
+            "CET-1CEST,M13.5.0,M10.5.0",
+        ] {
+            assert_eq!(PosixTz::parse(invalid), None, "{:?}", invalid);
+        }
+        assert_eq!(
+            PosixTz::parse("EST5EDT"),
+            PosixTz::parse("EST5EDT4,M3.2.0/2,M11.1.0/2")
+        );
+    }
+
+    #[test]
+    fn test_offset_at() {
+        // The EU changes at 01:00 UTC on the last Sundays of March and October
+        let tz = PosixTz::parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        assert_eq!(at(&tz, "2021-03-28 00:59:59"), ("CET".to_owned(), 3600));
+        assert_eq!(at(&tz, "2021-03-28 01:00:00"), ("CEST".to_owned(), 7200));
+        assert_eq!(at(&tz, "2021-10-31 00:59:59"), ("CEST".to_owned(), 7200));
+        assert_eq!(at(&tz, "2021-10-31 01:00:00"), ("CET".to_owned(), 3600));
+
+        let utc = |local: &str| {
+            let local = NaiveDateTime::parse_from_str(local, "%Y-%m-%d %H:%M").unwrap();
+            tz.to_utc(&local).format("%Y-%m-%d %H:%M").to_string()
+        };
+        assert_eq!(utc("2021-07-01 12:00"), "2021-07-01 10:00");
+        assert_eq!(utc("2021-10-31 02:30"), "2021-10-31 00:30");
+        assert_eq!(utc("2021-03-28 02:30"), "2021-03-28 01:30");
+
+        let tz = PosixTz::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(at(&tz, "2021-01-15 00:00:00"), ("AEDT".to_owned(), 39600));
+        assert_eq!(at(&tz, "2021-07-15 00:00:00"), ("AEST".to_owned(), 36000));
+        assert_eq!(at(&tz, "2021-12-15 00:00:00"), ("AEDT".to_owned(), 39600));
+
+        // Both day-of-year forms, with and without February 29th
+        let tz = PosixTz::parse("XST0XDT,J60/0,300/0").unwrap();
+        assert_eq!(at(&tz, "2020-02-29 12:00:00"), ("XST".to_owned(), 0));
+        assert_eq!(at(&tz, "2020-03-01 12:00:00"), ("XDT".to_owned(), 3600));
+        assert_eq!(at(&tz, "2021-10-27 12:00:00"), ("XDT".to_owned(), 3600));
+        assert_eq!(at(&tz, "2021-10-28 12:00:00"), ("XST".to_owned(), 0));
+    }
+
+    #[test]
+    fn test_matching_zone() {
+        let zone = |tz: &str| PosixTz::parse(tz).unwrap().matching_zone(2021);
+        assert_eq!(zone("CET-1CEST,M3.5.0,M10.5.0/3"), Some(Tz::CET));
+        assert_eq!(zone("EST5EDT,M3.2.0,M11.1.0"), Some(Tz::EST5EDT));
+        assert_eq!(zone("UTC0"), Some(Tz::UTC));
+
+        let tz = zone("IST-5:30").unwrap();
+        assert!(tz == Tz::Asia__Kolkata || tz == Tz::Asia__Calcutta);
+
+        // No zone changes on these days
+        assert_eq!(zone("CET-1CEST,M3.1.0,M10.1.0"), None);
+    }
+}
@@ -0,0 +1,437 @@
+// Detection on Unix-likes, which keep the timezone as a name in a file (/etc/timezone on Debian
+// and derivatives, /var/db/zoneinfo on FreeBSD) or as /etc/localtime, a symlink into the
+// zoneinfo database (most everything else) or a copy of one of its files. Android is the odd one
+// out, keeping it in a system property instead, and illumos/Solaris set it as a variable in
+// /etc/default/init. Embedded systems without a zoneinfo database (OpenWrt) describe it with a
+// POSIX TZ string in /etc/TZ. With the `systemd` feature, systemd is asked before any of them.
+
+use chrono::{Datelike, Utc};
+use chrono_tz::{Tz, TZ_VARIANTS};
+
+use std::collections::HashSet;
+use std::env;
+use std::fs::{self, read_link};
+use std::path::{Path, PathBuf};
+
+use super::{DetectError, PosixTz};
+
+/// Files containing just the zone's name, relative to `/`
+const NAME_FILES: &[&str] = &["etc/timezone", "var/db/zoneinfo"];
+
+/// Where zoneinfo databases are usually installed, relative to `/`
+const ZONEINFO_DIRS: &[&str] = &[
+    "usr/share/zoneinfo",
+    "usr/lib/zoneinfo",
+    "usr/share/lib/zoneinfo",
+    "var/db/timezone/zoneinfo",
+];
+
+/// The name of the zone the system is configured to use
+pub fn system_zone() -> Result<String, DetectError> {
+    #[cfg(target_os = "android")]
+    if let Some(zone) = android_zone() {
+        return Ok(zone);
+    }
+
+    #[cfg(all(target_os = "linux", feature = "systemd"))]
+    if let Some(zone) = timedated_zone() {
+        return Ok(zone);
+    }
+
+    // illumos and Solaris set the zone for every process through TZ (from /etc/default/init), so
+    // unlike elsewhere it's the system's setting rather than an override
+    #[cfg(any(target_os = "illumos", target_os = "solaris"))]
+    if let Some(zone) = env::var("TZ").ok().and_then(|tz| tz_value(&tz)) {
+        return Ok(zone);
+    }
+
+    system_zone_in(Path::new("/"))
+}
+
+/// Where `system_zone` looks, in order
+pub fn sources() -> Vec<&'static str> {
+    let mut sources = Vec::new();
+    if cfg!(target_os = "android") {
+        sources.push("the persist.sys.timezone property");
+    }
+    if cfg!(all(target_os = "linux", feature = "systemd")) {
+        sources.push("timedatectl");
+    }
+    if cfg!(any(target_os = "illumos", target_os = "solaris")) {
+        sources.push("the TZ variable");
+    }
+    sources.extend(&[
+        "/etc/timezone",
+        "/var/db/zoneinfo",
+        "/etc/default/init",
+        "/etc/localtime",
+        "/etc/TZ",
+    ]);
+    sources
+}
+
+/// The tzdata release the zoneinfo database under `root` was built from, as recorded in its
+/// tzdata.zi (`# version 2021a`) or, on some distributions, a +VERSION file
+pub fn tzdata_version_in(root: &Path) -> Option<String> {
+    let tzdir = env::var_os("TZDIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(ZONEINFO_DIRS.iter().map(|dir| root.join(dir)))
+        .find(|dir| dir.is_dir())?;
+
+    let from_zi = fs::read_to_string(tzdir.join("tzdata.zi"))
+        .ok()
+        .and_then(|zi| {
+            zi.lines()
+                .next()?
+                .strip_prefix("# version ")
+                .map(str::to_owned)
+        });
+    from_zi
+        .or_else(|| fs::read_to_string(tzdir.join("+VERSION")).ok())
+        .map(|version| version.trim().to_owned())
+        .filter(|version| !version.is_empty())
+}
+
+// Android (including Termux) has no /etc/timezone or usable /etc/localtime, but keeps the zone
+// in a system property
+#[cfg(target_os = "android")]
+fn android_zone() -> Option<String> {
+    command_output("getprop", &["persist.sys.timezone"])
+}
+
+// Where systemd is running, timedated knows the zone regardless of how /etc is laid out.
+// `timedatectl` asks it over D-Bus (and fails if it isn't running, e.g. in a container).
+#[cfg(all(target_os = "linux", feature = "systemd"))]
+fn timedated_zone() -> Option<String> {
+    command_output("timedatectl", &["show", "--property=Timezone", "--value"])
+}
+
+// The trimmed output of a command, if it succeeds and prints anything
+#[cfg(any(target_os = "android", all(target_os = "linux", feature = "systemd")))]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+
+    let output = String::from_utf8(output.stdout).ok()?;
+    Some(output.trim().to_owned()).filter(|output| !output.is_empty())
+}
+
+// Detection relative to `root` rather than `/`, so it can be tested
+fn system_zone_in(root: &Path) -> Result<String, DetectError> {
+    for file in NAME_FILES {
+        let file = root.join(file);
+
+        if file.is_file() {
+            let zone = fs::read_to_string(&file)?;
+            let zone = zone.trim();
+
+            if !zone.is_empty() {
+                return Ok(zone.to_owned());
+            }
+        }
+    }
+
+    if let Ok(init) = fs::read_to_string(root.join("etc/default/init")) {
+        if let Some(zone) = init_zone(&init) {
+            return Ok(zone);
+        }
+    }
+
+    let localtime = root.join("etc/localtime");
+
+    if let Ok(metadata) = fs::symlink_metadata(&localtime) {
+        if !metadata.file_type().is_symlink() {
+            let contents = fs::read(&localtime)?;
+            return fingerprint(root, &contents).ok_or_else(|| {
+                DetectError::UnknownZone(format!(
+                    "{} (a copy of no known zone)",
+                    localtime.display()
+                ))
+            });
+        }
+
+        let target = read_link(&localtime)?;
+
+        if let Some(zone) = zone_from_path(&target) {
+            return Ok(zone);
+        }
+
+        // The link may point at another link (e.g. /etc/alternatives/localtime) that leads into
+        // the database
+        return fs::canonicalize(&localtime)
+            .ok()
+            .and_then(|path| zone_from_path(&path))
+            .ok_or_else(|| DetectError::UnknownZone(target.display().to_string()));
+    }
+
+    if let Ok(tz) = fs::read_to_string(root.join("etc/TZ")) {
+        if let Some(tz) = tz_value(&tz) {
+            return posix_zone(&tz, Utc::now().year()).ok_or(DetectError::UnknownZone(tz));
+        }
+    }
+
+    Err(DetectError::NotConfigured)
+}
+
+// The zone a TZ value names, which is usually a POSIX TZ string rather than a zone's name
+fn posix_zone(tz: &str, year: i32) -> Option<String> {
+    if tz.parse::<Tz>().is_ok() {
+        return Some(tz.to_owned());
+    }
+
+    PosixTz::parse(tz)?
+        .matching_zone(year)
+        .map(|zone| zone.name().to_owned())
+}
+
+// The TZ set by an illumos/Solaris /etc/default/init, a shell-style file of variables like
+// `TZ=US/Pacific` in which the last assignment wins
+fn init_zone(init: &str) -> Option<String> {
+    init.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.strip_prefix("TZ="))
+        .next_back()
+        .and_then(tz_value)
+}
+
+// The zone named by a value of TZ, which may be quoted and, to make clear it names a file in the
+// zoneinfo database, start with a ':'
+fn tz_value(value: &str) -> Option<String> {
+    let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+    let value = value.strip_prefix(':').unwrap_or(value).trim();
+    Some(value.to_owned()).filter(|value| !value.is_empty())
+}
+
+// Some systems copy a zone's file to /etc/localtime rather than linking to it, which leaves its
+// contents as the only clue to which zone it is. Several zones can share a file (Europe/London
+// is also GB and Europe/Belfast), so the names listed in the database's zone1970.tab (or the
+// older zone.tab), which are the canonical ones, are tried first.
+fn fingerprint(root: &Path, contents: &[u8]) -> Option<String> {
+    let tzdir = env::var_os("TZDIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(ZONEINFO_DIRS.iter().map(|dir| root.join(dir)))
+        .find(|dir| dir.is_dir())?;
+
+    let canonical = ["zone1970.tab", "zone.tab"]
+        .iter()
+        .filter_map(|tab| fs::read_to_string(tzdir.join(tab)).ok())
+        .next()
+        .map(|tab| {
+            tab.lines()
+                .filter(|line| !line.starts_with('#'))
+                .filter_map(|line| line.split('\t').nth(2))
+                .map(str::to_owned)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let seen = canonical.iter().cloned().collect::<HashSet<_>>();
+    let others = TZ_VARIANTS
+        .iter()
+        .map(|tz| tz.name().to_owned())
+        .filter(|name| !seen.contains(name));
+
+    canonical.into_iter().chain(others).find(|zone| {
+        let path = tzdir.join(zone);
+        fs::metadata(&path).is_ok_and(|metadata| metadata.len() == contents.len() as u64)
+            && fs::read(&path).is_ok_and(|candidate| candidate == contents)
+    })
+}
+
+/// The zone a path into a zoneinfo database is for, e.g. `Europe/London` for
+/// `/usr/share/zoneinfo/Europe/London` or the relative `../usr/share/zoneinfo/Europe/London`.
+/// The database can be anywhere: macOS, for one, keeps it at versioned paths like
+/// `/var/db/timezone/tz/2021a.1.0/zoneinfo/`, and NixOS and Guix in their stores
+/// (`/nix/store/<hash>-tzdata-2021a/share/zoneinfo/`). Zones in the database's `posix/` and
+/// `right/` subtrees (the latter counting leap seconds) are the same as the plain ones.
+fn zone_from_path(path: &Path) -> Option<String> {
+    let components = path
+        .iter()
+        .map(|component| component.to_str())
+        .collect::<Option<Vec<_>>>()?;
+
+    let start = components.iter().rposition(|&c| c == "zoneinfo")? + 1;
+    match &components[start..] {
+        [] | ["posix" | "right"] => None,
+        ["posix" | "right", zone @ ..] | zone => Some(zone.join("/")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // An empty directory to stand in for `/`, removed when dropped
+    struct Root(PathBuf);
+
+    impl Root {
+        fn new(name: &str) -> Root {
+            let root = std::env::temp_dir().join(format!("tz-{}-{}", name, std::process::id()));
+            fs::create_dir_all(root.join("etc")).unwrap();
+            Root(root)
+        }
+    }
+
+    impl Drop for Root {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_tzdata_version() {
+        let root = Root::new("tzdata-zi");
+        let zoneinfo = root.0.join("usr/share/zoneinfo");
+        fs::create_dir_all(&zoneinfo).unwrap();
+        assert_eq!(tzdata_version_in(&root.0), None);
+
+        fs::write(zoneinfo.join("+VERSION"), "2023c\n").unwrap();
+        assert_eq!(tzdata_version_in(&root.0).as_deref(), Some("2023c"));
+        fs::write(
+            zoneinfo.join("tzdata.zi"),
+            "# version 2025b\n# This zic input file...\n",
+        )
+        .unwrap();
+        assert_eq!(tzdata_version_in(&root.0).as_deref(), Some("2025b"));
+    }
+
+    #[test]
+    fn test_system_zone() {
+        let root = Root::new("etc-timezone");
+        fs::write(root.0.join("etc/timezone"), "Asia/Kolkata\n").unwrap();
+        assert_eq!(system_zone_in(&root.0).unwrap(), "Asia/Kolkata");
+
+        // Arch and others only have the symlink, often a relative one
+        let root = Root::new("relative-localtime");
+        symlink(
+            "../usr/share/zoneinfo/Europe/Berlin",
+            root.0.join("etc/localtime"),
+        )
+        .unwrap();
+        assert_eq!(system_zone_in(&root.0).unwrap(), "Europe/Berlin");
+
+        // An empty /etc/timezone is ignored in favor of the symlink
+        fs::write(root.0.join("etc/timezone"), "\n").unwrap();
+        assert_eq!(system_zone_in(&root.0).unwrap(), "Europe/Berlin");
+
+        let root = Root::new("chained-localtime");
+        fs::create_dir_all(root.0.join("zoneinfo/America")).unwrap();
+        fs::write(root.0.join("zoneinfo/America/Lima"), "").unwrap();
+        symlink(
+            root.0.join("zoneinfo/America/Lima"),
+            root.0.join("etc/alternative"),
+        )
+        .unwrap();
+        symlink("alternative", root.0.join("etc/localtime")).unwrap();
+        assert_eq!(system_zone_in(&root.0).unwrap(), "America/Lima");
+
+        let root = Root::new("copied-localtime");
+        let zoneinfo = root.0.join("usr/share/zoneinfo");
+        fs::create_dir_all(zoneinfo.join("Europe")).unwrap();
+        fs::write(
+            zoneinfo.join("zone.tab"),
+            "# comment\nGB\t+513030-0000731\tEurope/London\n",
+        )
+        .unwrap();
+        fs::write(zoneinfo.join("Europe/Belfast"), "TZif London").unwrap();
+        fs::write(zoneinfo.join("Europe/London"), "TZif London").unwrap();
+        fs::write(zoneinfo.join("Europe/Paris"), "TZif Paris").unwrap();
+        fs::write(zoneinfo.join("EST5EDT"), "TZif EST5EDT").unwrap();
+
+        fs::write(root.0.join("etc/localtime"), "TZif London").unwrap();
+        assert_eq!(system_zone_in(&root.0).unwrap(), "Europe/London");
+        fs::write(root.0.join("etc/localtime"), "TZif EST5EDT").unwrap();
+        assert_eq!(system_zone_in(&root.0).unwrap(), "EST5EDT");
+        fs::write(root.0.join("etc/localtime"), "TZif Mars").unwrap();
+        assert!(matches!(
+            system_zone_in(&root.0),
+            Err(DetectError::UnknownZone(_))
+        ));
+
+        // FreeBSD records the name alongside the copy
+        fs::create_dir_all(root.0.join("var/db")).unwrap();
+        fs::write(root.0.join("var/db/zoneinfo"), "Europe/Paris\n").unwrap();
+        assert_eq!(system_zone_in(&root.0).unwrap(), "Europe/Paris");
+
+        // illumos and Solaris set TZ for every process in /etc/default/init
+        let root = Root::new("default-init");
+        fs::create_dir_all(root.0.join("etc/default")).unwrap();
+        fs::write(
+            root.0.join("etc/default/init"),
+            "# TZ=Etc/UTC\nTZ=\"US/Pacific\"\nCMASK=022\n",
+        )
+        .unwrap();
+        assert_eq!(system_zone_in(&root.0).unwrap(), "US/Pacific");
+
+        // OpenWrt describes the zone with a POSIX TZ string
+        let root = Root::new("etc-tz");
+        fs::write(root.0.join("etc/TZ"), "CET-1CEST,M3.5.0,M10.5.0/3\n").unwrap();
+        assert_eq!(system_zone_in(&root.0).unwrap(), "CET");
+        fs::write(root.0.join("etc/TZ"), "UTC\n").unwrap();
+        assert_eq!(system_zone_in(&root.0).unwrap(), "UTC");
+        fs::write(root.0.join("etc/TZ"), "CET-1CEST,M3.1.0,M10.1.0\n").unwrap();
+        assert!(matches!(
+            system_zone_in(&root.0),
+            Err(DetectError::UnknownZone(_))
+        ));
+
+        let root = Root::new("unconfigured");
+        assert!(matches!(
+            system_zone_in(&root.0),
+            Err(DetectError::NotConfigured)
+        ));
+    }
+
+    #[test]
+    fn test_tz_value() {
+        assert_eq!(tz_value(":Asia/Kolkata").as_deref(), Some("Asia/Kolkata"));
+        assert_eq!(tz_value("'Asia/Kolkata' ").as_deref(), Some("Asia/Kolkata"));
+        assert_eq!(tz_value("\":\""), None);
+        assert_eq!(init_zone("CMASK=022\nLANG=C\n"), None);
+    }
+
+    #[test]
+    fn test_zone_from_path() {
+        let zone = |path: &str| zone_from_path(Path::new(path));
+        assert_eq!(
+            zone("/usr/share/zoneinfo/Asia/Kolkata").as_deref(),
+            Some("Asia/Kolkata")
+        );
+        assert_eq!(
+            zone("/var/db/timezone/zoneinfo/UTC").as_deref(),
+            Some("UTC")
+        );
+        assert_eq!(
+            zone("/var/db/timezone/tz/2021a.1.0/zoneinfo/America/Argentina/Salta").as_deref(),
+            Some("America/Argentina/Salta")
+        );
+        assert_eq!(
+            zone("/nix/store/p4lrfj5n-tzdata-2021a/share/zoneinfo/Europe/Berlin").as_deref(),
+            Some("Europe/Berlin")
+        );
+        assert_eq!(
+            zone("/gnu/store/0yh8gkrd-tzdata-2021a/share/zoneinfo/Asia/Tokyo").as_deref(),
+            Some("Asia/Tokyo")
+        );
+        assert_eq!(
+            zone("/usr/share/zoneinfo/right/America/New_York").as_deref(),
+            Some("America/New_York")
+        );
+        assert_eq!(
+            zone("/usr/share/zoneinfo/posix/UTC").as_deref(),
+            Some("UTC")
+        );
+        assert_eq!(zone("/usr/share/zoneinfo/posix"), None);
+        assert_eq!(zone("/usr/share/zoneinfo/"), None);
+        assert_eq!(zone("/etc/localtime.orig"), None);
+    }
+}
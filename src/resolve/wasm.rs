@@ -0,0 +1,20 @@
+// In a browser (or any other JavaScript host) there's no system configuration to read, but the
+// host knows the user's timezone: it's the one Intl.DateTimeFormat resolves to by default.
+
+use js_sys::{Array, Intl, Object, Reflect};
+
+use super::DetectError;
+
+/// Where the zone is read from
+pub const SOURCES: &[&str] = &["Intl.DateTimeFormat().resolvedOptions().timeZone"];
+
+/// The zone `Intl.DateTimeFormat().resolvedOptions().timeZone` reports
+pub fn intl_zone() -> Result<String, DetectError> {
+    let options = Intl::DateTimeFormat::new(&Array::new(), &Object::new()).resolved_options();
+
+    Reflect::get(&options, &"timeZone".into())
+        .ok()
+        .and_then(|zone| zone.as_string())
+        .filter(|zone| !zone.is_empty())
+        .ok_or(DetectError::NotConfigured)
+}
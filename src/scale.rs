@@ -0,0 +1,184 @@
+//! Express instants on the TAI and GPS time scales, which count leap seconds that UTC (and so
+//! every timezone) skips over, and as the Julian Dates astronomers use.
+//!
+//! TAI has been a whole number of seconds ahead of UTC since 1972, and GPS time has stayed 19
+//! seconds behind TAI since it started on 1980-01-06. The offsets come from the leap-second table
+//! below, which is current as of the most recent leap second (at the end of 2016); instants
+//! after any leap second announced since then would be a second or more off.
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+
+// When each leap second took effect (at midnight UTC on the 1st of the month), and TAI − UTC
+// from then on
+const LEAP_SECONDS: &[(i32, u32, i64)] = &[
+    (1972, 1, 10),
+    (1972, 7, 11),
+    (1973, 1, 12),
+    (1974, 1, 13),
+    (1975, 1, 14),
+    (1976, 1, 15),
+    (1977, 1, 16),
+    (1978, 1, 17),
+    (1979, 1, 18),
+    (1980, 1, 19),
+    (1981, 7, 20),
+    (1982, 7, 21),
+    (1983, 7, 22),
+    (1985, 7, 23),
+    (1988, 1, 24),
+    (1990, 1, 25),
+    (1991, 1, 26),
+    (1992, 7, 27),
+    (1993, 7, 28),
+    (1994, 7, 29),
+    (1996, 1, 30),
+    (1997, 7, 31),
+    (1999, 1, 32),
+    (2006, 1, 33),
+    (2009, 1, 34),
+    (2012, 7, 35),
+    (2015, 7, 36),
+    (2017, 1, 37),
+];
+
+/// TAI − GPS, fixed when GPS time started
+pub const TAI_MINUS_GPS_SECONDS: i64 = 19;
+
+const SECONDS_PER_WEEK: i64 = 7 * 24 * 60 * 60;
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+// The Modified Julian Date of the Unix epoch, and how far the Julian Date is ahead of it
+const MJD_OF_UNIX_EPOCH: i64 = 40_587;
+const JD_MINUS_MJD: f64 = 2_400_000.5;
+
+/// How many seconds TAI is ahead of UTC at `at`, or `None` before 1972, when the difference
+/// wasn't a whole number of seconds
+pub fn tai_minus_utc(at: DateTime<Utc>) -> Option<i64> {
+    LEAP_SECONDS
+        .iter()
+        .rev()
+        .find(|&&(year, month, _)| {
+            at.naive_utc() >= NaiveDate::from_ymd(year, month, 1).and_hms(0, 0, 0)
+        })
+        .map(|&(_, _, offset)| offset)
+}
+
+/// `at` as a date and time on the TAI scale
+pub fn tai(at: DateTime<Utc>) -> Option<NaiveDateTime> {
+    Some(at.naive_utc() + Duration::seconds(tai_minus_utc(at)?))
+}
+
+/// A date and time on the GPS scale, along with the GPS week and seconds into it
+#[derive(Debug, PartialEq, Eq)]
+pub struct GpsTime {
+    pub datetime: NaiveDateTime,
+    /// Seconds since the GPS epoch (1980-01-06 00:00:00 UTC)
+    pub seconds: i64,
+    /// Weeks since the GPS epoch, without the rollover that receivers broadcast
+    pub week: i64,
+    pub seconds_of_week: i64,
+}
+
+/// `at` on the GPS scale, or `None` before GPS time started
+pub fn gps(at: DateTime<Utc>) -> Option<GpsTime> {
+    let datetime = tai(at)? - Duration::seconds(TAI_MINUS_GPS_SECONDS);
+    let seconds = (datetime - gps_epoch()).num_seconds();
+    if seconds < 0 {
+        return None;
+    }
+    Some(GpsTime {
+        datetime,
+        seconds,
+        week: seconds / SECONDS_PER_WEEK,
+        seconds_of_week: seconds % SECONDS_PER_WEEK,
+    })
+}
+
+/// The Julian Date of `at`: days (and the fraction of one) since noon UTC on 4713-01-01 BC in the
+/// proleptic Julian calendar
+pub fn julian_date(at: DateTime<Utc>) -> f64 {
+    modified_julian_date(at) + JD_MINUS_MJD
+}
+
+/// The Modified Julian Date of `at`: days since midnight UTC on 1858-11-17. Days are counted
+/// whole before adding the time of day, so the fraction keeps its precision.
+pub fn modified_julian_date(at: DateTime<Utc>) -> f64 {
+    let days = at.timestamp().div_euclid(SECONDS_PER_DAY) + MJD_OF_UNIX_EPOCH;
+    let seconds = at.timestamp().rem_euclid(SECONDS_PER_DAY) as f64
+        + f64::from(at.timestamp_subsec_nanos()) / 1e9;
+    days as f64 + seconds / SECONDS_PER_DAY as f64
+}
+
+fn gps_epoch() -> NaiveDateTime {
+    NaiveDate::from_ymd(1980, 1, 6).and_hms(0, 0, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_tai() {
+        let at = Utc.ymd(2021, 3, 10).and_hms(9, 30, 0);
+        assert_eq!(tai_minus_utc(at), Some(37));
+        assert_eq!(
+            tai(at),
+            Some(NaiveDate::from_ymd(2021, 3, 10).and_hms(9, 30, 37))
+        );
+
+        // Either side of the leap second at the end of 2016
+        assert_eq!(
+            tai_minus_utc(Utc.ymd(2016, 12, 31).and_hms(23, 59, 59)),
+            Some(36)
+        );
+        assert_eq!(
+            tai_minus_utc(Utc.ymd(2017, 1, 1).and_hms(0, 0, 0)),
+            Some(37)
+        );
+
+        assert_eq!(tai_minus_utc(Utc.ymd(1971, 12, 31).and_hms(0, 0, 0)), None);
+    }
+
+    #[test]
+    fn test_gps() {
+        assert_eq!(
+            gps(Utc.ymd(2021, 3, 10).and_hms(9, 30, 0)),
+            Some(GpsTime {
+                datetime: NaiveDate::from_ymd(2021, 3, 10).and_hms(9, 30, 18),
+                seconds: 1_299_403_818,
+                week: 2148,
+                seconds_of_week: 293_418,
+            })
+        );
+        assert_eq!(
+            gps(Utc.ymd(1980, 1, 6).and_hms(0, 0, 0)).unwrap().seconds,
+            0
+        );
+        assert_eq!(gps(Utc.ymd(1980, 1, 5).and_hms(0, 0, 0)), None);
+    }
+
+    #[test]
+    fn test_julian_date() {
+        let j2000 = Utc.ymd(2000, 1, 1).and_hms(12, 0, 0);
+        assert_eq!(julian_date(j2000), 2_451_545.0);
+        assert_eq!(modified_julian_date(j2000), 51_544.5);
+
+        assert_eq!(
+            modified_julian_date(Utc.ymd(1858, 11, 17).and_hms(0, 0, 0)),
+            0.0
+        );
+        assert_eq!(
+            format!("{:.6}", julian_date(Utc.ymd(2021, 3, 10).and_hms(9, 30, 0))),
+            "2459283.895833"
+        );
+        assert_eq!(
+            format!(
+                "{:.6}",
+                modified_julian_date(Utc.ymd(1969, 12, 31).and_hms(18, 0, 0))
+            ),
+            "40586.750000"
+        );
+    }
+}
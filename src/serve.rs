@@ -0,0 +1,410 @@
+// `tz serve`: a small JSON API over HTTP, so dashboards and chatbots can use the same zone
+// resolution and date/time parsing as the command line without starting a process per request.
+//
+// GET /convert?to=<tz>[&from=<tz>][&at=<datetime>] ← Convert `at` (or now) from `from` to `to`
+// GET /zones[?q=<text>] ← Zones whose names contain `q` (or all of them)
+//
+// Aliases and groups from the config work in `from` and `to`, the same as --from and --to.
+// Conversions take the same shape as `tz --json` prints, which schema/conversion.schema.json
+// describes.
+//
+// Connections are answered by a fixed number of workers, and each has a time limit and a limit on
+// the size of its request, so clients that are slow or send too much can't use up threads or
+// memory.
+
+use chrono::{DateTime, SubsecRound, Utc};
+use chrono_tz::{OffsetName, Tz, TZ_VARIANTS};
+use serde_json::{json, Value};
+use tz::parse::try_parse_datetime_with;
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::Config;
+
+/// JSON Schema for conversions as `conversion` writes them (and for errors alongside them)
+pub const SCHEMA: &str = include_str!("../schema/conversion.schema.json");
+
+const WORKERS: usize = 16;
+// Connections waiting for a worker, beyond which they're turned away
+const QUEUED_CONNECTIONS: usize = 64;
+// For reading the request and writing the response, each
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_REQUEST_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 16 * 1024;
+
+pub struct Api {
+    config: Config,
+    /// Zone to convert from when a request doesn't give one
+    from_tz: Tz,
+}
+
+impl Api {
+    pub fn new(config: Config, from_tz: Tz) -> Api {
+        Api { config, from_tz }
+    }
+
+    /// The status line and JSON body answering a request for `target` (a path and query)
+    fn respond(&self, method: &str, target: &str, now: DateTime<Utc>) -> (&'static str, Value) {
+        if method != "GET" {
+            return error("405 Method Not Allowed", "Only GET requests are supported");
+        }
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let params = query
+            .split('&')
+            .filter_map(|param| param.split_once('=').or(Some((param, ""))))
+            .filter(|(name, _)| !name.is_empty())
+            .map(|(name, value)| (decode(name), decode(value)))
+            .collect::<Vec<_>>();
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(param, _)| param == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        match path {
+            "/convert" => self.convert(param("from"), param("to"), param("at"), now),
+            "/zones" => self.zones(param("q").unwrap_or("")),
+            _ => error("404 Not Found", &format!("No such endpoint {:?}", path)),
+        }
+    }
+
+    /// Convert `at` (or `now`) from `from` to `to`, each given as on the command line; the
+    /// JSON body is either the conversion or an `error`
+    pub fn convert(
+        &self,
+        from: Option<&str>,
+        to: Option<&str>,
+        at: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> (&'static str, Value) {
+        let from_tz = match from {
+            Some(from) => match self.config.resolve_zones(from).as_deref() {
+                Some([tz]) => *tz,
+                Some(_) => return error("400 Bad Request", "from must be a single timezone"),
+                None => return error("400 Bad Request", &unknown_zone(from)),
+            },
+            None => self.from_tz,
+        };
+        let targets = match to.map(|to| (to, self.config.resolve_zones(to))) {
+            Some((_, Some(targets))) => targets,
+            Some((to, None)) => return error("400 Bad Request", &unknown_zone(to)),
+            None => return error("400 Bad Request", "to is required"),
+        };
+
+        let datetime = match at {
+            Some(at) => match try_parse_datetime_with(from_tz, at, &self.config.business_days()) {
+                Ok(datetime) => datetime,
+                Err(e) => return error("400 Bad Request", &format!("Invalid at: {}", e)),
+            },
+            None => now.with_timezone(&from_tz),
+        };
+
+        ("200 OK", conversion(datetime, &targets))
+    }
+
+    fn zones(&self, query: &str) -> (&'static str, Value) {
+        let needle = query.trim().to_lowercase().replace(' ', "_");
+        let mut zones = TZ_VARIANTS
+            .iter()
+            .map(|tz| tz.name())
+            .filter(|name| name.to_lowercase().contains(&needle))
+            .collect::<Vec<_>>();
+
+        // Whatever the query resolves to (an abbreviation, alias, or loose city name) comes first
+        let resolved = self.config.resolve_zones(query).unwrap_or_default();
+        for tz in resolved.iter().rev() {
+            zones.retain(|name| *name != tz.name());
+            zones.insert(0, tz.name());
+        }
+
+        ("200 OK", json!({ "zones": zones }))
+    }
+}
+
+/// Serve `api` on `address` (like `127.0.0.1:8080`) until the process is stopped
+pub fn run(address: &str, api: Api) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    eprintln!("-> Listening on http://{}", listener.local_addr()?);
+
+    let api = Arc::new(api);
+    let (sender, receiver) = mpsc::sync_channel::<TcpStream>(QUEUED_CONNECTIONS);
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..WORKERS {
+        let api = Arc::clone(&api);
+        let receiver = Arc::clone(&receiver);
+        thread::spawn(move || loop {
+            // Only locked while waiting for the next connection, not while answering it
+            let stream = receiver.lock().expect("A worker panicked").recv();
+            match stream {
+                // A client that goes away mid-request only affects its own connection
+                Ok(stream) => drop(handle(stream, &api)),
+                Err(_) => return,
+            }
+        });
+    }
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let timeouts = stream
+            .set_read_timeout(Some(TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(TIMEOUT)));
+        if timeouts.is_err() {
+            continue;
+        }
+
+        if let Err(TrySendError::Full(stream)) = sender.try_send(stream) {
+            let (status, body) = error(
+                "503 Service Unavailable",
+                "Too many connections, try again shortly",
+            );
+            let _ = write_response(&stream, status, &body);
+        }
+    }
+    Ok(())
+}
+
+fn handle(stream: TcpStream, api: &Api) -> io::Result<()> {
+    let (status, body) = match read_request(&mut BufReader::new(&stream))? {
+        Ok(request_line) => {
+            let mut parts = request_line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some(method), Some(target)) => {
+                    api.respond(method, target, Utc::now().trunc_subsecs(0))
+                }
+                _ => error("400 Bad Request", "Malformed request"),
+            }
+        }
+        Err(response) => response,
+    };
+    write_response(&stream, status, &body)
+}
+
+// The request line, or the response to a request too big to read
+fn read_request(reader: &mut impl BufRead) -> io::Result<Result<String, (&'static str, Value)>> {
+    let request_line = match read_line(reader, MAX_REQUEST_LINE)? {
+        Some(line) => line,
+        None => {
+            return Ok(Err(error(
+                "414 URI Too Long",
+                "The request line is too long",
+            )))
+        }
+    };
+
+    // Nothing in the headers (or a body) changes the response
+    let mut remaining = MAX_HEADERS;
+    loop {
+        match read_line(reader, remaining)? {
+            Some(header) if header.trim().is_empty() => break,
+            Some(header) => remaining -= header.len(),
+            None => {
+                let message = "The request headers are too long";
+                return Ok(Err(error("431 Request Header Fields Too Large", message)));
+            }
+        }
+    }
+
+    Ok(Ok(request_line))
+}
+
+// A line of at most `limit` bytes, or `None` if it's longer
+fn read_line(reader: &mut impl BufRead, limit: usize) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    reader.take(limit as u64).read_line(&mut line)?;
+    if line.len() == limit && !line.ends_with('\n') {
+        return Ok(None);
+    }
+    Ok(Some(line))
+}
+
+fn write_response(mut stream: impl Write, status: &str, body: &Value) -> io::Result<()> {
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// `datetime` and the same instant in each of `targets`
+pub fn conversion(datetime: DateTime<Tz>, targets: &[Tz]) -> Value {
+    let targets = targets
+        .iter()
+        .map(|tz| moment(datetime.with_timezone(tz)))
+        .collect::<Vec<_>>();
+    json!({ "from": moment(datetime), "to": targets })
+}
+
+fn moment(datetime: DateTime<Tz>) -> Value {
+    json!({
+        "zone": datetime.timezone().name(),
+        "datetime": datetime.to_rfc3339(),
+        "abbreviation": datetime.offset().abbreviation(),
+    })
+}
+
+fn error(status: &'static str, message: &str) -> (&'static str, Value) {
+    (status, json!({ "error": message }))
+}
+
+fn unknown_zone(name: &str) -> String {
+    format!("Unknown timezone {:?}", name)
+}
+
+// Undoes percent-encoding in a query string, where `+` also stands for a space
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_respond() {
+        let api = Api::new(Config::default(), Tz::America__New_York);
+        let now = Utc.ymd(2021, 3, 10).and_hms(14, 30, 0);
+        let respond = |target: &str| api.respond("GET", target, now);
+
+        assert_eq!(
+            respond("/convert?to=Asia%2FKolkata&at=2021-03-10+09:30"),
+            (
+                "200 OK",
+                json!({
+                    "from": {
+                        "zone": "America/New_York",
+                        "datetime": "2021-03-10T09:30:00-05:00",
+                        "abbreviation": "EST",
+                    },
+                    "to": [{
+                        "zone": "Asia/Kolkata",
+                        "datetime": "2021-03-10T20:00:00+05:30",
+                        "abbreviation": "IST",
+                    }],
+                })
+            )
+        );
+        assert_eq!(
+            respond("/convert?from=utc&to=tokyo").1["to"][0]["datetime"],
+            "2021-03-10T23:30:00+09:00"
+        );
+
+        assert_eq!(respond("/convert?at=09:30").0, "400 Bad Request");
+        assert_eq!(
+            respond("/convert?to=nowhere").1["error"],
+            "Unknown timezone \"nowhere\""
+        );
+        assert_eq!(respond("/convert?to=utc&at=noonish").0, "400 Bad Request");
+        assert_eq!(respond("/nothing").0, "404 Not Found");
+        assert_eq!(
+            api.respond("POST", "/convert", now).0,
+            "405 Method Not Allowed"
+        );
+
+        let zones = respond("/zones?q=kolk").1;
+        assert_eq!(zones, json!({ "zones": ["Asia/Kolkata"] }));
+        let zones = respond("/zones?q=new+york").1;
+        assert_eq!(zones["zones"][0], "America/New_York");
+    }
+
+    #[test]
+    fn test_read_request() {
+        let read = |request: String| read_request(&mut io::Cursor::new(request)).unwrap();
+
+        assert_eq!(
+            read("GET /zones HTTP/1.1\r\nHost: localhost\r\n\r\n".to_owned()),
+            Ok("GET /zones HTTP/1.1\r\n".to_owned())
+        );
+        assert_eq!(
+            read("GET /zones HTTP/1.1\r\n".to_owned()),
+            Ok("GET /zones HTTP/1.1\r\n".to_owned())
+        );
+
+        let long_target = format!(
+            "GET /zones?q={} HTTP/1.1\r\n\r\n",
+            "a".repeat(MAX_REQUEST_LINE)
+        );
+        assert_eq!(read(long_target).unwrap_err().0, "414 URI Too Long");
+
+        let many_headers = format!(
+            "GET /zones HTTP/1.1\r\n{}\r\n",
+            "X-Padding: aaaaaaaaaaaaaaaa\r\n".repeat(MAX_HEADERS / 16)
+        );
+        assert_eq!(
+            read(many_headers).unwrap_err().0,
+            "431 Request Header Fields Too Large"
+        );
+    }
+
+    #[test]
+    fn test_schema() {
+        let schema = serde_json::from_str::<Value>(SCHEMA).unwrap();
+        let keys = |value: &Value| {
+            let mut keys = value
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>();
+            keys.sort();
+            keys
+        };
+
+        let datetime = Tz::Asia__Dubai.ymd(2021, 3, 10).and_hms(9, 30, 0);
+        let converted = conversion(datetime, &[Tz::UTC]);
+        assert_eq!(
+            keys(&converted),
+            keys(&schema["oneOf"][0]["properties"])
+                .into_iter()
+                .filter(|key| key != "id")
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            keys(&converted["from"]),
+            keys(&schema["$defs"]["moment"]["properties"])
+        );
+        assert_eq!(converted["from"]["abbreviation"], "+04");
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("Asia%2FKolkata"), "Asia/Kolkata");
+        assert_eq!(decode("2021-03-10+09%3A30"), "2021-03-10 09:30");
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode("caf%C3%A9"), "café");
+    }
+}
@@ -0,0 +1,165 @@
+// `tz config export` / `tz config import`: move the parts of a config that make sense on any
+// machine (favorites, the format, aliases, groups, and labels) between configs as one TOML
+// document, so a team can share a setup or one person can keep several machines in sync.
+
+use serde::{Deserialize, Serialize};
+use toml::value::{Table, Value};
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::config::{Alias, Config, Group};
+
+/// The shareable part of a config
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Shared {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub favorites: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(
+        skip_serializing_if = "BTreeMap::is_empty",
+        serialize_with = "toml::ser::tables_last"
+    )]
+    pub aliases: BTreeMap<String, Alias>,
+    #[serde(
+        skip_serializing_if = "BTreeMap::is_empty",
+        serialize_with = "toml::ser::tables_last"
+    )]
+    pub groups: BTreeMap<String, Group>,
+    #[serde(rename = "label", skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Shared {
+    pub fn from_config(config: &Config) -> Shared {
+        Shared {
+            favorites: config.favorites.clone(),
+            format: config.format.clone(),
+            aliases: config.aliases.clone().into_iter().collect(),
+            groups: config.groups.clone().into_iter().collect(),
+            labels: config.labels.clone().into_iter().collect(),
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        let contents = toml::to_string(self).expect("Couldn't serialize the config");
+        format!(
+            "# Exported from tz {}; load with `tz config import <this file>`\n{}",
+            env!("CARGO_PKG_VERSION"),
+            contents
+        )
+    }
+}
+
+/// Read a document to import from a file or (with the `import-url` feature) an http(s) URL
+pub fn fetch(source: &str) -> Result<String, String> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        download(source)
+    } else {
+        fs::read_to_string(source).map_err(|e| format!("Couldn't read {}: {}", source, e))
+    }
+}
+
+#[cfg(feature = "import-url")]
+fn download(url: &str) -> Result<String, String> {
+    ureq::get(url)
+        .call()
+        .map_err(|e| format!("Couldn't download {}: {}", url, e))?
+        .into_string()
+        .map_err(|e| format!("Couldn't download {}: {}", url, e))
+}
+
+#[cfg(not(feature = "import-url"))]
+fn download(url: &str) -> Result<String, String> {
+    Err(format!(
+        "Couldn't download {}: URLs aren't supported in this build (it needs the import-url feature)",
+        url
+    ))
+}
+
+/// Merge the shareable settings in `document` into the config in `existing` (if there is
+/// one). Imported aliases, groups, and labels are added to (or replace) the existing ones;
+/// imported favorites and the format replace the existing ones, if the document sets them.
+/// Everything else in the existing config is kept, though its comments and layout aren't.
+pub fn import(existing: Option<&str>, document: &str) -> Result<String, String> {
+    let invalid = |e: toml::de::Error| format!("Invalid config to import: {}", e);
+    let shared = toml::from_str::<Shared>(document).map_err(invalid)?;
+
+    let mut merged = match existing {
+        Some(existing) => {
+            toml::from_str::<Table>(existing).map_err(|e| format!("Invalid config: {}", e))?
+        }
+        None => Table::new(),
+    };
+
+    let imported = Value::try_from(&shared).map_err(|e| e.to_string())?;
+    let imported = match imported {
+        Value::Table(imported) => imported,
+        _ => unreachable!("Shared always serializes to a table"),
+    };
+
+    for (key, value) in imported {
+        match (merged.get_mut(&key), value) {
+            (Some(Value::Table(existing)), Value::Table(value)) => existing.extend(value),
+            (_, value) => {
+                merged.insert(key, value);
+            }
+        }
+    }
+
+    let merged = toml::to_string(&Value::Table(merged)).map_err(|e| e.to_string())?;
+    Config::from_toml(&merged).map_err(invalid)?;
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_import() {
+        let config = Config::from_toml(
+            r#"
+            favorites = ["Europe/London"]
+            clock = "12h"
+
+            [aliases]
+            office = "America/Los_Angeles"
+            client = { zone = "Asia/Tokyo", format = "%H:%M" }
+
+            [groups]
+            team = ["office", "client"]
+            "#,
+        )
+        .unwrap();
+
+        let exported = Shared::from_config(&config).to_toml();
+        assert!(exported.starts_with("# Exported from tz"));
+        assert!(!exported.contains("clock"));
+
+        let existing = r#"
+            favorites = ["Asia/Kolkata"]
+            locale = "fr_FR"
+
+            [aliases]
+            home = "Asia/Kolkata"
+            office = "Europe/Berlin"
+            "#;
+
+        let merged = Config::from_toml(&import(Some(existing), &exported).unwrap()).unwrap();
+        assert_eq!(merged.favorites, vec!["Europe/London"]);
+        assert_eq!(merged.locale.as_deref(), Some("fr_FR"));
+        assert_eq!(merged.aliases["home"].zone(), "Asia/Kolkata");
+        assert_eq!(merged.aliases["office"].zone(), "America/Los_Angeles");
+        assert_eq!(merged.style("client"), config.style("client"));
+        assert_eq!(merged.groups["team"], config.groups["team"]);
+
+        let merged = import(None, "[aliases]\nhq = \"Asia/Tokyo\"\n").unwrap();
+        assert_eq!(merged, "[aliases]\nhq = \"Asia/Tokyo\"\n");
+
+        assert!(import(None, "favorites = \"Asia/Tokyo\"").is_err());
+        assert!(import(Some("favorites = ["), "").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Solar time at a place: when the sun says it is, rather than the timezone.
+//!
+//! Mean solar time moves 4 minutes for every degree of longitude from Greenwich. Apparent solar
+//! time follows the sun itself, which runs up to about 16 minutes ahead of or behind that over a
+//! year (the equation of time, approximated here as NOAA does, to within a minute or so).
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Timelike, Utc};
+
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+/// A place on Earth, in degrees north and east
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Coordinates that aren't `latitude,longitude` in range
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CoordinatesError {
+    pub input: String,
+}
+
+impl fmt::Display for CoordinatesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid coordinates {:?} (expected latitude,longitude in degrees, like 12.97,77.59)",
+            self.input
+        )
+    }
+}
+
+impl Error for CoordinatesError {}
+
+impl FromStr for Coordinates {
+    type Err = CoordinatesError;
+
+    /// Parse `latitude,longitude` in decimal degrees, with south and west negative
+    fn from_str(input: &str) -> Result<Coordinates, CoordinatesError> {
+        let error = || CoordinatesError {
+            input: input.to_owned(),
+        };
+        let (latitude, longitude) = input.split_once(',').ok_or_else(error)?;
+        let latitude = latitude.trim().parse::<f64>().map_err(|_| error())?;
+        let longitude = longitude.trim().parse::<f64>().map_err(|_| error())?;
+
+        if (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude) {
+            Ok(Coordinates {
+                latitude,
+                longitude,
+            })
+        } else {
+            Err(error())
+        }
+    }
+}
+
+impl fmt::Display for Coordinates {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let north_south = if self.latitude < 0.0 { 'S' } else { 'N' };
+        let east_west = if self.longitude < 0.0 { 'W' } else { 'E' };
+        write!(
+            f,
+            "{:.2}°{}, {:.2}°{}",
+            self.latitude.abs(),
+            north_south,
+            self.longitude.abs(),
+            east_west
+        )
+    }
+}
+
+/// How far apparent solar time is ahead of mean solar time at `at`
+pub fn equation_of_time(at: DateTime<Utc>) -> Duration {
+    // The fraction of the year gone, in radians
+    let hours = f64::from(at.hour()) + f64::from(at.minute()) / 60.0;
+    let days_in_year = if NaiveDate::from_ymd_opt(at.year(), 2, 29).is_some() {
+        366.0
+    } else {
+        365.0
+    };
+    let year = 2.0 * PI / days_in_year * (f64::from(at.ordinal0()) + (hours - 12.0) / 24.0);
+
+    let minutes = 229.18
+        * (0.000075 + 0.001868 * year.cos()
+            - 0.032077 * year.sin()
+            - 0.014615 * (2.0 * year).cos()
+            - 0.040849 * (2.0 * year).sin());
+    Duration::milliseconds((minutes * 60_000.0).round() as i64)
+}
+
+/// Local mean solar time at `longitude` at the instant `at`
+pub fn mean_solar_time(at: DateTime<Utc>, longitude: f64) -> NaiveDateTime {
+    at.naive_utc() + longitude_offset(longitude)
+}
+
+/// Local apparent solar time (a sundial's) at `longitude` at the instant `at`
+pub fn apparent_solar_time(at: DateTime<Utc>, longitude: f64) -> NaiveDateTime {
+    mean_solar_time(at, longitude) + equation_of_time(at)
+}
+
+/// When the sun crosses the meridian at `longitude` on `date` (in mean solar time there)
+pub fn solar_noon(date: NaiveDate, longitude: f64) -> DateTime<Utc> {
+    let mean_noon =
+        DateTime::<Utc>::from_utc(date.and_hms(12, 0, 0), Utc) - longitude_offset(longitude);
+    mean_noon - equation_of_time(mean_noon)
+}
+
+fn longitude_offset(longitude: f64) -> Duration {
+    // 360° in 24 hours is 240 seconds a degree
+    Duration::milliseconds((longitude * 240_000.0).round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_coordinates() {
+        let bengaluru = "12.97, 77.59".parse::<Coordinates>().unwrap();
+        assert_eq!(
+            bengaluru,
+            Coordinates {
+                latitude: 12.97,
+                longitude: 77.59
+            }
+        );
+        assert_eq!(bengaluru.to_string(), "12.97°N, 77.59°E");
+        assert_eq!(
+            "-33.87,-70.65".parse::<Coordinates>().unwrap().to_string(),
+            "33.87°S, 70.65°W"
+        );
+
+        assert!("12.97".parse::<Coordinates>().is_err());
+        assert!("91,0".parse::<Coordinates>().is_err());
+        assert!("0,east".parse::<Coordinates>().is_err());
+    }
+
+    #[test]
+    fn test_solar_time() {
+        // Early November has the sun furthest ahead, by about 16 and a half minutes
+        let eot = equation_of_time(Utc.ymd(2021, 11, 3).and_hms(12, 0, 0));
+        assert!((eot.num_seconds() - 16 * 60 - 25).abs() < 30);
+        // and mid-February furthest behind, by about 14 minutes
+        let eot = equation_of_time(Utc.ymd(2021, 2, 11).and_hms(12, 0, 0));
+        assert!((eot.num_seconds() + 14 * 60 + 15).abs() < 30);
+
+        let at = Utc.ymd(2021, 3, 10).and_hms(9, 30, 0);
+        assert_eq!(
+            mean_solar_time(at, 77.59).to_string(),
+            "2021-03-10 14:40:21.600"
+        );
+        assert_eq!(
+            mean_solar_time(at, -90.0).to_string(),
+            "2021-03-10 03:30:00"
+        );
+        let apparent = apparent_solar_time(at, 77.59) - mean_solar_time(at, 77.59);
+        assert_eq!(apparent, equation_of_time(at));
+
+        // At Greenwich, the sun is highest around 12:10 in March
+        let noon = solar_noon(NaiveDate::from_ymd(2021, 3, 10), 0.0);
+        assert_eq!(noon.format("%H:%M").to_string(), "12:10");
+    }
+}
@@ -0,0 +1,141 @@
+// `tz sort`: put lines read from stdin in the order they happened, by the first timestamp on each,
+// for merging logs from servers that stamp in different zones and formats.
+//
+// Lines without a timestamp (a stack trace, a wrapped message) stay with the stamped line above
+// them, and lines stamped with the same instant keep the order they came in. Stamps without an
+// offset are taken to be in --from.
+
+use chrono::{DateTime, Utc};
+
+use std::io::{self, BufRead, Write};
+
+use tz::convert::TimestampRewriter;
+
+// Fixed width, so prefixed lines line up
+const UTC_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+
+/// A stamped line and the unstamped lines after it
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    instant: Option<DateTime<Utc>>,
+    lines: Vec<String>,
+}
+
+/// Sort stdin onto stdout, prefixing each entry with its instant in UTC if `prefix_utc`
+pub fn run(rewriter: &TimestampRewriter, prefix_utc: bool) -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+
+    let lines = stdin
+        .lock()
+        .split(b'\n')
+        .map(|line| line.map(|line| String::from_utf8_lossy(&line).into_owned()))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    for entry in sort(rewriter, lines) {
+        write_entry(&mut out, &entry, prefix_utc)?;
+    }
+    out.flush()
+}
+
+fn sort(rewriter: &TimestampRewriter, lines: Vec<String>) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+
+    for line in lines {
+        let line = line.trim_end_matches('\r').to_owned();
+        match rewriter.first_instant(&line) {
+            Some(instant) => entries.push(Entry {
+                instant: Some(instant),
+                lines: vec![line],
+            }),
+            None => match entries.last_mut() {
+                Some(entry) => entry.lines.push(line),
+                // Anything before the first stamp (e.g. a header) stays at the top
+                None => entries.push(Entry {
+                    instant: None,
+                    lines: vec![line],
+                }),
+            },
+        }
+    }
+
+    // Stable, so equal instants keep their order
+    entries.sort_by_key(|entry| entry.instant);
+    entries
+}
+
+fn write_entry(out: &mut impl Write, entry: &Entry, prefix_utc: bool) -> io::Result<()> {
+    for (i, line) in entry.lines.iter().enumerate() {
+        if prefix_utc {
+            let prefix = match entry.instant {
+                Some(instant) if i == 0 => instant.format(UTC_FORMAT).to_string(),
+                _ => " ".repeat(24),
+            };
+            write!(out, "{} ", prefix)?;
+        }
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono_tz::Tz;
+
+    fn sorted(lines: &[&str], prefix_utc: bool) -> String {
+        let rewriter = TimestampRewriter::new(Tz::America__New_York, Tz::UTC);
+        let lines = lines.iter().map(|line| line.to_string()).collect();
+        let mut out = Vec::new();
+        for entry in sort(&rewriter, lines) {
+            write_entry(&mut out, &entry, prefix_utc).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_sort() {
+        let lines = [
+            "== merged logs ==",
+            "2021-03-10T15:00:02+01:00 eu: retrying",
+            "2021-03-10 09:00:01 us: request failed",
+            "    at handler (app.js:10)",
+            r#"1.2.3.4 - - [10/Mar/2021:14:00:01 +0000] "GET /health" 200"#,
+            "2021-03-10T14:00:01Z eu: same instant, later line",
+        ];
+
+        assert_eq!(
+            sorted(&lines, false),
+            [
+                "== merged logs ==",
+                "2021-03-10 09:00:01 us: request failed",
+                "    at handler (app.js:10)",
+                r#"1.2.3.4 - - [10/Mar/2021:14:00:01 +0000] "GET /health" 200"#,
+                "2021-03-10T14:00:01Z eu: same instant, later line",
+                "2021-03-10T15:00:02+01:00 eu: retrying",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_prefix_utc() {
+        let lines = [
+            "2021-03-10 09:00:01.5 us: request failed",
+            "    at handler (app.js:10)",
+        ];
+
+        assert_eq!(
+            sorted(&lines, true),
+            format!(
+                "2021-03-10T14:00:01.500Z {}\n{}{}\n",
+                lines[0],
+                " ".repeat(25),
+                lines[1]
+            )
+        );
+    }
+}
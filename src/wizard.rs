@@ -0,0 +1,173 @@
+// First-run setup: when there's no config file and tz is being used interactively, ask a few
+// questions and write a config from the answers. Declining still writes a (commented-out)
+// config, so the question is only ever asked once.
+
+use chrono_tz::Tz;
+use tz::resolve::{current_tz, parse_tz};
+
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+
+use crate::config::{BusinessHours, Config, ConfigError};
+
+const DECLINED: &str = "\
+# tz config. Every key is optional, e.g.:
+#
+# favorites = [\"America/New_York\", \"Europe/London\"]
+# clock = \"12h\"
+";
+
+/// Whether the wizard can ask anything: both the questions and the answers need a terminal
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+/// Run the wizard and write its answers to `path`, returning the resulting config
+pub fn run(path: &Path) -> Result<Config, ConfigError> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let write_error = |e| ConfigError::Write(path.to_owned(), e);
+
+    let contents = match ask_all(&mut input, path).map_err(write_error)? {
+        Some(contents) => contents,
+        None => DECLINED.to_owned(),
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(write_error)?;
+    }
+    fs::write(path, &contents).map_err(write_error)?;
+    eprintln!("-> Wrote {}\n", path.display());
+
+    Config::from_toml(&contents).map_err(|e| ConfigError::Parse(path.to_owned(), e))
+}
+
+// Returns the config to write, or `None` if setup was declined
+fn ask_all(input: &mut impl BufRead, path: &Path) -> io::Result<Option<String>> {
+    eprintln!("No config found at {}.", path.display());
+    let setup = ask(input, "Set one up now? [Y/n]", |answer| match answer {
+        "" | "y" | "Y" | "yes" => Some(true),
+        "n" | "N" | "no" => Some(false),
+        _ => None,
+    })?;
+
+    if !setup {
+        return Ok(None);
+    }
+
+    let detected = current_tz().ok();
+    let home = match detected {
+        Some(tz) => ask(input, &format!("Your timezone [{}]", tz), |answer| {
+            if answer.is_empty() {
+                Some(tz)
+            } else {
+                parse_tz(answer)
+            }
+        })?,
+        None => ask(input, "Your timezone (e.g. Europe/London)", |answer| {
+            Some(answer)
+                .filter(|answer| !answer.is_empty())
+                .and_then(parse_tz)
+        })?,
+    };
+
+    let others = ask(
+        input,
+        "Other timezones to show by default, comma-separated (blank for none)",
+        |answer| {
+            answer
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(parse_tz)
+                .collect::<Option<Vec<_>>>()
+        },
+    )?;
+
+    let clock = ask(input, "12h or 24h clock? [24h]", |answer| match answer {
+        "" | "24" | "24h" => Some("24h"),
+        "12" | "12h" => Some("12h"),
+        _ => None,
+    })?;
+
+    let hours = ask(input, "Business hours [09:00-17:00]", |answer| {
+        if answer.is_empty() {
+            "09:00-17:00".parse::<BusinessHours>().ok()
+        } else {
+            answer.parse().ok()
+        }
+    })?;
+
+    Ok(Some(render(home, &others, clock, hours)))
+}
+
+// Prompt until `parse` accepts the (trimmed) answer
+fn ask<T>(
+    input: &mut impl BufRead,
+    question: &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> io::Result<T> {
+    loop {
+        eprint!("{}: ", question);
+        io::stderr().flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        match parse(answer.trim()) {
+            Some(value) => return Ok(value),
+            None => eprintln!("Didn't understand {:?}, try again", answer.trim()),
+        }
+    }
+}
+
+fn render(home: Tz, others: &[Tz], clock: &str, hours: BusinessHours) -> String {
+    let mut favorites = vec![home];
+    favorites.extend(others.iter().filter(|&&tz| tz != home));
+
+    let favorites = favorites
+        .iter()
+        .map(|tz| format!("{:?}", tz.name()))
+        .collect::<Vec<_>>();
+
+    format!(
+        "favorites = [{}]\nclock = {:?}\nbusiness_hours = \"{}\"\n",
+        favorites.join(", "),
+        clock,
+        hours
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ask_all() {
+        let path = Path::new("config.toml");
+
+        let mut answers = "n\n".as_bytes();
+        assert_eq!(ask_all(&mut answers, path).unwrap(), None);
+        assert!(Config::from_toml(DECLINED).is_ok());
+
+        let mut answers = "y\nkolkata\ntokyo, Nowhere\ntokyo, london\n12h\n\n".as_bytes();
+        let contents = ask_all(&mut answers, path).unwrap().unwrap();
+        assert_eq!(
+            contents,
+            "favorites = [\"Asia/Kolkata\", \"Asia/Tokyo\", \"Europe/London\"]\n\
+             clock = \"12h\"\nbusiness_hours = \"09:00-17:00\"\n"
+        );
+
+        let config = Config::from_toml(&contents).unwrap();
+        assert_eq!(
+            config.favorite_zones(),
+            vec![Tz::Asia__Kolkata, Tz::Asia__Tokyo, Tz::Europe__London]
+        );
+
+        let mut answers = "y\n".as_bytes();
+        assert!(ask_all(&mut answers, path).is_err());
+    }
+}
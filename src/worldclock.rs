@@ -0,0 +1,53 @@
+// `tz <tz_id> <datetime> --url`: a timeanddate.com link for the converted instant, which shows it
+// in the zone of whoever opens it (and lists it in cities around the world).
+
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+
+use crate::gcal::encode;
+
+const FIXED_TIME_URL: &str = "https://www.timeanddate.com/worldclock/fixedtime.html";
+
+// timeanddate.com identifies zones by its own location IDs; this one is UTC
+const UTC_LOCATION: u32 = 1440;
+
+/// A link showing the instant `start`, titled `summary` and lasting `duration` if given
+pub fn link(start: DateTime<Tz>, duration: Option<Duration>, summary: Option<&str>) -> String {
+    let mut link = String::from(FIXED_TIME_URL);
+    link.push('?');
+    if let Some(summary) = summary {
+        link.push_str(&format!("msg={}&", encode(summary)));
+    }
+    link.push_str(&format!(
+        "iso={}&p1={}",
+        start.with_timezone(&Utc).format("%Y%m%dT%H%M%S"),
+        UTC_LOCATION
+    ));
+    if let Some(duration) = duration.filter(|duration| *duration > Duration::zero()) {
+        let minutes = duration.num_minutes();
+        link.push_str(&format!("&ah={}&am={}", minutes / 60, minutes % 60));
+    }
+    link
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_link() {
+        let start = Tz::Asia__Kolkata.ymd(2021, 3, 10).and_hms(9, 30, 0);
+
+        assert_eq!(
+            link(start, None, None),
+            "https://www.timeanddate.com/worldclock/fixedtime.html?iso=20210310T040000&p1=1440"
+        );
+        assert_eq!(
+            link(start, Some(Duration::minutes(90)), Some("Launch call")),
+            "https://www.timeanddate.com/worldclock/fixedtime.html?msg=Launch%20call\
+             &iso=20210310T040000&p1=1440&ah=1&am=30"
+        );
+    }
+}